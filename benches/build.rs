@@ -0,0 +1,40 @@
+//! `build` a large collection of machines.
+#![feature(test)]
+extern crate test;
+
+use pure_hfsm::prelude::*;
+use test::Bencher;
+
+type Builder = builder::StateMachines<Noop<()>, builder::Target>;
+
+/// 1000 machines of 20 states, each state going to the next one and entering
+/// the next machine
+fn builder() -> Builder {
+    let state = |machine: usize, i: usize| {
+        let transitions = vec![
+            builder::Target::Goto(((i + 1) % 20).to_string()),
+            builder::Target::Enter(((machine + 1) % 1000).to_string()),
+        ];
+        builder::State::new(i.to_string(), Noop::new(), transitions)
+    };
+    let machine = |m: usize| {
+        builder::StateMachine::new(m.to_string(), (0..20).map(|i| state(m, i)).collect())
+    };
+    builder::StateMachines((0..1000).map(machine).collect())
+}
+
+/// This includes cloning the builder, see the `clone` bench
+#[bench]
+fn build(b: &mut Bencher) {
+    let builder = builder();
+    b.iter(|| {
+        let machines: StateMachines<_, Always<()>> = builder.clone().build();
+        machines
+    });
+}
+
+#[bench]
+fn clone(b: &mut Bencher) {
+    let builder = builder();
+    b.iter(|| builder.clone());
+}
@@ -0,0 +1,72 @@
+//! `update` 10k entities whose state has 10 transitions that don't fire,
+//! stateless ones taking the fast path, compared to stateful ones.
+#![feature(test)]
+extern crate test;
+
+use pure_hfsm::prelude::*;
+use test::Bencher;
+
+/// The health of the entity
+type Health = u32;
+
+/// Fires when the health drops below the threshold
+struct Below {
+    threshold: Health,
+    target: Target,
+    stateless: bool,
+}
+impl Transition for Below {
+    type Context<'w, 's> = Health;
+
+    fn decide(&self, _: &mut StateData, _: &mut StateData, health: &Health) -> Target {
+        match *health < self.threshold {
+            true => self.target.clone(),
+            false => Target::Continue,
+        }
+    }
+    fn needs_data(&self) -> bool {
+        !self.stateless
+    }
+}
+/// `(threshold, target, stateless)`
+impl IntoTransition<Below> for (Health, builder::Target, bool) {
+    fn into_with(self, mapping: &NameMapping) -> Below {
+        let target = mapping.target(&self.1).expect("valid name");
+        Below {
+            threshold: self.0,
+            target,
+            stateless: self.2,
+        }
+    }
+}
+
+/// `Fight` with 10 transitions to `Flee`, none firing at full health
+fn machines(stateless: bool) -> StateMachines<Noop<Health>, Below> {
+    let flee = |i: Health| (i * 10, builder::Target::Goto("Flee".to_owned()), stateless);
+    let states = vec![
+        builder::State::new("Fight", Noop::new(), (1..=10).map(flee).collect()),
+        builder::State::new("Flee", Noop::new(), vec![]),
+    ];
+    builder::StateMachines(vec![builder::StateMachine::new("Guard", states)]).build()
+}
+
+fn update_10k(b: &mut Bencher, stateless: bool) {
+    let machines = machines(stateless);
+    let mut entities: Vec<_> = (0..10_000).map(|_| NestedMachine::new_active()).collect();
+    let mut health = 100;
+    b.iter(|| {
+        for nested in &mut entities {
+            let _ = nested.update(&machines, &mut health).unwrap();
+        }
+    });
+}
+
+#[bench]
+fn stateless(b: &mut Bencher) {
+    update_10k(b, true);
+}
+
+#[bench]
+fn stateful(b: &mut Bencher) {
+    update_10k(b, false);
+}
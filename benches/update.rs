@@ -0,0 +1,34 @@
+//! Compare `update` with `update_unchecked` on a validated `NestedMachine`.
+#![feature(test)]
+extern crate test;
+
+use pure_hfsm::prelude::*;
+use test::Bencher;
+
+/// A machine going through 100 states in a loop
+fn machines() -> StateMachines<Noop<()>, Always<()>> {
+    let state = |i: usize| {
+        builder::State::new(
+            i.to_string(),
+            Noop::new(),
+            vec![builder::Target::Goto(((i + 1) % 100).to_string())],
+        )
+    };
+    let machine = builder::StateMachine::new("Loop", (0..100).map(state).collect());
+    builder::StateMachines(vec![machine]).build()
+}
+
+#[bench]
+fn update(b: &mut Bencher) {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+    b.iter(|| nested.update(&machines, &mut ()).unwrap());
+}
+
+#[bench]
+fn update_unchecked(b: &mut Bencher) {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+    nested.validate_against(&machines).unwrap();
+    b.iter(|| nested.update_unchecked(&machines, &mut ()).unwrap());
+}
@@ -0,0 +1,298 @@
+//! Static analysis of state machines, based on [`DescribeTransition`]
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::slice;
+
+use crate::{
+    DescribeTransition, Error, SHandle, SHandleInner, SmHandle, SmHandleInner, StateMachines,
+    Target,
+};
+
+/// Tarjan's strongly connected components algorithm
+struct Components<'a> {
+    edges: &'a [Vec<usize>],
+    index: Vec<Option<usize>>,
+    low_link: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    components: Vec<Vec<usize>>,
+}
+impl<'a> Components<'a> {
+    fn of(edges: &'a [Vec<usize>]) -> Vec<Vec<usize>> {
+        let mut this = Components {
+            edges,
+            index: vec![None; edges.len()],
+            low_link: vec![0; edges.len()],
+            on_stack: vec![false; edges.len()],
+            stack: Vec::new(),
+            next_index: 0,
+            components: Vec::new(),
+        };
+        for node in 0..edges.len() {
+            if this.index[node].is_none() {
+                this.visit(node);
+            }
+        }
+        this.components
+    }
+    fn visit(&mut self, node: usize) {
+        self.index[node] = Some(self.next_index);
+        self.low_link[node] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(node);
+        self.on_stack[node] = true;
+        for &next in &self.edges[node] {
+            match self.index[next] {
+                None => {
+                    self.visit(next);
+                    self.low_link[node] = self.low_link[node].min(self.low_link[next]);
+                }
+                Some(index) if self.on_stack[next] => {
+                    self.low_link[node] = self.low_link[node].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+        if Some(self.low_link[node]) == self.index[node] {
+            let mut component = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack[member] = false;
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            component.reverse();
+            self.components.push(component);
+        }
+    }
+}
+
+impl<B, T: DescribeTransition> crate::State<B, T> {
+    /// All the targets this state may fire, including its default
+    /// transition and timeout
+    fn targets(&self) -> impl Iterator<Item = Target> + '_ {
+        let timeout = self.timeout.as_ref().map(|(_, target)| target.clone());
+        let transitions = self.all_transitions().flat_map(T::targets);
+        transitions.chain(timeout)
+    }
+}
+
+impl<B, T: DescribeTransition> StateMachines<B, T> {
+    /// For each machine, the machines it may directly `Enter`, without duplicates
+    fn enter_edges(&self) -> Vec<Vec<usize>> {
+        let machine_edges = |machine: &crate::StateMachine<B, T>| {
+            let mut edges = Vec::new();
+            for target in machine.states.iter().flat_map(crate::State::targets) {
+                if let Target::Enter(SmHandle(entered))
+                | Target::EnterOnce(SmHandle(entered))
+                | Target::EnterAt(SmHandle(entered), _) = target
+                {
+                    let entered = entered as usize;
+                    if !edges.contains(&entered) {
+                        edges.push(entered);
+                    }
+                }
+            }
+            // A transition may refer to machines out of this collection
+            edges.retain(|entered| *entered < self.machines.len());
+            edges
+        };
+        self.machines.iter().map(machine_edges).collect()
+    }
+
+    /// For each machine, the machines any of its states may directly
+    /// [`Target::Enter`]
+    ///
+    /// This is the call graph of the machines, the reverse of
+    /// [`StateMachines::entered_by`] at the machine level. All machines are
+    /// listed, in handle order, even those that enter no machine. Entered
+    /// machines are listed once, in the order they first appear in the
+    /// machine's states and transitions. [`Target::EnterOnce`] and
+    /// [`Target::EnterAt`] count as entering, targets out of this collection
+    /// are ignored.
+    pub fn enter_graph(&self) -> Vec<(SmHandle, Vec<SmHandle>)> {
+        let to_handle = |i: usize| SmHandle(i as SmHandleInner);
+        let to_entry = |(i, edges): (usize, Vec<usize>)| {
+            (to_handle(i), edges.into_iter().map(to_handle).collect())
+        };
+        self.enter_edges()
+            .into_iter()
+            .enumerate()
+            .map(to_entry)
+            .collect()
+    }
+
+    /// Groups of machines that may `Enter` each other recursively
+    ///
+    /// Each group is a set of machines where any machine can eventually be
+    /// entered again from itself, which would grow the
+    /// [`label::NestedMachine`](crate::label::NestedMachine) stack without
+    /// bounds. A machine that may enter itself directly is a group of one.
+    /// This is almost always an authoring mistake.
+    pub fn enter_cycles(&self) -> Vec<Vec<SmHandle>> {
+        let edges = self.enter_edges();
+        let is_cycle = |component: &Vec<usize>| match component[..] {
+            [single] => edges[single].contains(&single),
+            _ => true,
+        };
+        let to_handles = |component: Vec<usize>| {
+            let to_handle = |i| SmHandle(i as SmHandleInner);
+            component.into_iter().map(to_handle).collect()
+        };
+        Components::of(&edges)
+            .into_iter()
+            .filter(is_cycle)
+            .map(to_handles)
+            .collect()
+    }
+
+    /// Call `f` on each state reachable from the initial states of `machine`,
+    /// in breadth-first order
+    ///
+    /// `f` is called once per state with its machine, its handle, its
+    /// behavior and its transitions. A state is reachable if a chain of
+    /// [`Target::Goto`] leads to it, or if it is the initial state of a region
+    /// of a machine reachable with [`Target::Enter`], or the state of a
+    /// [`Target::EnterAt`]. The targets of transitions are read with
+    /// [`DescribeTransition::targets`], along with the target of the state's
+    /// timeout, targets out of this collection are ignored. Does nothing if
+    /// `machine` is not part of this collection.
+    pub fn walk_reachable(
+        &self,
+        machine: &SmHandle,
+        mut f: impl FnMut(SmHandle, SHandle, &B, &[T]),
+    ) {
+        let f = |machine, handle, state: &crate::State<B, T>| {
+            f(machine, handle, &state.behavior, &state.transitions);
+        };
+        self.walk(slice::from_ref(machine), f);
+    }
+    /// Like [`StateMachines::walk_reachable`], from several `roots` machines
+    fn walk(&self, roots: &[SmHandle], mut f: impl FnMut(SmHandle, SHandle, &crate::State<B, T>)) {
+        let mut visited: Vec<Vec<bool>> = self
+            .machines
+            .iter()
+            .map(|machine| vec![false; machine.states.len()])
+            .collect();
+        let mut queue = VecDeque::new();
+        let mut visit = |machine: &SmHandle, state: &SHandle, queue: &mut VecDeque<_>| {
+            let slot = visited
+                .get_mut(machine.0 as usize)
+                .and_then(|states| states.get_mut(state.0 as usize));
+            if let Some(slot) = slot.filter(|visited| !**visited) {
+                *slot = true;
+                queue.push_back((machine.clone(), state.clone()));
+            }
+        };
+        // Entering a machine starts all its regions
+        let regions =
+            |machine: &SmHandle| self.machine(machine).map_or(&[][..], |m| &m.regions[..]);
+        for machine in roots {
+            for state in regions(machine) {
+                visit(machine, state, &mut queue);
+            }
+        }
+        while let Some((machine_handle, state_handle)) = queue.pop_front() {
+            let state = &self.machines[machine_handle.0 as usize].states[state_handle.0 as usize];
+            f(machine_handle.clone(), state_handle, state);
+            for target in state.targets() {
+                match target {
+                    Target::Goto(next) => visit(&machine_handle, &next, &mut queue),
+                    Target::Enter(entered) | Target::EnterOnce(entered) => {
+                        for state in regions(&entered) {
+                            visit(&entered, state, &mut queue);
+                        }
+                    }
+                    Target::EnterAt(entered, first) => {
+                        visit(&entered, &first, &mut queue);
+                        for state in regions(&entered).iter().skip(1) {
+                            visit(&entered, state, &mut queue);
+                        }
+                    }
+                    Target::Continue | Target::Complete | Target::PopN(_) => {}
+                }
+            }
+        }
+    }
+
+    /// Check that no reachable state may fire a target that doesn't exist
+    ///
+    /// This walks the states reachable from each machine like
+    /// [`StateMachines::walk_reachable`], and returns an error for each
+    /// target of their transitions and timeout that doesn't resolve:
+    /// [`Error::BadStateName`] for a [`Target::Goto`] to a state not in the
+    /// machine, [`Error::BadMachineName`] for a [`Target::Enter`] of a
+    /// machine not in this collection. The interrupts are checked too, their
+    /// `Goto` against the first machine, where
+    /// [`label::NestedMachine::new_active`](crate::label::NestedMachine::new_active)
+    /// starts.
+    ///
+    /// A `StateMachines` built with [`crate::builder::StateMachines::build`]
+    /// only fails this if some transitions were created another way, such as
+    /// with [`StateMachines::map_transitions`]. When this returns `Ok`,
+    /// [`label::NestedMachine::update`](crate::label::NestedMachine::update)
+    /// never returns a lookup error for machines entered by name or handle.
+    pub fn verify_runnable(&self) -> Result<(), Vec<Error>> {
+        let mut errors = Vec::new();
+        let mut check = |machine: &SmHandle, target: Target| match target {
+            Target::Goto(state) if self.state_name(machine, &state).is_none() => {
+                let machine = machine.clone();
+                errors.push(Error::BadStateName { machine, state });
+            }
+            Target::Enter(entered) | Target::EnterOnce(entered) | Target::EnterAt(entered, _)
+                if self.machine(&entered).is_none() =>
+            {
+                errors.push(Error::BadMachineName(entered));
+            }
+            Target::EnterAt(machine, state) if self.state_name(&machine, &state).is_none() => {
+                errors.push(Error::BadStateName { machine, state });
+            }
+            _ => {}
+        };
+        let first = SmHandle(0);
+        for target in self.interrupts.iter().flat_map(T::targets) {
+            check(&first, target);
+        }
+        let roots: Vec<_> = (0..self.machines.len())
+            .map(|i| SmHandle(i as SmHandleInner))
+            .collect();
+        self.walk(&roots, |machine, _, state| {
+            for target in state.targets() {
+                check(&machine, target);
+            }
+        });
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
+    /// Every state whose transitions may [`Target::Enter`] `target`, as
+    /// `(machine, state)` pairs
+    ///
+    /// This is the reverse of following `Enter` edges, use it to find all
+    /// the callers of a shared machine. [`Target::EnterOnce`] and
+    /// [`Target::EnterAt`] count as entering. Pairs are in handle order, each
+    /// state appears once even if several of its transitions enter `target`.
+    pub fn entered_by(&self, target: &SmHandle) -> Vec<(SmHandle, SHandle)> {
+        let enters = |decided: Target| match decided {
+            Target::Enter(entered) | Target::EnterOnce(entered) | Target::EnterAt(entered, _) => {
+                entered == *target
+            }
+            _ => false,
+        };
+        let mut ret = Vec::new();
+        for (mi, machine) in self.machines.iter().enumerate() {
+            for (si, state) in machine.states.iter().enumerate() {
+                if state.targets().any(enters) {
+                    let handles = (SmHandle(mi as SmHandleInner), SHandle(si as SHandleInner));
+                    ret.push(handles);
+                }
+            }
+        }
+        ret
+    }
+}
@@ -0,0 +1,119 @@
+//! Ready-made [`Behavior`]s
+//!
+//! Like most of [`crate::transition`], they work with any context type `C`
+//! that does not borrow. [`Completable`] wraps another behavior and has the
+//! same context.
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+
+use crate::{Behavior, StateData};
+
+/// Does nothing
+///
+/// For states that only wait for one of their transitions to fire.
+pub struct Noop<C>(PhantomData<fn(&mut C)>);
+impl<C> Noop<C> {
+    pub fn new() -> Self {
+        Noop(PhantomData)
+    }
+}
+impl<C> Default for Noop<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<C> Clone for Noop<C> {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+impl<C> Behavior for Noop<C> {
+    type Context<'w, 's> = C;
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &mut C) {}
+}
+
+/// A [`Behavior`] that knows when it is done, see [`Completable`]
+pub trait CompletableBehavior: Behavior {
+    /// Whether the behavior finished its work, given its `data` after an
+    /// update
+    fn is_done(&self, data: &StateData) -> bool;
+}
+
+/// The data of a [`Completable`] state, seen by its transitions
+///
+/// `data` is the wrapped behavior's own data.
+pub struct Completion {
+    pub data: StateData,
+    /// Whether [`CompletableBehavior::is_done`] after the last update
+    pub done: bool,
+}
+
+/// Shares whether `B` is done with the transitions of its state
+///
+/// After each update of `B`, its [`CompletableBehavior::is_done`] is stored
+/// in a [`Completion`], which the transitions get as `data`: a `Completable`
+/// always [shares its data](Behavior::shares_data).
+/// [`crate::transition::BehaviorDone`] fires once it is done. The other
+/// methods of `B` get the wrapped behavior's own data, as usual.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Completable<B>(pub B);
+impl<B> Completable<B> {
+    fn completion(data: &mut StateData) -> &mut Completion {
+        data.downcast_mut()
+            .expect("the data of a `Completable` is a `Completion`")
+    }
+}
+impl<B: CompletableBehavior> Behavior for Completable<B> {
+    type Context<'w, 's> = B::Context<'w, 's>;
+
+    fn update<'w, 's>(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        ctx: &mut Self::Context<'w, 's>,
+    ) {
+        let completion = Self::completion(data);
+        self.0.update(&mut completion.data, machine_data, ctx);
+        completion.done = self.0.is_done(&completion.data);
+    }
+    fn default_data(&self) -> StateData {
+        let data = self.0.default_data();
+        Box::new(Completion { data, done: false })
+    }
+    fn on_enter<'w, 's>(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        ctx: &mut Self::Context<'w, 's>,
+    ) {
+        self.0
+            .on_enter(&mut Self::completion(data).data, machine_data, ctx);
+    }
+    fn shares_data(&self) -> bool {
+        true
+    }
+    fn transitions_first(&self) -> bool {
+        self.0.transitions_first()
+    }
+    fn can_exit<'w, 's>(&self, data: &StateData, ctx: &Self::Context<'w, 's>) -> bool {
+        match data.downcast_ref::<Completion>() {
+            Some(completion) => self.0.can_exit(&completion.data, ctx),
+            None => self.0.can_exit(data, ctx),
+        }
+    }
+    fn result(&self, data: &mut StateData, machine_data: &mut StateData) -> Option<StateData> {
+        self.0
+            .result(&mut Self::completion(data).data, machine_data)
+    }
+    fn on_result<'w, 's>(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        result: StateData,
+        ctx: &mut Self::Context<'w, 's>,
+    ) {
+        let data = &mut Self::completion(data).data;
+        self.0.on_result(data, machine_data, result, ctx);
+    }
+}
@@ -0,0 +1,153 @@
+//! Integration with the bevy game engine
+//!
+//! With the `bevy` feature, [`StateMachines`] is an [`Asset`] and
+//! [`NestedMachine`] a [`Component`]: load the state machines as an asset,
+//! shared by all entities, and store the running state of each entity in its
+//! `NestedMachine` component.
+//!
+//! [`StateMachinesLoader`] loads `.hfsm.ron` files as [`builder::StateMachines`]
+//! and builds them, add it to your app with [`StateMachinesPlugin`].
+//!
+//! Note that a glob import of this crate (`use pure_hfsm::*`) brings this
+//! module in scope under the name `bevy`, which conflicts with the `bevy`
+//! crate. Import the items you need by name instead.
+use alloc::format;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use bevy::app::{App, Plugin};
+use bevy::asset::io::Reader;
+use bevy::asset::VisitAssetDependencies;
+use bevy::asset::{Asset, AssetApp, AssetLoader, LoadContext, UntypedAssetId};
+use bevy::ecs::component::{Component, Mutable, StorageType};
+use bevy::reflect::utility::GenericTypePathCell;
+use bevy::reflect::TypePath;
+use serde::de::DeserializeOwned;
+
+use crate::builder::{self, IntoTransition};
+use crate::label::NestedMachine;
+use crate::{StateMachines, Transition};
+
+impl<B: TypePath, T: TypePath> TypePath for StateMachines<B, T> {
+    fn type_path() -> &'static str {
+        static CELL: GenericTypePathCell = GenericTypePathCell::new();
+        CELL.get_or_insert::<Self, _>(|| {
+            let (b, t) = (B::type_path(), T::type_path());
+            format!("pure_hfsm::StateMachines<{b}, {t}>")
+        })
+    }
+    fn short_type_path() -> &'static str {
+        static CELL: GenericTypePathCell = GenericTypePathCell::new();
+        CELL.get_or_insert::<Self, _>(|| {
+            let (b, t) = (B::short_type_path(), T::short_type_path());
+            format!("StateMachines<{b}, {t}>")
+        })
+    }
+    fn type_ident() -> Option<&'static str> {
+        Some("StateMachines")
+    }
+    fn crate_name() -> Option<&'static str> {
+        Some("pure_hfsm")
+    }
+    fn module_path() -> Option<&'static str> {
+        Some("pure_hfsm")
+    }
+}
+impl<B, T> VisitAssetDependencies for StateMachines<B, T> {
+    fn visit_dependencies(&self, _visit: &mut impl FnMut(UntypedAssetId)) {}
+}
+impl<B, T> Asset for StateMachines<B, T>
+where
+    B: TypePath + Send + Sync + 'static,
+    T: TypePath + Send + Sync + 'static,
+{
+}
+
+impl Component for NestedMachine {
+    const STORAGE_TYPE: StorageType = StorageType::Table;
+    type Mutability = Mutable;
+}
+
+/// Error while loading a `.hfsm.ron` file
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    Build(builder::UnknownTarget),
+}
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "could not read state machines: {err}"),
+            LoadError::Ron(err) => write!(f, "invalid state machines: {err}"),
+            LoadError::Build(err) => write!(f, "invalid state machines: {err}"),
+        }
+    }
+}
+impl std::error::Error for LoadError {}
+
+/// Holds the type parameters of the loader and plugin, while being `Send`
+/// and `Sync` regardless of them
+type Marker<B, T, Trs> = PhantomData<fn() -> (B, T, Trs)>;
+
+/// Load `.hfsm.ron` files into `StateMachines<B, Trs>`
+///
+/// The files are deserialized as `builder::StateMachines<B, T>`, which are
+/// then [`try_build`](builder::StateMachines::try_build).
+pub struct StateMachinesLoader<B, T, Trs>(Marker<B, T, Trs>);
+impl<B, T, Trs> Default for StateMachinesLoader<B, T, Trs> {
+    fn default() -> Self {
+        StateMachinesLoader(PhantomData)
+    }
+}
+impl<B, T, Trs> AssetLoader for StateMachinesLoader<B, T, Trs>
+where
+    B: DeserializeOwned + TypePath + Send + Sync + 'static,
+    T: DeserializeOwned + IntoTransition<Trs> + 'static,
+    Trs: Transition + TypePath + Send + Sync + 'static,
+{
+    type Asset = StateMachines<B, Trs>;
+    type Settings = ();
+    type Error = LoadError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(LoadError::Io)?;
+        let description: builder::StateMachines<B, T> =
+            ron::de::from_bytes(&bytes).map_err(LoadError::Ron)?;
+        description.try_build().map_err(LoadError::Build)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["hfsm.ron"]
+    }
+}
+
+/// Register `StateMachines<B, Trs>` as an asset, loaded with
+/// [`StateMachinesLoader<B, T, Trs>`]
+pub struct StateMachinesPlugin<B, T, Trs>(Marker<B, T, Trs>);
+impl<B, T, Trs> Default for StateMachinesPlugin<B, T, Trs> {
+    fn default() -> Self {
+        StateMachinesPlugin(PhantomData)
+    }
+}
+impl<B, T, Trs> Plugin for StateMachinesPlugin<B, T, Trs>
+where
+    B: DeserializeOwned + TypePath + Send + Sync + 'static,
+    T: DeserializeOwned + IntoTransition<Trs> + 'static,
+    Trs: Transition + TypePath + Send + Sync + 'static,
+{
+    fn build(&self, app: &mut App) {
+        app.init_asset::<StateMachines<B, Trs>>()
+            .register_asset_loader(StateMachinesLoader::<B, T, Trs>::default());
+    }
+}
@@ -14,11 +14,21 @@
 //! the [`StateMachines::build`] method, you can use it with
 //! [`label::NestedMachine`](crate::label::NestedMachine) to manage a state
 //! machine.
-use ahash::AHashMap;
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use core::hash::BuildHasher;
+use core::time::Duration;
+
+#[cfg(feature = "serde")]
 use serde::Deserialize;
 use smallvec::SmallVec;
 
-use crate::{SHandle, SHandleInner, SmHandle, SmHandleInner};
+use crate::{HashMap, SHandle, SHandleInner, SmHandle, SmHandleInner};
 
 /// Convert `Self` into something that implements [`crate::Transition`]
 ///
@@ -32,6 +42,115 @@ pub trait IntoTransition<T> {
     fn into_with(self, mapping: &NameMapping) -> T;
 }
 
+/// Convert a [`crate::Transition`] back into `Self`, the reverse of [`IntoTransition`]
+///
+/// Use the [`HandleMapping`] to get back the serialized [`Target`] of
+/// [`crate::Target`]s stored in the compiled transition.
+pub trait FromTransition<T>: Sized {
+    /// Convert `transition` into `Self`, `None` if it is not possible
+    fn from_with(transition: &T, mapping: &HandleMapping) -> Option<Self>;
+}
+
+/// Obtain a serialized [`Target`] from a [`crate::Target`]
+///
+/// This is the reverse of [`NameMapping`], for a single machine: it is scoped
+/// to the machine the converted transitions are part of.
+pub struct HandleMapping<'a> {
+    machine_names: &'a [String],
+    state_names: &'a [String],
+    /// State names of all machines, for [`crate::Target::EnterAt`]
+    all_state_names: &'a [Vec<String>],
+}
+impl<'a> HandleMapping<'a> {
+    /// Get the [`Target`] corresponding to this [`crate::Target`]
+    ///
+    /// `None` if the target refers to a non-existing state or machine, or is
+    /// a [`crate::Target::Continue`], which doesn't have a serialized form.
+    pub fn target(&self, target: &crate::Target) -> Option<Target> {
+        match target {
+            crate::Target::Goto(state) => Some(Target::Goto(self.state_name(state)?.to_owned())),
+            crate::Target::Enter(machine) => {
+                Some(Target::Enter(self.machine_name(machine)?.to_owned()))
+            }
+            crate::Target::EnterOnce(machine) => {
+                Some(Target::EnterOnce(self.machine_name(machine)?.to_owned()))
+            }
+            crate::Target::EnterAt(machine, state) => {
+                let states = self.all_state_names.get(machine.0 as usize)?;
+                let state = states.get(state.0 as usize)?;
+                let machine = self.machine_name(machine)?;
+                Some(Target::EnterAt(machine.to_owned(), state.clone()))
+            }
+            crate::Target::Complete => Some(Target::End),
+            crate::Target::PopN(n) => Some(Target::PopN(*n)),
+            crate::Target::Continue => None,
+        }
+    }
+    /// Name of `state` in the current machine
+    pub fn state_name(&self, state: &SHandle) -> Option<&'a str> {
+        self.state_names.get(state.0 as usize).map(String::as_ref)
+    }
+    /// Name of `machine`
+    pub fn machine_name(&self, machine: &SmHandle) -> Option<&'a str> {
+        self.machine_names
+            .get(machine.0 as usize)
+            .map(String::as_ref)
+    }
+}
+
+/// A transition [`FromTransition::from_with`] couldn't convert
+#[derive(Debug)]
+pub struct UnconvertibleTransition {
+    /// Name of the machine the transition is in
+    pub machine: String,
+    /// Name of the state the transition is in
+    pub state: String,
+    /// Index of the transition in the state, see [`State::default`] for the
+    /// index of the default transition
+    pub index: usize,
+}
+
+/// An id used in a [`StateMachines`] is missing from the registry passed to
+/// `StateMachines::build_with_registry`, or a timeout target doesn't exist
+#[derive(Debug)]
+pub enum UnknownId {
+    /// The behavior of a state or the [`StateMachine::on_complete`] of a
+    /// machine is not in the behavior registry
+    Behavior(String),
+    /// A transition or the [`State::default`] of a state is not in the
+    /// transition registry
+    Transition(String),
+    /// A [`State::timeout`] refers to a state or machine that doesn't exist,
+    /// see [`StateMachines::try_build`]
+    Timeout(UnknownTarget),
+}
+
+/// A [`State::timeout`] refers to a state or machine that doesn't exist,
+/// see [`StateMachines::try_build`]
+#[derive(Debug)]
+pub struct UnknownTarget {
+    /// Name of the machine of the state with the timeout
+    pub machine: String,
+    /// Name of the state with the timeout
+    pub state: String,
+    /// The target of the timeout
+    pub target: Target,
+}
+impl fmt::Display for UnknownTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let UnknownTarget {
+            machine,
+            state,
+            target,
+        } = self;
+        write!(
+            f,
+            "the timeout of {machine}/{state} refers to a state or machine that doesn't exist: \
+            {target}"
+        )
+    }
+}
+
 /// Obtain a [`Target`](crate::Target) based on serialized state and machine names
 ///
 /// Use the [`NameMapping::target`], [`NameMapping::goto`] and [`NameMapping::enter`]
@@ -41,15 +160,28 @@ pub trait IntoTransition<T> {
 ///
 /// The names correspond to the ones you provided in [`State`] and [`StateMachine`]
 /// `name` fields.
+///
+/// [`StateMachines::build_with_mapping`] returns the `NameMapping` used to
+/// build the machines, to look up handles by name afterward, or to build
+/// more machines referring to them with [`StateMachines::build_after`].
+#[derive(Debug, Clone)]
 pub struct NameMapping {
-    state_names: AHashMap<String, SHandleInner>,
-    machine_names: AHashMap<String, SmHandleInner>,
+    state_names: HashMap<String, SHandleInner>,
+    machine_names: HashMap<String, SmHandleInner>,
+    /// State names scoped to their machine, indexed by machine handle, for
+    /// [`Target::EnterAt`]
+    machine_states: Vec<HashMap<String, SHandleInner>>,
+    /// How many machines were built with this mapping, the handle of the
+    /// next machine to build
+    machine_count: usize,
 }
 impl NameMapping {
-    fn new() -> Self {
+    fn with_capacity(machines: usize, states: usize) -> Self {
         NameMapping {
-            state_names: AHashMap::new(),
-            machine_names: AHashMap::new(),
+            state_names: HashMap::with_capacity_and_hasher(states, Default::default()),
+            machine_names: HashMap::with_capacity_and_hasher(machines, Default::default()),
+            machine_states: Vec::with_capacity(machines),
+            machine_count: 0,
         }
     }
     /// Get [`crate::Target`] corresponding to this [`Target`]
@@ -57,7 +189,10 @@ impl NameMapping {
         match target {
             Target::Goto(name) => self.goto(name),
             Target::Enter(name) => self.enter(name),
+            Target::EnterOnce(name) => self.enter_once(name),
+            Target::EnterAt(machine, state) => self.enter_at(machine, state),
             Target::End => Some(crate::Target::Complete),
+            Target::PopN(n) => Some(crate::Target::PopN(*n)),
         }
     }
     /// Get a [`crate::Target::Goto`] pointing to `State` named `name`
@@ -70,29 +205,210 @@ impl NameMapping {
         let target = self.machine_names.get(name)?;
         Some(crate::Target::Enter(SmHandle(*target)))
     }
+    /// Get a [`crate::Target::EnterOnce`] pointing to `StateMachine` named `name`
+    pub fn enter_once(&self, name: &str) -> Option<crate::Target> {
+        let target = self.machine_names.get(name)?;
+        Some(crate::Target::EnterOnce(SmHandle(*target)))
+    }
+    /// Get a [`crate::Target::EnterAt`] pointing to the `State` named `state`
+    /// of the `StateMachine` named `machine`
+    ///
+    /// Unlike [`NameMapping::goto`], `state` is looked up in `machine` only.
+    pub fn enter_at(&self, machine: &str, state: &str) -> Option<crate::Target> {
+        let machine = *self.machine_names.get(machine)?;
+        let state = self.machine_states.get(machine as usize)?.get(state)?;
+        Some(crate::Target::EnterAt(SmHandle(machine), SHandle(*state)))
+    }
+    /// All machine names with their handle, in arbitrary order
+    pub fn machines<'a>(&'a self) -> impl Iterator<Item = (&'a str, SmHandle)> + 'a {
+        let to_pair =
+            |(name, handle): (&'a String, &SmHandleInner)| (name.as_str(), SmHandle(*handle));
+        self.machine_names.iter().map(to_pair)
+    }
+    /// All state names with their handle, in arbitrary order
+    ///
+    /// State names are not scoped to their machine: when several machines
+    /// have a state with the same name, this is the handle of the state in
+    /// the last of those machines, which is also what [`NameMapping::goto`]
+    /// returns.
+    pub fn states<'a>(&'a self) -> impl Iterator<Item = (&'a str, SHandle)> + 'a {
+        let to_pair =
+            |(name, handle): (&'a String, &SHandleInner)| (name.as_str(), SHandle(*handle));
+        self.state_names.iter().map(to_pair)
+    }
 }
 
 /// Convenience enum for serialized state machines
 ///
 /// Pass this enum to the [`NameMapping::target`] method to get the corresponding
 /// [`crate::Target`] needed to implement the [`crate::Transition`] trait.
-#[derive(Deserialize)]
+///
+/// Variants can also be spelled in lowercase, so that both the RON-style
+/// `Goto("Idle")` and JSON-style `{ "goto": "Idle" }` forms are accepted.
+/// `End` may also be spelled `Complete`, to match [`crate::Target::Complete`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
 pub enum Target {
+    #[cfg_attr(feature = "serde", serde(alias = "goto"))]
     Goto(String),
+    #[cfg_attr(feature = "serde", serde(alias = "enter"))]
     Enter(String),
+    #[cfg_attr(feature = "serde", serde(alias = "enter_once"))]
+    EnterOnce(String),
+    /// Enter the machine named by the first field at the state named by
+    /// the second, see [`crate::Target::EnterAt`]
+    #[cfg_attr(feature = "serde", serde(alias = "enter_at"))]
+    EnterAt(String, String),
+    #[cfg_attr(
+        feature = "serde",
+        serde(alias = "end", alias = "Complete", alias = "complete")
+    )]
     End,
+    /// See [`crate::Target::PopN`], this doesn't refer to any name
+    #[cfg_attr(feature = "serde", serde(alias = "pop_n"))]
+    PopN(u8),
+}
+
+/// Same as the `Display` of [`crate::Target`], with names instead of
+/// handles, such as `goto:Idle`
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Goto(state) => write!(f, "goto:{state}"),
+            Target::Enter(machine) => write!(f, "enter:{machine}"),
+            Target::EnterOnce(machine) => write!(f, "enter_once:{machine}"),
+            Target::EnterAt(machine, state) => write!(f, "enter_at:{machine}:{state}"),
+            Target::End => f.write_str("complete"),
+            Target::PopN(n) => write!(f, "pop_n:{n}"),
+        }
+    }
 }
 
+/// A single state, with its behavior and transitions
+///
+/// In human readable formats, the name of the state can be replaced by a map
+/// to set the other fields, for example `{ "name": "Aim", "region": 1 }` in
+/// JSON or `(name: "Aim", region: 1)` in RON.
+#[derive(Clone)]
 pub struct State<B, T> {
     pub name: String,
     pub behavior: B,
     pub transitions: Vec<T>,
+    /// The parallel region this state is part of, `0` by default
+    ///
+    /// Each region of a machine has its own active state, all are updated
+    /// every tick, in the order regions first appear in the machine. The
+    /// first state of a region is its initial state. A [`Target::Goto`] only
+    /// changes the active state of the region it was returned from, and
+    /// should target a state of the same region.
+    ///
+    /// When a region `Enter`s a nested machine, the whole machine is
+    /// suspended (the following regions are not updated this tick) until the
+    /// nested machine completes. When a region completes, it stops running
+    /// while the other regions keep running, the machine completes once all
+    /// its regions completed.
+    pub region: u8,
+    /// Fire the target once the state ran for this long, `None` by default
+    ///
+    /// The time a state ran is only counted by
+    /// [`label::NestedMachine::update_timed`](crate::label::NestedMachine::update_timed),
+    /// see there. The timeout is checked last, after all of the state's
+    /// `transitions` and its `default` didn't fire, so it acts like a last
+    /// transition. Its index, as in [`crate::label::TransitionStats`], is
+    /// right after the `default`: the number of `transitions`, plus one if
+    /// the state has a `default`.
+    ///
+    /// In human readable formats, set it in the map of the state's name as
+    /// `"timeout": [seconds, target]`, for example
+    /// `{ "name": "Search", "timeout": [10.0, { "goto": "Patrol" }] }`.
+    ///
+    /// # Panics
+    ///
+    /// [`StateMachines::build`] panics if the target refers to a state or
+    /// machine that doesn't exist, use [`StateMachines::try_build`] to get an
+    /// error instead.
+    pub timeout: Option<(Duration, Target)>,
+    /// The transition evaluated when none of `transitions` fired, `None` by
+    /// default
+    ///
+    /// This is the "else" of the state: it is evaluated after all of
+    /// `transitions`, whatever their order, and before the `timeout`. Its
+    /// index, as in [`crate::label::TransitionStats`] or
+    /// [`label::NestedMachine::set_transition_enabled`](crate::label::NestedMachine::set_transition_enabled),
+    /// is the number of `transitions`, the index of the `timeout` comes
+    /// after it.
+    ///
+    /// In human readable formats, set it in the map of the state's name as
+    /// `"default": transition`, for example
+    /// `{ "name": "Idle", "default": { "goto": "Patrol" } }`.
+    pub default: Option<T>,
+}
+impl<B, T> State<B, T> {
+    /// A state in region `0`, without timeout nor default transition
+    ///
+    /// Set the other fields with the struct update syntax, for example
+    /// `State { region: 1, ..State::new("Aim", behavior, transitions) }`, to
+    /// keep compiling when new fields are added.
+    pub fn new(name: impl Into<String>, behavior: B, transitions: Vec<T>) -> Self {
+        State {
+            name: name.into(),
+            behavior,
+            transitions,
+            region: 0,
+            timeout: None,
+            default: None,
+        }
+    }
 }
 
 /// A single state machine which states can refer to each other by [`String`] name
+///
+/// This is `Clone` when `B` and `T` are, to use a machine as a template for
+/// several variants.
+///
+/// In human readable formats, the name of the machine can be replaced by a
+/// map to set its metadata, for example
+/// `{ "name": "Guard", "metadata": { "author": "Nicola" } }` in JSON.
+#[derive(Clone)]
 pub struct StateMachine<B, T> {
     pub name: String,
     pub states: Vec<State<B, T>>,
+    /// Information about the machine that doesn't affect how it runs, such
+    /// as its category or author, empty by default
+    ///
+    /// This is kept in the [`crate::StateMachines`], see
+    /// [`crate::StateMachines::machine_metadata`].
+    pub metadata: BTreeMap<String, String>,
+    /// A behavior updated once when the machine completes, `None` by
+    /// default
+    ///
+    /// This runs when the last running region of the machine returns
+    /// [`Target::End`], or a state of the machine returns a [`Target::PopN`]
+    /// of at least one machine, right before the machine is popped from the
+    /// stack. It comes after the [`crate::Behavior::result`] of the state
+    /// that completed the machine, and before the
+    /// [`crate::Behavior::on_result`] of the state that entered it. It gets
+    /// a fresh `data` from its [`crate::Behavior::default_data`] and the
+    /// machine's `machine_data`, to conclude what the states of the machine
+    /// did. It doesn't run when the machine is left otherwise: popped as an
+    /// outer machine of a `PopN`, by an interrupt or by
+    /// [`NestedMachine::pop`](crate::label::NestedMachine::pop).
+    ///
+    /// In human readable formats, set it in the map of the machine's name
+    /// as `"on_complete": behavior`.
+    pub on_complete: Option<B>,
+}
+impl<B, T> StateMachine<B, T> {
+    /// A machine without metadata nor `on_complete` behavior, see
+    /// [`State::new`] to set the other fields
+    pub fn new(name: impl Into<String>, states: Vec<State<B, T>>) -> Self {
+        StateMachine {
+            name: name.into(),
+            states,
+            metadata: BTreeMap::new(),
+            on_complete: None,
+        }
+    }
 }
 
 /// Multiple state machines that may refer each other by [`String`] name
@@ -100,61 +416,538 @@ pub struct StateMachine<B, T> {
 /// Use [`StateMachines::build`] to get a [`crate::StateMachines`] usable with
 /// [`label::NestedMachine`](crate::label::NestedMachine) for an efficient
 /// state machine. `T` must implement [`IntoTransition`].
-#[derive(Deserialize)]
-#[serde(transparent)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Deserialize), serde(transparent))]
 pub struct StateMachines<B, T>(pub Vec<StateMachine<B, T>>);
 
+impl<B, T> IntoIterator for StateMachines<B, T> {
+    type Item = StateMachine<B, T>;
+    type IntoIter = alloc::vec::IntoIter<StateMachine<B, T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+impl<'a, B, T> IntoIterator for &'a StateMachines<B, T> {
+    type Item = &'a StateMachine<B, T>;
+    type IntoIter = core::slice::Iter<'a, StateMachine<B, T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+impl<'a, B, T> IntoIterator for &'a mut StateMachines<B, T> {
+    type Item = &'a mut StateMachine<B, T>;
+    type IntoIter = core::slice::IterMut<'a, StateMachine<B, T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
+impl<B, T> FromIterator<StateMachine<B, T>> for StateMachines<B, T> {
+    fn from_iter<I: IntoIterator<Item = StateMachine<B, T>>>(iter: I) -> Self {
+        StateMachines(iter.into_iter().collect())
+    }
+}
+impl<B, T> Extend<StateMachine<B, T>> for StateMachines<B, T> {
+    fn extend<I: IntoIterator<Item = StateMachine<B, T>>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+impl<B, T> Default for StateMachines<B, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl<B, T> StateMachines<B, T> {
+    /// An empty collection of state machines
+    pub fn new() -> Self {
+        StateMachines(Vec::new())
+    }
+    /// An empty collection with room for `machines` state machines
+    ///
+    /// Use this when adding many machines with
+    /// [`StateMachines::push_machine`], for example when loading them from
+    /// separate files.
+    pub fn with_capacity(machines: usize) -> Self {
+        StateMachines(Vec::with_capacity(machines))
+    }
+    /// Add a state machine to this collection
+    ///
+    /// The first machine added is the one [`label::NestedMachine::new_active`]
+    /// starts in.
+    ///
+    /// [`label::NestedMachine::new_active`]: crate::label::NestedMachine::new_active
+    pub fn push_machine(&mut self, machine: StateMachine<B, T>) {
+        self.0.push(machine);
+    }
+    /// Iterate over the machines, in order
+    pub fn iter(&self) -> core::slice::Iter<'_, StateMachine<B, T>> {
+        self.0.iter()
+    }
+    /// Iterate mutably over the machines, in order
+    pub fn iter_mut(&mut self) -> core::slice::IterMut<'_, StateMachine<B, T>> {
+        self.0.iter_mut()
+    }
     /// Convert `Self` into a [`crate::StateMachines`]
     ///
     /// See [`NameMapping`] and [`IntoTransition`] for details on why this is
     /// necessary.
+    ///
+    /// # Panics
+    ///
+    /// If a [`State::timeout`] refers to a state or machine that doesn't
+    /// exist, see [`StateMachines::try_build`].
     pub fn build<Trs>(self) -> crate::StateMachines<B, Trs>
     where
         T: IntoTransition<Trs>,
+        Trs: crate::Transition,
+    {
+        self.build_with_mapping().0
+    }
+    /// Like [`StateMachines::build`], but returns an error rather than panic
+    /// when a [`State::timeout`] refers to a state or machine that doesn't
+    /// exist
+    ///
+    /// Use this for machines loaded at runtime. The targets of transitions
+    /// are converted by [`IntoTransition`], which is not checked here.
+    pub fn try_build<Trs>(self) -> Result<crate::StateMachines<B, Trs>, UnknownTarget>
+    where
+        T: IntoTransition<Trs>,
+        Trs: crate::Transition,
+    {
+        let mapping = NameMapping::with_capacity(0, 0);
+        self.try_build_after(mapping).map(|(machines, _)| machines)
+    }
+    /// Like [`StateMachines::build`], but also returns the [`NameMapping`]
+    /// used to convert the transitions
+    ///
+    /// Use this to look up handles by name after building, without scanning
+    /// the names of the built [`crate::StateMachines`].
+    pub fn build_with_mapping<Trs>(self) -> (crate::StateMachines<B, Trs>, NameMapping)
+    where
+        T: IntoTransition<Trs>,
+        Trs: crate::Transition,
+    {
+        // `build_after` reserves room for the names of `self`
+        self.build_after(NameMapping::with_capacity(0, 0))
+    }
+    /// Like [`StateMachines::build_with_mapping`], for machines that follow
+    /// the ones built with `base`
+    ///
+    /// Use this to build machines split across several files: the
+    /// transitions of `self` can refer to the machines named in `base`, in
+    /// addition to their own. The machines of `self` get the handles
+    /// following the ones of `base`, and the returned `NameMapping` has the
+    /// names of both, to build the next file with it. Files can only refer to
+    /// the machines of files built before them.
+    ///
+    /// The returned [`crate::StateMachines`] only has the machines of `self`,
+    /// but its handles are the ones they will have once all files are
+    /// merged: don't run it before merging it in the `StateMachines` built
+    /// with `base`, with [`crate::StateMachines::merge`].
+    ///
+    /// As with a single build, state names are not scoped to their machine,
+    /// the states of `self` take precedence over the states of `base` with
+    /// the same name.
+    pub fn build_after<Trs>(self, base: NameMapping) -> (crate::StateMachines<B, Trs>, NameMapping)
+    where
+        T: IntoTransition<Trs>,
+        Trs: crate::Transition,
     {
-        let mut mapping = NameMapping::new();
+        self.try_build_after(base)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// [`StateMachines::build_after`], returning an error for a
+    /// [`State::timeout`] with an unknown target
+    fn try_build_after<Trs>(
+        self,
+        base: NameMapping,
+    ) -> Result<(crate::StateMachines<B, Trs>, NameMapping), UnknownTarget>
+    where
+        T: IntoTransition<Trs>,
+        Trs: crate::Transition,
+    {
+        let mut mapping = base;
+        let state_count = self.0.iter().map(|m| m.states.len()).sum();
+        mapping.machine_names.reserve(self.0.len());
+        mapping.state_names.reserve(state_count);
+        mapping.machine_states.reserve(self.0.len());
+        let first_machine = mapping.machine_count;
+        mapping.machine_count += self.0.len();
+        // `with_capacity` reserves exactly, and stays inline up to
+        // `INLINE_MACHINES`, so pushing the machines never reallocates
         let mut ret = crate::StateMachines {
             machines: SmallVec::with_capacity(self.0.len()),
             machine_names: Vec::with_capacity(self.0.len()),
             state_names: Vec::with_capacity(self.0.len()),
+            interrupts: Vec::new(),
+            version: crate::next_version(),
+            first_machine: first_machine as SmHandleInner,
         };
         // First: iterate through the builder to collect all state and machine names
-        for (mi, StateMachine { name, states }) in self.0.iter().enumerate() {
+        for (mi, StateMachine { name, states, .. }) in self.0.iter().enumerate() {
             ret.machine_names.push(name.clone());
-            mapping
-                .machine_names
-                .insert(name.clone(), mi as SmHandleInner);
+            let handle = (first_machine + mi) as SmHandleInner;
+            mapping.machine_names.insert(name.clone(), handle);
 
             ret.state_names.push(Vec::with_capacity(states.len()));
             let state_names = ret.state_names.last_mut().unwrap();
+            let mut machine_states =
+                HashMap::with_capacity_and_hasher(states.len(), Default::default());
             for (si, State { name, .. }) in states.iter().enumerate() {
                 state_names.push(name.clone());
                 mapping.state_names.insert(name.clone(), si as SHandleInner);
+                machine_states.insert(name.clone(), si as SHandleInner);
             }
+            mapping.machine_states.push(machine_states);
         }
         // Then, we can finally build the REAL crate::StateMachines now that we
         // know the String->index mapping
-        for StateMachine { states, .. } in self.0.into_iter() {
-            let mut machine = Vec::with_capacity(states.len());
-            for State {
+        for machine in self.0.into_iter() {
+            ret.machines.push(machine.compile(&mapping)?);
+        }
+        Ok((ret, mapping))
+    }
+}
+
+/// The compiled [`State::timeout`], `Err` with the target if it doesn't exist
+fn compile_timeout(
+    (duration, target): (Duration, Target),
+    mapping: &NameMapping,
+) -> Result<(Duration, crate::Target), Target> {
+    match mapping.target(&target) {
+        Some(compiled) => Ok((duration, compiled)),
+        None => Err(target),
+    }
+}
+
+impl<B, T> StateMachine<B, T> {
+    /// Convert `self` into the compact representation of a machine, with
+    /// `mapping` for its transitions
+    fn compile<Trs>(
+        self,
+        mapping: &NameMapping,
+    ) -> Result<crate::StateMachine<B, Trs>, UnknownTarget>
+    where
+        T: IntoTransition<Trs>,
+        Trs: crate::Transition,
+    {
+        let mut states = SmallVec::with_capacity(self.states.len());
+        let mut region_ids = SmallVec::<[u8; 1]>::new();
+        let mut regions = SmallVec::new();
+        for (
+            si,
+            State {
+                name,
                 transitions,
                 behavior,
-                ..
-            } in states.into_iter()
-            {
-                machine.push(crate::State {
-                    transitions: transitions
-                        .into_iter()
-                        .map(|t| t.into_with(&mapping))
-                        .collect(),
-                    behavior,
+                region,
+                timeout,
+                default,
+            },
+        ) in self.states.into_iter().enumerate()
+        {
+            if !region_ids.contains(&region) {
+                region_ids.push(region);
+                regions.push(SHandle(si as SHandleInner));
+            }
+            let unknown = |target| UnknownTarget {
+                machine: self.name.clone(),
+                state: name,
+                target,
+            };
+            let timeout = match timeout {
+                Some(timeout) => Some(compile_timeout(timeout, mapping).map_err(unknown)?),
+                None => None,
+            };
+            let transitions: Vec<Trs> = transitions
+                .into_iter()
+                .map(|t| t.into_with(mapping))
+                .collect();
+            states.push(crate::State {
+                stateless: crate::stateless_prefix(&transitions),
+                transitions,
+                behavior,
+                region,
+                timeout,
+                default: default.map(|t| t.into_with(mapping)),
+            });
+        }
+        Ok(crate::StateMachine {
+            states,
+            regions,
+            metadata: self.metadata,
+            on_complete: self.on_complete,
+        })
+    }
+    /// Like [`StateMachines::build`] for a single machine, that can enter
+    /// the machines named `machine_names`, which have the states named
+    /// `state_names`
+    ///
+    /// `state_names` should already list this machine's own state names.
+    /// Panics like [`StateMachines::build`].
+    pub(crate) fn build_alone<Trs>(
+        self,
+        machine_names: &[String],
+        state_names: &[Vec<String>],
+    ) -> crate::StateMachine<B, Trs>
+    where
+        T: IntoTransition<Trs>,
+        Trs: crate::Transition,
+    {
+        let mut mapping = NameMapping::with_capacity(machine_names.len(), self.states.len());
+        for (mi, name) in machine_names.iter().enumerate() {
+            mapping
+                .machine_names
+                .insert(name.clone(), mi as SmHandleInner);
+        }
+        for names in state_names {
+            let to_entry = |(si, name): (usize, &String)| (name.clone(), si as SHandleInner);
+            let machine_states = names.iter().enumerate().map(to_entry).collect();
+            mapping.machine_states.push(machine_states);
+        }
+        for (si, state) in self.states.iter().enumerate() {
+            mapping
+                .state_names
+                .insert(state.name.clone(), si as SHandleInner);
+        }
+        self.compile(&mapping).unwrap_or_else(|err| panic!("{err}"))
+    }
+}
+
+impl<B, T> StateMachines<B, T> {
+    /// Like [`StateMachines::build`], but keeps the handles of `prev` for
+    /// machines and states that have the same name
+    ///
+    /// Normally, the handles of machines and states are their position in
+    /// the builder, so inserting a state in the middle of a machine changes
+    /// the handle of all following states. With this method, machines and
+    /// states present in `prev` keep their handle, new ones take the handles
+    /// of removed ones or the next available handle. If more machines or
+    /// states were removed than added, the last ones are moved to fill the
+    /// gaps, changing their handle.
+    ///
+    /// Positions with a meaning are taken from `self`, not `prev`: the first
+    /// machine stays first (it is the one [`crate::label::NestedMachine::new_active`]
+    /// starts in), the first state of each machine stays first and the first
+    /// state of each region stays the first of its region. States are swapped
+    /// with another state of the same region to keep those, even if it
+    /// changes their handle.
+    ///
+    /// With `prev = None`, this is equivalent to [`StateMachines::build`].
+    pub fn build_with_handle_map<Trs, B2, T2>(
+        self,
+        prev: Option<&crate::StateMachines<B2, T2>>,
+    ) -> crate::StateMachines<B, Trs>
+    where
+        T: IntoTransition<Trs>,
+        Trs: crate::Transition,
+    {
+        let prev = match prev {
+            Some(prev) => prev,
+            None => return self.build(),
+        };
+        let machine_order = stable_order(self.0.iter().map(|m| &m.name), &prev.machine_names);
+        let mut machines = reorder(self.0, &machine_order);
+        for machine in machines.iter_mut() {
+            let prev_states = match prev.machine_handle(&machine.name) {
+                Some(handle) => &prev.state_names[handle.0 as usize],
+                None => continue,
+            };
+            let states = core::mem::take(&mut machine.states);
+            let mut state_order = stable_order(states.iter().map(|s| &s.name), prev_states);
+            keep_region_initials(&mut state_order, &states);
+            machine.states = reorder(states, &state_order);
+        }
+        StateMachines(machines).build()
+    }
+}
+/// Where to put each of `names` so that they are at the same index as in `prev`
+///
+/// Returns, for each final position, the index in `names` of the item to put
+/// there. The first of `names` always stays first.
+fn stable_order<'a>(names: impl Iterator<Item = &'a String>, prev: &[String]) -> Vec<usize> {
+    let prev: HashMap<&str, usize> = prev
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, name)| (name.as_str(), i))
+        .collect();
+    let names: Vec<_> = names.collect();
+    let mut slots = vec![None; names.len()];
+    if let Some(first) = slots.first_mut() {
+        *first = Some(0);
+    }
+    let mut unplaced = Vec::new();
+    for (i, name) in names.iter().enumerate().skip(1) {
+        match prev.get(name.as_str()) {
+            Some(&slot) if slot < slots.len() && slots[slot].is_none() => slots[slot] = Some(i),
+            _ => unplaced.push(i),
+        }
+    }
+    let mut unplaced = unplaced.into_iter();
+    let mut fill = |slot: Option<usize>| slot.or_else(|| unplaced.next()).unwrap();
+    slots.into_iter().map(&mut fill).collect()
+}
+/// Swap items of `order` so that the first state of each region in `states`
+/// is still the first of its region once reordered
+fn keep_region_initials<B, T>(order: &mut [usize], states: &[State<B, T>]) {
+    let mut seen = SmallVec::<[u8; 4]>::new();
+    for (i, state) in states.iter().enumerate() {
+        if seen.contains(&state.region) {
+            continue;
+        }
+        seen.push(state.region);
+        let in_region = |&j: &usize| states[j].region == state.region;
+        let first = order.iter().position(in_region).unwrap();
+        let current = order.iter().position(|&j| j == i).unwrap();
+        order.swap(first, current);
+    }
+}
+fn reorder<X>(items: Vec<X>, order: &[usize]) -> Vec<X> {
+    let mut items: Vec<_> = items.into_iter().map(Some).collect();
+    order.iter().map(|&i| items[i].take().unwrap()).collect()
+}
+
+#[cfg(feature = "std")]
+impl StateMachines<String, String> {
+    /// Convert `Self` into a [`crate::StateMachines`], where behaviors and
+    /// transitions are ids to look up in registries
+    ///
+    /// This lets you refer to behaviors and transitions defined elsewhere by
+    /// name, rather than embedding them in the state machine description: just
+    /// deserialize a `StateMachines<String, String>`.
+    /// The registry values are cloned for each use.
+    ///
+    /// Returns an [`UnknownId`] error for the first id missing from its
+    /// registry, or for a timeout that refers to a state or machine that
+    /// doesn't exist.
+    pub fn build_with_registry<B, T, Trs, S1, S2>(
+        self,
+        behaviors: &std::collections::HashMap<String, B, S1>,
+        transitions: &std::collections::HashMap<String, T, S2>,
+    ) -> Result<crate::StateMachines<B, Trs>, UnknownId>
+    where
+        B: Clone,
+        T: Clone + IntoTransition<Trs>,
+        Trs: crate::Transition,
+        S1: BuildHasher,
+        S2: BuildHasher,
+    {
+        let mut machines = Vec::with_capacity(self.0.len());
+        for StateMachine {
+            name,
+            states,
+            metadata,
+            on_complete,
+        } in self.0.into_iter()
+        {
+            let mut resolved = Vec::with_capacity(states.len());
+            for state in states.into_iter() {
+                let behavior = behaviors
+                    .get(&state.behavior)
+                    .ok_or(UnknownId::Behavior(state.behavior))?;
+                let mut state_transitions = Vec::with_capacity(state.transitions.len());
+                for id in state.transitions.into_iter() {
+                    let transition = transitions.get(&id).ok_or(UnknownId::Transition(id))?;
+                    state_transitions.push(transition.clone());
+                }
+                let default = match state.default {
+                    Some(id) => Some(transitions.get(&id).ok_or(UnknownId::Transition(id))?),
+                    None => None,
+                };
+                resolved.push(State {
+                    name: state.name,
+                    behavior: behavior.clone(),
+                    transitions: state_transitions,
+                    region: state.region,
+                    timeout: state.timeout,
+                    default: default.cloned(),
+                });
+            }
+            let on_complete = match on_complete {
+                Some(id) => Some(behaviors.get(&id).ok_or(UnknownId::Behavior(id))?),
+                None => None,
+            };
+            machines.push(StateMachine {
+                name,
+                states: resolved,
+                metadata,
+                on_complete: on_complete.cloned(),
+            });
+        }
+        StateMachines(machines)
+            .try_build()
+            .map_err(UnknownId::Timeout)
+    }
+}
+
+/// Get back a builder from a compiled [`crate::StateMachines`]
+///
+/// Machine and state names, their order (and therefore their handles) and
+/// the order of transitions round-trip losslessly. Behaviors are cloned.
+/// Transitions round-trip as well as their [`FromTransition`] impl allows.
+/// Calling [`StateMachines::build`] on the result gives back an equivalent
+/// [`crate::StateMachines`].
+impl<B: Clone, T, Trs> TryFrom<&crate::StateMachines<B, Trs>> for StateMachines<B, T>
+where
+    T: FromTransition<Trs>,
+{
+    type Error = UnconvertibleTransition;
+
+    fn try_from(machines: &crate::StateMachines<B, Trs>) -> Result<Self, Self::Error> {
+        let mut ret = Vec::with_capacity(machines.machines.len());
+        let names = machines.machine_names.iter().zip(&machines.state_names);
+        for (machine, (machine_name, state_names)) in machines.machines.iter().zip(names) {
+            let mapping = HandleMapping {
+                machine_names: &machines.machine_names,
+                state_names,
+                all_state_names: &machines.state_names,
+            };
+            let mut states = Vec::with_capacity(machine.states.len());
+            for (state, name) in machine.states.iter().zip(state_names) {
+                let mut transitions = Vec::with_capacity(state.transitions.len());
+                for (index, transition) in state.transitions.iter().enumerate() {
+                    let transition = T::from_with(transition, &mapping).ok_or_else(|| {
+                        UnconvertibleTransition {
+                            machine: machine_name.clone(),
+                            state: name.clone(),
+                            index,
+                        }
+                    })?;
+                    transitions.push(transition);
+                }
+                let timeout = state
+                    .timeout
+                    .as_ref()
+                    .and_then(|(duration, target)| Some((*duration, mapping.target(target)?)));
+                let default = match &state.default {
+                    Some(default) => Some(T::from_with(default, &mapping).ok_or_else(|| {
+                        UnconvertibleTransition {
+                            machine: machine_name.clone(),
+                            state: name.clone(),
+                            index: state.transitions.len(),
+                        }
+                    })?),
+                    None => None,
+                };
+                states.push(State {
+                    name: name.clone(),
+                    behavior: state.behavior.clone(),
+                    transitions,
+                    region: state.region,
+                    timeout,
+                    default,
                 });
             }
-            ret.machines.push(crate::StateMachine {
-                states: machine.into(),
+            ret.push(StateMachine {
+                name: machine_name.clone(),
+                states,
+                metadata: machine.metadata.clone(),
+                on_complete: machine.on_complete.clone(),
             });
         }
-        ret
+        Ok(StateMachines(ret))
     }
 }
@@ -1,51 +1,193 @@
 //! Deserialize state machines
-use serde::{de::Visitor, Deserialize, Deserializer};
-use std::marker::PhantomData;
+//!
+//! [`builder::StateMachine`] and [`builder::State`] are deserialized from
+//! sequences:
+//! * A `StateMachine` is `[ "Name", State1, State2, .. ]`, in human readable
+//!   formats, `"Name"` can be replaced by a map with its metadata, see
+//!   [`builder::StateMachine`]
+//! * A `State` is `[ "Name", Behavior, Transition1, Transition2, .. ]`, in
+//!   human readable formats, `"Name"` can be replaced by a map of the other
+//!   fields of the state, see [`builder::State`]
+//!
+//! The `Deserialize` impls of those types use the visitors of this module,
+//! you can use them directly to deserialize with your own format adapters,
+//! for example to read a single `StateMachine` inside a larger asset.
+//!
+//! [`builder::StateMachines`] is a sequence of `StateMachine`s. Use
+//! [`machines_or_map`] to also accept a map from machine name to the list of
+//! its states, `{ "Name": [State1, State2, ..], .. }`.
+//!
+//! [`builder::StateMachines`]: crate::builder::StateMachines
+//! [`builder::StateMachine`]: crate::builder::StateMachine
+//! [`builder::State`]: crate::builder::State
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::time::Duration;
+use serde::de::{value::MapAccessDeserializer, Error, Visitor};
+use serde::{Deserialize, Deserializer};
 
-use crate::builder::{State, StateMachine};
+use crate::builder::{State, StateMachine, StateMachines, Target};
 
-struct StateMachineVisitor<B, T>(PhantomData<(B, T)>);
+/// Most elements to allocate for before reading them
+///
+/// The size hint of a sequence comes from the input, a malicious file could
+/// report a huge length to make the loader allocate all the memory.
+const MAX_PREALLOCATED: usize = 1024;
+
+/// The capacity to reserve for a sequence with the given remaining size hint
+fn cautious(hint: Option<usize>) -> usize {
+    hint.unwrap_or(2).min(MAX_PREALLOCATED)
+}
+
+/// Visitor for a [`StateMachine`] sequence
+pub struct StateMachineVisitor<B, T>(PhantomData<(B, T)>);
 impl<B, T> StateMachineVisitor<B, T> {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self(PhantomData)
     }
 }
+impl<B, T> Default for StateMachineVisitor<B, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl<'de, B: Deserialize<'de>, T: Deserialize<'de>> Visitor<'de> for StateMachineVisitor<B, T> {
     type Value = StateMachine<B, T>;
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("A List describing a StateMachine [ \"Name\" State1 State2 ..]")
     }
     fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
     where
         A: serde::de::SeqAccess<'de>,
     {
-        let name = seq.next_element()?.ok_or_else(|| todo!())?;
-        let mut states = Vec::with_capacity(seq.size_hint().unwrap_or(2));
+        let NameOrHeader(header) = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let MachineHeader {
+            name,
+            metadata,
+            on_complete,
+        } = header;
+        let mut states = Vec::with_capacity(cautious(seq.size_hint()));
         while let Some(state) = seq.next_element()? {
             states.push(state);
         }
-        Ok(StateMachine { name, states })
+        Ok(StateMachine {
+            name,
+            states,
+            metadata,
+            on_complete,
+        })
     }
 }
 
-struct StateVisitor<B, T>(PhantomData<(B, T)>);
+/// Visitor for a [`StateMachines`] sequence or map, see [`machines_or_map`]
+pub struct StateMachinesVisitor<B, T>(PhantomData<(B, T)>);
+impl<B, T> StateMachinesVisitor<B, T> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+impl<B, T> Default for StateMachinesVisitor<B, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<'de, B: Deserialize<'de>, T: Deserialize<'de>> Visitor<'de> for StateMachinesVisitor<B, T> {
+    type Value = StateMachines<B, T>;
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        formatter.write_str("A List of StateMachines or a map { \"Name\": [State1 State2 ..] }")
+    }
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut machines = Vec::with_capacity(cautious(seq.size_hint()));
+        while let Some(machine) = seq.next_element()? {
+            machines.push(machine);
+        }
+        Ok(StateMachines(machines))
+    }
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let mut machines = Vec::with_capacity(cautious(map.size_hint()));
+        while let Some((name, states)) = map.next_entry::<String, _>()? {
+            machines.push(StateMachine::new(name, states));
+        }
+        Ok(StateMachines(machines))
+    }
+}
+
+/// Deserialize a [`StateMachines`] from either its usual sequence form, or
+/// a map from machine name to its states
+///
+/// Use it with `#[serde(deserialize_with = "pure_hfsm::de::machines_or_map")]`
+/// on a field. The machines are in the order of the map's keys in the input,
+/// so the first key is the machine [`NestedMachine::new_active`] starts in.
+/// Machines in the map form have no metadata. Only self-describing formats
+/// can tell apart a sequence from a map, others only accept the sequence.
+///
+/// [`NestedMachine::new_active`]: crate::label::NestedMachine::new_active
+pub fn machines_or_map<'de, D, B, T>(deserializer: D) -> Result<StateMachines<B, T>, D::Error>
+where
+    D: Deserializer<'de>,
+    B: Deserialize<'de>,
+    T: Deserialize<'de>,
+{
+    let visitor = StateMachinesVisitor::new();
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(visitor)
+    } else {
+        deserializer.deserialize_seq(visitor)
+    }
+}
+
+/// Visitor for a [`State`] sequence
+pub struct StateVisitor<B, T>(PhantomData<(B, T)>);
 impl<B, T> StateVisitor<B, T> {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self(PhantomData)
     }
 }
+impl<B, T> Default for StateVisitor<B, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl<'de, B: Deserialize<'de>, T: Deserialize<'de>> Visitor<'de> for StateVisitor<B, T> {
     type Value = State<B, T>;
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("A List describing a State [ \"Name\" Behavior Trs1 Trs2 .. ]")
     }
     fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
     where
         A: serde::de::SeqAccess<'de>,
     {
-        let name = seq.next_element()?.ok_or_else(|| todo!())?;
-        let behavior = seq.next_element()?.ok_or_else(|| todo!())?;
-        let mut transitions = Vec::with_capacity(seq.size_hint().unwrap_or(2));
+        let NameOrHeader(header) = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let StateHeader {
+            name,
+            region,
+            timeout,
+            default,
+        } = header;
+        let timeout = match timeout {
+            Some((seconds, target)) => {
+                let duration = Duration::try_from_secs_f64(seconds).map_err(A::Error::custom)?;
+                Some((duration, target))
+            }
+            None => None,
+        };
+        let behavior = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        let mut transitions = Vec::with_capacity(cautious(seq.size_hint()));
         while let Some(transition) = seq.next_element()? {
             transitions.push(transition);
         }
@@ -53,10 +195,108 @@ impl<'de, B: Deserialize<'de>, T: Deserialize<'de>> Visitor<'de> for StateVisito
             name,
             behavior,
             transitions,
+            region,
+            timeout,
+            default,
         })
     }
 }
 
+/// The fields of a sequence that can be set in its header, the first element
+/// of the sequence
+trait Header<'de>: Deserialize<'de> {
+    /// The form of the map header, for error messages
+    const EXPECTING: &'static str;
+
+    /// The header with only a name, all other fields to their default
+    fn with_name(name: String) -> Self;
+}
+
+/// The fields of a `State` that can be set in its header
+#[derive(Deserialize)]
+struct StateHeader<T> {
+    name: String,
+    #[serde(default)]
+    region: u8,
+    /// In seconds, see [`State::timeout`]
+    #[serde(default)]
+    timeout: Option<(f64, Target)>,
+    #[serde(default = "Option::default")]
+    default: Option<T>,
+}
+impl<'de, T: Deserialize<'de>> Header<'de> for StateHeader<T> {
+    const EXPECTING: &'static str = "A State name or a map { \"name\": \"Name\", \"region\": 0 }";
+
+    fn with_name(name: String) -> Self {
+        StateHeader {
+            name,
+            region: 0,
+            timeout: None,
+            default: None,
+        }
+    }
+}
+
+/// The fields of a `StateMachine` that can be set in its header
+#[derive(Deserialize)]
+struct MachineHeader<B> {
+    name: String,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+    #[serde(default = "Option::default")]
+    on_complete: Option<B>,
+}
+impl<'de, B: Deserialize<'de>> Header<'de> for MachineHeader<B> {
+    const EXPECTING: &'static str =
+        "A StateMachine name or a map { \"name\": \"Name\", \"metadata\": { .. } }";
+
+    fn with_name(name: String) -> Self {
+        MachineHeader {
+            name,
+            metadata: BTreeMap::new(),
+            on_complete: None,
+        }
+    }
+}
+
+/// The first element of a sequence, either its name or a map of its fields
+struct NameOrHeader<H>(H);
+
+struct HeaderVisitor<H>(PhantomData<H>);
+impl<'de, H: Header<'de>> Visitor<'de> for HeaderVisitor<H> {
+    type Value = NameOrHeader<H>;
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        formatter.write_str(H::EXPECTING)
+    }
+    fn visit_str<E: Error>(self, name: &str) -> Result<Self::Value, E> {
+        self.visit_string(name.to_owned())
+    }
+    fn visit_string<E: Error>(self, name: String) -> Result<Self::Value, E> {
+        Ok(NameOrHeader(H::with_name(name)))
+    }
+    fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        H::deserialize(MapAccessDeserializer::new(map)).map(NameOrHeader)
+    }
+}
+
+impl<'de, H: Header<'de>> Deserialize<'de> for NameOrHeader<H> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let visitor = HeaderVisitor(PhantomData);
+        // Only self-describing formats can tell apart a name from a map
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(visitor)
+        } else {
+            deserializer.deserialize_string(visitor)
+        }
+    }
+}
+
 impl<'de, B: Deserialize<'de>, T: Deserialize<'de>> Deserialize<'de> for StateMachine<B, T> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
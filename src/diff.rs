@@ -0,0 +1,120 @@
+//! Changes between two builds of the same state machines
+//!
+//! See [`StateMachines::diff`].
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::StateMachines;
+
+/// A single difference between two [`StateMachines`]
+///
+/// Machines and states are identified by name, since their handles may be
+/// different between the two builds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// `machine` is only in the new `StateMachines`
+    AddedMachine { machine: String },
+    /// `machine` is only in the old `StateMachines`
+    RemovedMachine { machine: String },
+    /// `state` of `machine` is only in the new `StateMachines`
+    AddedState { machine: String, state: String },
+    /// `state` of `machine` is only in the old `StateMachines`
+    RemovedState { machine: String, state: String },
+    /// `state` of `machine` is in both, but its behavior, transitions or
+    /// region changed
+    ChangedState { machine: String, state: String },
+}
+
+/// All the differences between two [`StateMachines`], see
+/// [`StateMachines::diff`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diff {
+    changes: Vec<Change>,
+}
+impl Diff {
+    /// Whether the two `StateMachines` are the same
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+    /// The changes, see [`StateMachines::diff`] for their order
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+}
+impl IntoIterator for Diff {
+    type Item = Change;
+    type IntoIter = alloc::vec::IntoIter<Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+impl<B: PartialEq, T: PartialEq> StateMachines<B, T> {
+    /// What changed from `self` to `new`, to only reset the running
+    /// [`label::NestedMachine`](crate::label::NestedMachine)s affected by a
+    /// reload
+    ///
+    /// Machines and states are compared by name. A state is changed when its
+    /// behavior, its transitions (in order), its region, its timeout or its
+    /// default transition is different.
+    /// Transitions are compared as built, with handles: a transition to a
+    /// state that moved in its machine is changed.
+    ///
+    /// Changes are listed in the order of `self`: for each machine, its
+    /// removed and changed states, then the states added to it. Machines
+    /// added in `new` come last, with their own states not listed.
+    ///
+    /// A state that only moved in its machine is not a change, even though
+    /// its [`crate::SHandle`] is different in `new`, so a running
+    /// `NestedMachine` would resume in the wrong state. Use
+    /// [`crate::builder::StateMachines::build_with_handle_map`] to build
+    /// `new` so that handles stay the same.
+    pub fn diff(&self, new: &Self) -> Diff {
+        let mut changes = Vec::new();
+        for (old_handle, machine) in self.machines() {
+            let machine_name = || machine.to_owned();
+            let new_handle = match new.machine_handle(machine) {
+                Some(handle) => handle,
+                None => {
+                    let machine = machine_name();
+                    changes.push(Change::RemovedMachine { machine });
+                    continue;
+                }
+            };
+            let old_machine = &self.machines[old_handle.0 as usize];
+            let new_machine = &new.machines[new_handle.0 as usize];
+            let old_names = &self.state_names[old_handle.0 as usize];
+            let new_names = &new.state_names[new_handle.0 as usize];
+            for (old_state, state) in old_machine.states.iter().zip(old_names) {
+                let position = new_names.iter().position(|name| name == state);
+                let change = match position.map(|si| &new_machine.states[si]) {
+                    None => Change::RemovedState {
+                        machine: machine_name(),
+                        state: state.clone(),
+                    },
+                    Some(new_state) if new_state != old_state => Change::ChangedState {
+                        machine: machine_name(),
+                        state: state.clone(),
+                    },
+                    Some(_) => continue,
+                };
+                changes.push(change);
+            }
+            for state in new_names.iter().filter(|name| !old_names.contains(name)) {
+                changes.push(Change::AddedState {
+                    machine: machine_name(),
+                    state: state.clone(),
+                });
+            }
+        }
+        for (_, machine) in new.machines() {
+            if !self.contains_machine(machine) {
+                let machine = machine.to_owned();
+                changes.push(Change::AddedMachine { machine });
+            }
+        }
+        Diff { changes }
+    }
+}
@@ -0,0 +1,218 @@
+//! Trait objects for heterogeneous states
+//!
+//! A [`crate::StateMachines`] has a single behavior type and a single
+//! transition type. To mix different behavior or transition types in the
+//! same collection, you can use `Box<dyn DynBehavior<C> + Send + Sync>`
+//! and `Box<dyn DynTransition<C> + Send + Sync>`, which implement
+//! [`Behavior`] and [`Transition`].
+//!
+//! [`Behavior`] and [`Transition`] can't be made into trait objects, since
+//! their `Context` associated type is generic over lifetimes.
+//! [`DynBehavior`] and [`DynTransition`] instead fix the context type as the
+//! `C` type parameter, lifetimes included. This means that the trait objects
+//! can only be used with a single context type, so it doesn't work with types
+//! that borrow for a different lifetime on each update, such as bevy's
+//! `SystemParam`s. Use an owned or `'static` context type, or an enum for `B`
+//! and `T` with those.
+//!
+//! Any [`Behavior`] and [`Transition`] with a matching context type for all
+//! lifetimes implement the `Dyn` traits, so they can be boxed as is.
+use alloc::boxed::Box;
+
+use crate::{Behavior, Event, StateData, Target, Transition};
+
+/// A [`Behavior`] that can be made into a trait object
+pub trait DynBehavior<C> {
+    /// See [`Behavior::update`]
+    fn update(&self, data: &mut StateData, machine_data: &mut StateData, ctx: &mut C);
+
+    /// See [`Behavior::default_data`]
+    fn default_data(&self) -> StateData {
+        Box::new(())
+    }
+
+    /// See [`Behavior::on_enter`]
+    fn on_enter(&self, data: &mut StateData, machine_data: &mut StateData, ctx: &mut C) {
+        let _ = (data, machine_data, ctx);
+    }
+
+    /// See [`Behavior::shares_data`]
+    fn shares_data(&self) -> bool {
+        false
+    }
+
+    /// See [`Behavior::transitions_first`]
+    fn transitions_first(&self) -> bool {
+        false
+    }
+
+    /// See [`Behavior::can_exit`]
+    fn can_exit(&self, data: &StateData, ctx: &C) -> bool {
+        let _ = (data, ctx);
+        true
+    }
+
+    /// See [`Behavior::result`]
+    fn result(&self, data: &mut StateData, machine_data: &mut StateData) -> Option<StateData> {
+        let _ = (data, machine_data);
+        None
+    }
+
+    /// See [`Behavior::on_result`]
+    fn on_result(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        result: StateData,
+        ctx: &mut C,
+    ) {
+        let _ = (data, machine_data, result, ctx);
+    }
+}
+impl<C, B> DynBehavior<C> for B
+where
+    B: for<'w, 's> Behavior<Context<'w, 's> = C>,
+{
+    fn update(&self, data: &mut StateData, machine_data: &mut StateData, ctx: &mut C) {
+        Behavior::update(self, data, machine_data, ctx)
+    }
+    fn default_data(&self) -> StateData {
+        Behavior::default_data(self)
+    }
+    fn on_enter(&self, data: &mut StateData, machine_data: &mut StateData, ctx: &mut C) {
+        Behavior::on_enter(self, data, machine_data, ctx)
+    }
+    fn shares_data(&self) -> bool {
+        Behavior::shares_data(self)
+    }
+    fn transitions_first(&self) -> bool {
+        Behavior::transitions_first(self)
+    }
+    fn can_exit(&self, data: &StateData, ctx: &C) -> bool {
+        Behavior::can_exit(self, data, ctx)
+    }
+    fn result(&self, data: &mut StateData, machine_data: &mut StateData) -> Option<StateData> {
+        Behavior::result(self, data, machine_data)
+    }
+    fn on_result(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        result: StateData,
+        ctx: &mut C,
+    ) {
+        Behavior::on_result(self, data, machine_data, result, ctx)
+    }
+}
+impl<C> Behavior for Box<dyn DynBehavior<C> + Send + Sync> {
+    type Context<'w, 's> = C;
+
+    fn update<'w, 's>(&self, data: &mut StateData, machine_data: &mut StateData, ctx: &mut C) {
+        (**self).update(data, machine_data, ctx)
+    }
+    fn default_data(&self) -> StateData {
+        (**self).default_data()
+    }
+    fn on_enter<'w, 's>(&self, data: &mut StateData, machine_data: &mut StateData, ctx: &mut C) {
+        (**self).on_enter(data, machine_data, ctx)
+    }
+    fn shares_data(&self) -> bool {
+        (**self).shares_data()
+    }
+    fn transitions_first(&self) -> bool {
+        (**self).transitions_first()
+    }
+    fn can_exit<'w, 's>(&self, data: &StateData, ctx: &C) -> bool {
+        (**self).can_exit(data, ctx)
+    }
+    fn result(&self, data: &mut StateData, machine_data: &mut StateData) -> Option<StateData> {
+        (**self).result(data, machine_data)
+    }
+    fn on_result<'w, 's>(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        result: StateData,
+        ctx: &mut C,
+    ) {
+        (**self).on_result(data, machine_data, result, ctx)
+    }
+}
+
+/// A [`Transition`] that can be made into a trait object
+pub trait DynTransition<C> {
+    /// See [`Transition::decide`]
+    fn decide(&self, data: &mut StateData, machine_data: &mut StateData, ctx: &C) -> Target;
+
+    /// See [`Transition::needs_data`]
+    fn needs_data(&self) -> bool {
+        true
+    }
+
+    /// See [`Transition::decide_event`]
+    fn decide_event(
+        &self,
+        event: &Event,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        ctx: &C,
+    ) -> Target {
+        let _ = (event, data, machine_data, ctx);
+        Target::Continue
+    }
+
+    /// See [`Transition::on_fire`]
+    fn on_fire(&self, data: &mut StateData, machine_data: &mut StateData, ctx: &mut C) {
+        let _ = (data, machine_data, ctx);
+    }
+}
+impl<C, T> DynTransition<C> for T
+where
+    T: for<'w, 's> Transition<Context<'w, 's> = C>,
+{
+    fn decide(&self, data: &mut StateData, machine_data: &mut StateData, ctx: &C) -> Target {
+        Transition::decide(self, data, machine_data, ctx)
+    }
+    fn needs_data(&self) -> bool {
+        Transition::needs_data(self)
+    }
+    fn decide_event(
+        &self,
+        event: &Event,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        ctx: &C,
+    ) -> Target {
+        Transition::decide_event(self, event, data, machine_data, ctx)
+    }
+    fn on_fire(&self, data: &mut StateData, machine_data: &mut StateData, ctx: &mut C) {
+        Transition::on_fire(self, data, machine_data, ctx)
+    }
+}
+impl<C> Transition for Box<dyn DynTransition<C> + Send + Sync> {
+    type Context<'w, 's> = C;
+
+    fn decide<'w, 's>(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        ctx: &C,
+    ) -> Target {
+        (**self).decide(data, machine_data, ctx)
+    }
+    fn needs_data(&self) -> bool {
+        (**self).needs_data()
+    }
+    fn decide_event<'w, 's>(
+        &self,
+        event: &Event,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        ctx: &C,
+    ) -> Target {
+        (**self).decide_event(event, data, machine_data, ctx)
+    }
+    fn on_fire<'w, 's>(&self, data: &mut StateData, machine_data: &mut StateData, ctx: &mut C) {
+        (**self).on_fire(data, machine_data, ctx)
+    }
+}
@@ -5,12 +5,80 @@
 //! [`NestedMachine::update`] method does all the magic of managing the
 //! state machine
 
-use crate::{Behavior, Error, SHandle, SmHandle, StateData, Target, Transition};
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt;
+use core::ops::ControlFlow;
+use core::time::Duration;
 
-#[derive(Debug)]
+use smallvec::SmallVec;
+
+#[cfg(feature = "persist")]
+use crate::persist::{
+    SerializableData, SerializedData, SerializedFrame, SerializedMachine, SerializedState,
+    UnserializableData,
+};
+use crate::{
+    AsyncBehavior, Behavior, Error, Event, SHandle, SmHandle, StateData, StateId, Target,
+    Transition, INLINE_DEPTH,
+};
+
+/// Whether the state machine is still running after an `update`
+///
+/// New variants may be added in the future, like [`Target`], treat unknown
+/// variants as [`Complete::Running`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use = "the state machine may have completed"]
+#[non_exhaustive]
 pub enum Complete {
+    /// The last machine on the stack just completed, the `NestedMachine` is
+    /// now inactive
     Done,
+    /// The `NestedMachine` is still running
     Running,
+    /// The `NestedMachine` is inactive, because it was never started or it
+    /// already completed, `update` did nothing
+    Idle,
+    /// [`NestedMachine::update_budgeted`] ran out of budget, the tick is not
+    /// finished and resumes on the next update
+    Throttled,
+}
+
+/// The transition data `Vec`s of states that were left, reused by the next
+/// states entered
+///
+/// This way, going from state to state doesn't allocate once the
+/// `NestedMachine` is warm. The `Box<()>` initial data doesn't allocate.
+#[derive(Default)]
+struct Pool(Vec<Vec<StateData>>);
+impl Pool {
+    /// A `Vec` of `len` fresh `StateData`
+    fn take(&mut self, len: usize) -> Vec<StateData> {
+        if len == 0 {
+            return Vec::new();
+        }
+        let mut data = self.0.pop().unwrap_or_default();
+        data.extend((0..len).map(|_| Box::new(()) as StateData));
+        data
+    }
+    /// Keep the transition data `Vec` of `state`, which is being left
+    fn recycle(&mut self, state: &mut State) {
+        if let Some(mut data) = state.transitions.take() {
+            if data.capacity() != 0 {
+                data.clear();
+                self.0.push(data);
+            }
+        }
+    }
+    fn recycle_machine(&mut self, mut machine: Machine) {
+        for state in machine.states.iter_mut() {
+            self.recycle(state);
+        }
+    }
 }
 
 /// Data for individual state
@@ -18,6 +86,13 @@ struct State {
     handle: SHandle,
     behavior: StateData,
     transitions: Option<Vec<StateData>>,
+    /// Machines entered with [`Target::EnterOnce`] since entering this state
+    entered_once: Vec<SmHandle>,
+    /// The result of the nested machine this state entered, not yet given
+    /// to [`Behavior::on_result`]
+    result: Option<StateData>,
+    /// Time spent in this state, since it was entered or its timeout fired
+    elapsed: Duration,
 }
 impl State {
     fn new(handle: SHandle) -> Self {
@@ -25,68 +100,752 @@ impl State {
             handle,
             behavior: Box::new(()),
             transitions: None,
+            entered_once: Vec::new(),
+            result: None,
+            elapsed: Duration::ZERO,
+        }
+    }
+    /// Count `dt` and initialize the transitions' data on the first update
+    /// of the state
+    ///
+    /// Returns `true` if this is the first update, the behavior's data is
+    /// then to be initialized by the caller, and the result of the nested
+    /// machine this state entered, to give to the behavior. When `shared`,
+    /// the transitions use the behavior's data, see
+    /// [`Behavior::shares_data`].
+    fn start<'t, Trs: Transition + 't>(
+        &mut self,
+        transitions: impl IntoIterator<Item = &'t Trs>,
+        shared: bool,
+        dt: Duration,
+        pool: &mut Pool,
+    ) -> (bool, Option<StateData>) {
+        self.elapsed += dt;
+        let result = self.result.take();
+        if self.transitions.is_some() {
+            return (false, result);
+        }
+        let with_data = transitions
+            .into_iter()
+            .filter(|t| !shared && t.needs_data());
+        self.transitions = Some(pool.take(with_data.count()));
+        (true, result)
+    }
+    fn update<'w, 's, B, Trs, Ctx>(
+        &mut self,
+        state: &crate::State<B, Trs>,
+        machine_data: &mut StateData,
+        ctx: &mut Ctx,
+        dt: Duration,
+        inputs: Inputs,
+        pool: &mut Pool,
+    ) -> Fired
+    where
+        B: Behavior<Context<'w, 's> = Ctx>,
+        Trs: Transition<Context<'w, 's> = Ctx>,
+    {
+        let behavior = &state.behavior;
+        let shared = behavior.shares_data();
+        // The state data is initialized on the first update of the state
+        let (entered, result) = self.start(state.all_transitions(), shared, dt, pool);
+        if entered {
+            self.behavior = behavior.default_data();
+            behavior.on_enter(&mut self.behavior, machine_data, ctx);
         }
+        if let Some(result) = result {
+            behavior.on_result(&mut self.behavior, machine_data, result, ctx);
+        }
+        let transitions_first = behavior.transitions_first();
+        if !transitions_first {
+            behavior.update(&mut self.behavior, machine_data, ctx);
+        }
+        let can_exit = |data: &StateData, ctx: &Ctx| behavior.can_exit(data, ctx);
+        let fired = self.fire(state, machine_data, ctx, inputs, shared, can_exit);
+        if transitions_first && fired.is_none() {
+            behavior.update(&mut self.behavior, machine_data, ctx);
+        }
+        fired
+    }
+    /// Evaluate the transitions then the timeout of `state`, see
+    /// [`State::decide`]
+    ///
+    /// The leading transitions that don't [`Transition::needs_data`] are
+    /// evaluated in a tight loop first, when there is no event, disabled
+    /// transition nor middleware, and the behavior doesn't share its data.
+    fn fire<'w, 's, B, Trs, Ctx>(
+        &mut self,
+        state: &crate::State<B, Trs>,
+        machine_data: &mut StateData,
+        ctx: &mut Ctx,
+        inputs: Inputs,
+        shared: bool,
+        can_exit: impl Fn(&StateData, &Ctx) -> bool,
+    ) -> Fired
+    where
+        Trs: Transition<Context<'w, 's> = Ctx>,
+    {
+        let transitions = &state.transitions;
+        let plain = inputs.event.is_none() && inputs.disabled.is_empty() && inputs.hook.is_none();
+        let stateless = match plain && !shared {
+            true => state.stateless,
+            false => 0,
+        };
+        if stateless != 0 {
+            let fired =
+                self.fire_stateless(&transitions[..stateless], machine_data, ctx, &can_exit);
+            if fired.is_some() {
+                return fired;
+            }
+        }
+        // The stateless transitions have no data slot, so skipping them
+        // keeps the others in sync with their data
+        let candidates = state.candidates(stateless);
+        let disabled = inputs.disabled;
+        let fired = self.decide(candidates, machine_data, ctx, inputs, shared, &can_exit);
+        if fired.is_some() {
+            return fired;
+        }
+        // The timeout is checked last, like a transition after all others
+        let (timeout, target) = state.timeout.as_ref()?;
+        let timed_out = self.elapsed >= *timeout
+            && !disabled.contains(&state.timeout_index())
+            && fires(&self.entered_once, target);
+        if !timed_out || !can_exit(&self.behavior, ctx) {
+            return None;
+        }
+        self.elapsed = Duration::ZERO;
+        let target = enter_once(&mut self.entered_once, target.clone());
+        Some((state.timeout_index(), target))
     }
-    fn update<'w, 's, 'ww, 'ss, B, Trs, Wrd, Updt>(
+    /// Evaluate `transitions`, which don't need data, without the
+    /// bookkeeping of [`State::decide`]
+    fn fire_stateless<'w, 's, Trs, Ctx>(
+        &mut self,
+        transitions: &[Trs],
+        machine_data: &mut StateData,
+        ctx: &mut Ctx,
+        can_exit: impl Fn(&StateData, &Ctx) -> bool,
+    ) -> Fired
+    where
+        Trs: Transition<Context<'w, 's> = Ctx>,
+    {
+        let mut no_data: StateData = Box::new(());
+        for (index, transition) in transitions.iter().enumerate() {
+            let target = transition.decide(&mut no_data, machine_data, ctx);
+            if fires(&self.entered_once, &target) {
+                if !can_exit(&self.behavior, ctx) {
+                    return None;
+                }
+                let target = enter_once(&mut self.entered_once, target);
+                transition.on_fire(&mut no_data, machine_data, ctx);
+                return Some((index, target));
+            }
+        }
+        None
+    }
+    /// Same as [`State::update`], for [`AsyncBehavior`]s
+    async fn update_async<'w, 's, B, Trs, Ctx>(
         &mut self,
         state: &crate::State<B, Trs>,
-        commands: &mut Updt,
-        world: &Wrd,
-    ) -> Target
+        machine_data: &mut StateData,
+        ctx: &mut Ctx,
+        dt: Duration,
+        inputs: Inputs<'_>,
+        pool: &mut Pool,
+    ) -> Fired
+    where
+        B: AsyncBehavior<Context<'w, 's> = Ctx>,
+        Trs: Transition<Context<'w, 's> = Ctx>,
+    {
+        let behavior = &state.behavior;
+        let shared = behavior.shares_data();
+        let (entered, result) = self.start(state.all_transitions(), shared, dt, pool);
+        if entered {
+            self.behavior = behavior.default_data();
+            behavior.on_enter(&mut self.behavior, machine_data, ctx);
+        }
+        if let Some(result) = result {
+            behavior.on_result(&mut self.behavior, machine_data, result, ctx);
+        }
+        let transitions_first = behavior.transitions_first();
+        if !transitions_first {
+            behavior.update(&mut self.behavior, machine_data, ctx).await;
+        }
+        let can_exit = |data: &StateData, ctx: &Ctx| behavior.can_exit(data, ctx);
+        let fired = self.fire(state, machine_data, ctx, inputs, shared, can_exit);
+        if transitions_first && fired.is_none() {
+            behavior.update(&mut self.behavior, machine_data, ctx).await;
+        }
+        fired
+    }
+    /// Evaluate `transitions` in order, returns the index of the first that
+    /// fired
+    ///
+    /// The [`Transition::on_fire`] of the transition that fired is called. A
+    /// [`Target::EnterOnce`] of an already entered machine doesn't fire, one
+    /// that fires is recorded, see [`enter_once`]. Each transition is given
+    /// the event of `inputs` before being polled, see
+    /// [`Transition::decide_event`], the disabled transitions of `inputs`
+    /// are skipped, and the target goes through the middleware of `inputs`.
+    /// When `shared`, all
+    /// transitions get the behavior's data. When `can_exit` returns `false`
+    /// for the behavior's data, the transition that fired is suppressed, see
+    /// [`Behavior::can_exit`].
+    fn decide<'t, 'w, 's, Trs, Ctx>(
+        &mut self,
+        transitions: impl IntoIterator<Item = (usize, &'t Trs)>,
+        machine_data: &mut StateData,
+        ctx: &mut Ctx,
+        mut inputs: Inputs,
+        shared: bool,
+        can_exit: impl FnOnce(&StateData, &Ctx) -> bool,
+    ) -> Fired
     where
-        B: Behavior<Update<'w, 's> = Updt, World<'ww, 'ss> = Wrd>,
-        Trs: Transition<World<'ww, 'ss> = Wrd>,
+        Trs: Transition<Context<'w, 's> = Ctx> + 't,
     {
-        state.behavior.update(&mut self.behavior, commands, world);
-        if self.transitions.is_none() {
-            let mut transitions: Vec<StateData> = Vec::with_capacity(state.transitions.len());
-            for _ in state.transitions.iter() {
-                transitions.push(Box::new(()));
+        if shared {
+            let data = &mut self.behavior;
+            for (index, transition) in transitions {
+                if inputs.disabled.contains(&index) {
+                    continue;
+                }
+                let target = decide(transition, index, &mut inputs, data, machine_data, ctx);
+                if fires(&self.entered_once, &target) {
+                    if !can_exit(data, ctx) {
+                        return None;
+                    }
+                    let target = enter_once(&mut self.entered_once, target);
+                    transition.on_fire(data, machine_data, ctx);
+                    return Some((index, target));
+                }
             }
-            self.transitions = Some(transitions);
+            return None;
+        }
+        let trans_data = self
+            .transitions
+            .as_mut()
+            .map_or(&mut [][..], |t| &mut t[..]);
+        let trans_data = &mut trans_data.iter_mut();
+        for (index, transition) in transitions {
+            let mut no_data: StateData = Box::new(());
+            let data = match transition.needs_data() {
+                true => trans_data.next().unwrap_or(&mut no_data),
+                false => &mut no_data,
+            };
+            // The data is taken even when disabled, to keep the others in sync
+            if inputs.disabled.contains(&index) {
+                continue;
+            }
+            let target = decide(transition, index, &mut inputs, data, machine_data, ctx);
+            if fires(&self.entered_once, &target) {
+                if !can_exit(&self.behavior, ctx) {
+                    return None;
+                }
+                let target = enter_once(&mut self.entered_once, target);
+                transition.on_fire(data, machine_data, ctx);
+                return Some((index, target));
+            }
+        }
+        None
+    }
+}
+
+/// The target the `index`th `transition` decides, with the event of `inputs`
+/// first if there is one, as replaced by the middleware of `inputs`
+fn decide<'w, 's, Trs, Ctx>(
+    transition: &Trs,
+    index: usize,
+    inputs: &mut Inputs,
+    data: &mut StateData,
+    machine_data: &mut StateData,
+    ctx: &Ctx,
+) -> Target
+where
+    Trs: Transition<Context<'w, 's> = Ctx>,
+{
+    let event_target = inputs.event.map_or(Target::Continue, |event| {
+        transition.decide_event(event, data, machine_data, ctx)
+    });
+    let target = match event_target {
+        Target::Continue => transition.decide(data, machine_data, ctx),
+        target => target,
+    };
+    inputs.intercept(index, target)
+}
+
+/// Whether `target` fires, `entered_once` are the machines the state already
+/// entered with [`Target::EnterOnce`], which don't fire again
+fn fires(entered_once: &[SmHandle], target: &Target) -> bool {
+    match target {
+        Target::Continue => false,
+        Target::EnterOnce(machine) => !entered_once.contains(machine),
+        _ => true,
+    }
+}
+
+/// Whether `target`, returned by [`Machine::settle`], completes the machine
+///
+/// The machine's result is then set and its `on_complete` behavior runs.
+fn completes(target: &Target) -> bool {
+    matches!(target, Target::Complete | Target::PopN(_))
+}
+
+/// Record `target` in `entered_once` if it is an `EnterOnce`
+///
+/// The record is the last of `entered_once` until [`NestedMachine::apply`]
+/// enters the machine, which undoes it when the depth limit is reached.
+fn enter_once(entered_once: &mut Vec<SmHandle>, target: Target) -> Target {
+    if let Target::EnterOnce(machine) = &target {
+        entered_once.push(machine.clone());
+    }
+    target
+}
+
+/// How many behavior and transition evaluations are left, see
+/// [`NestedMachine::update_budgeted`]
+struct Budget {
+    left: usize,
+    /// Whether a state was evaluated with this budget
+    spent: bool,
+}
+impl Budget {
+    fn unlimited() -> Self {
+        Budget::new(usize::MAX)
+    }
+    fn new(left: usize) -> Self {
+        Budget { left, spent: false }
+    }
+    /// Spend `cost` evaluations if they fit in what is left
+    ///
+    /// The first spending always fits, so that each update makes progress.
+    fn spend(&mut self, cost: usize) -> bool {
+        if self.spent && cost > self.left {
+            return false;
+        }
+        self.left = self.left.saturating_sub(cost);
+        self.spent = true;
+        true
+    }
+}
+
+/// Index and target of the transition that fired, if any
+///
+/// Indices follow the evaluation order: the index of a state's default
+/// transition is the number of transitions of the state, the index of its
+/// timeout comes after, see [`crate::builder::State::timeout`].
+type Fired = Option<(usize, Target)>;
+
+/// What an update of a machine gets besides the context
+struct Tick<'a> {
+    /// See [`NestedMachine::send_event`]
+    event: Option<&'a Event>,
+    /// The time since the previous update, see [`NestedMachine::update_timed`]
+    dt: Duration,
+    /// Whether to check that the states exist, see
+    /// [`NestedMachine::update_unchecked`]
+    checked: bool,
+    /// See [`NestedMachine::set_transition_enabled`]
+    disabled: &'a Disabled,
+    /// See [`NestedMachine::set_transition_middleware`]
+    middleware: Option<&'a mut Middleware>,
+}
+impl Tick<'_> {
+    /// The [`Inputs`] of `state` in `machine`
+    fn inputs(&mut self, machine: &SmHandle, state: &SHandle) -> Inputs<'_> {
+        let disabled = match self.disabled.is_empty() {
+            true => None,
+            false => self.disabled.get(&(machine.clone(), state.clone())),
+        };
+        let hook = |middleware| Hook {
+            middleware,
+            machine: machine.clone(),
+            state: state.clone(),
+        };
+        Inputs {
+            event: self.event,
+            disabled: disabled.map_or(&[], Vec::as_slice),
+            hook: self.middleware.as_deref_mut().map(hook),
         }
-        let trans_data = &mut self.transitions.iter_mut().flatten();
-        for (transition, data) in state.transitions.iter().zip(trans_data) {
-            let target = transition.decide(data, world);
-            if !matches!(target, Target::Continue) {
-                return target;
+    }
+}
+
+/// What the transitions of a state are evaluated with, see [`State::decide`]
+#[derive(Default)]
+struct Inputs<'a> {
+    event: Option<&'a Event>,
+    /// Indices of the transitions to skip
+    disabled: &'a [usize],
+    hook: Option<Hook<'a>>,
+}
+impl Inputs<'_> {
+    /// `target`, as replaced by the middleware if there is one
+    fn intercept(&mut self, index: usize, target: Target) -> Target {
+        match &mut self.hook {
+            Some(hook) => {
+                let context = TransitionContext {
+                    machine: hook.machine.clone(),
+                    state: hook.state.clone(),
+                    index,
+                };
+                (hook.middleware)(context, target)
             }
+            None => target,
+        }
+    }
+}
+
+/// The [`NestedMachine::set_transition_middleware`] middleware, with the
+/// state whose transitions it gets
+struct Hook<'a> {
+    middleware: &'a mut Middleware,
+    machine: SmHandle,
+    state: SHandle,
+}
+
+/// See [`NestedMachine::set_transition_middleware`]
+type Middleware = dyn FnMut(TransitionContext, Target) -> Target + Send + Sync;
+
+/// The transition whose decision is passed to the middleware, see
+/// [`NestedMachine::set_transition_middleware`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransitionContext {
+    pub machine: SmHandle,
+    pub state: SHandle,
+    /// The index of the transition in `state`, see
+    /// [`crate::builder::State::default`] for the index of the default
+    /// transition
+    pub index: usize,
+}
+
+/// The disabled transition indices of each `(machine, state)`, see
+/// [`NestedMachine::set_transition_enabled`]
+type Disabled = crate::HashMap<(SmHandle, SHandle), Vec<usize>>;
+
+/// How many times each transition fired
+///
+/// Only available with the `profiling` feature, see
+/// `NestedMachine::transition_stats`.
+#[derive(Debug, Clone, Default)]
+pub struct TransitionStats {
+    #[cfg(feature = "profiling")]
+    counts: crate::HashMap<(SmHandle, SHandle, usize), u64>,
+}
+impl TransitionStats {
+    fn record(&mut self, machine: &SmHandle, state: &SHandle, index: usize) {
+        #[cfg(feature = "profiling")]
+        {
+            let key = (machine.clone(), state.clone(), index);
+            *self.counts.entry(key).or_insert(0) += 1;
         }
-        Target::Continue
+        #[cfg(not(feature = "profiling"))]
+        let _ = (machine, state, index);
+    }
+    /// How many times the `index`th transition of `state` in `machine` fired
+    #[cfg(feature = "profiling")]
+    pub fn count(&self, machine: &SmHandle, state: &SHandle, index: usize) -> u64 {
+        let key = (machine.clone(), state.clone(), index);
+        self.counts.get(&key).copied().unwrap_or(0)
+    }
+    /// All transitions that fired at least once, as `(machine, state, index,
+    /// count)`, with `index` the index of the transition in its state
+    #[cfg(feature = "profiling")]
+    pub fn iter(&self) -> impl Iterator<Item = (SmHandle, SHandle, usize, u64)> + '_ {
+        let to_tuple = |((m, s, i), count): (&(SmHandle, SHandle, usize), &u64)| {
+            (m.clone(), s.clone(), *i, *count)
+        };
+        self.counts.iter().map(to_tuple)
     }
 }
 
 /// Data for individual machines
 struct Machine {
     handle: SmHandle,
-    state: State,
+    /// The active state of each parallel region of the machine
+    states: SmallVec<[State; 1]>,
+    /// Whether the regions other than the first one were started
+    regions_started: bool,
+    /// Data shared by all states of this machine, reset when entering it
+    data: StateData,
+    /// The region to update first on next update, and the `Target` returned
+    /// so far, when the last one was throttled, see
+    /// [`NestedMachine::update_budgeted`]
+    resume: Option<(usize, Target)>,
+    /// The region that entered the nested machine above this one
+    entered_from: usize,
+    /// The [`Behavior::result`] of the state that completed this machine
+    result: Option<StateData>,
 }
 impl Machine {
     fn new(handle: SmHandle) -> Self {
+        Self::with_state(handle, SHandle::INITIAL)
+    }
+    fn with_state(handle: SmHandle, state: SHandle) -> Self {
+        let mut states = SmallVec::new();
+        states.push(State::new(state));
         Machine {
             handle,
-            state: State::new(SHandle::INITIAL),
+            states,
+            regions_started: false,
+            data: Box::new(()),
+            resume: None,
+            entered_from: 0,
+            result: None,
+        }
+    }
+    /// The active state of the first region still running
+    fn state(&self) -> Option<&State> {
+        self.states.first()
+    }
+
+    /// Create the states of the regions other than the first one, the first
+    /// time the machine is updated
+    fn start_regions<B, Trs>(&mut self, machine: &crate::StateMachine<B, Trs>) {
+        if !self.regions_started {
+            self.regions_started = true;
+            let other_regions = machine.regions.iter().skip(1);
+            self.states.extend(other_regions.cloned().map(State::new));
+        }
+    }
+
+    /// Act on what the `region`th region decided during its update
+    ///
+    /// Updates `ret` with the last `Goto`, returns the next region to update
+    /// or the `Target` to return immediately.
+    fn apply(
+        &mut self,
+        region: usize,
+        fired: Fired,
+        stats: &mut TransitionStats,
+        pool: &mut Pool,
+        ret: &mut Target,
+    ) -> ControlFlow<Target, usize> {
+        let region_state = &mut self.states[region];
+        let target = match fired {
+            Some((index, target)) => {
+                stats.record(&self.handle, &region_state.handle, index);
+                target
+            }
+            None => Target::Continue,
+        };
+        match target {
+            // Note: this also resets the state data when `Goto`ing the current state
+            Target::Goto(ref new_state_handle) => {
+                pool.recycle(region_state);
+                *region_state = State::new(new_state_handle.clone());
+                *ret = target;
+            }
+            Target::Enter(_) | Target::EnterOnce(_) | Target::EnterAt(..) => {
+                self.entered_from = region;
+                return ControlFlow::Break(target);
+            }
+            // `PopN(0)` pops nothing, the machine keeps running
+            Target::PopN(0) | Target::Continue => {}
+            Target::PopN(_) => return ControlFlow::Break(target),
+            Target::Complete => {
+                pool.recycle(&mut self.states.remove(region));
+                if self.states.is_empty() {
+                    return ControlFlow::Break(Target::Complete);
+                }
+                return ControlFlow::Continue(region);
+            }
+        }
+        ControlFlow::Continue(region + 1)
+    }
+
+    /// Whether [`Machine::apply`]ing `fired` ends this machine
+    fn ends(&self, fired: &Fired) -> bool {
+        match fired {
+            Some((_, Target::Complete)) => self.states.len() == 1,
+            Some((_, Target::PopN(n))) => *n > 0,
+            _ => false,
+        }
+    }
+
+    /// The state of the `region`th region in `machine`
+    ///
+    /// When not `checked`, the state is assumed to exist in `machine`.
+    fn region_state<'m, B, Trs>(
+        &self,
+        machine: &'m crate::StateMachine<B, Trs>,
+        region: usize,
+        checked: bool,
+    ) -> Result<&'m crate::State<B, Trs>, Error> {
+        let handle = &self.states[region].handle;
+        if !checked {
+            return Ok(machine.state_indexed(handle));
+        }
+        machine.state(handle).ok_or_else(|| Error::BadStateName {
+            machine: self.handle.clone(),
+            state: handle.clone(),
+        })
+    }
+
+    /// Handle what the `region`th region decided once its update returned
+    ///
+    /// When `fired` ends this machine, `result` is called with the data of
+    /// the region's state and of the machine, and its return value becomes
+    /// the machine's result. Then see [`Machine::apply`]. When this returns
+    /// [`ControlFlow::Break`] with a target that [`completes`], the
+    /// caller runs the machine's `on_complete` behavior.
+    fn settle(
+        &mut self,
+        region: usize,
+        fired: Fired,
+        result: impl FnOnce(&mut StateData, &mut StateData) -> Option<StateData>,
+        stats: &mut TransitionStats,
+        pool: &mut Pool,
+        ret: &mut Target,
+    ) -> ControlFlow<Target, usize> {
+        let result = match self.ends(&fired) {
+            true => result(&mut self.states[region].behavior, &mut self.data),
+            false => None,
+        };
+        let flow = self.apply(region, fired, stats, pool, ret);
+        if let ControlFlow::Break(target) = &flow {
+            if completes(target) {
+                self.result = result;
+            }
+        }
+        flow
+    }
+
+    /// Update all regions in order
+    ///
+    /// Returns [`Target::Enter`] as soon as a region enters a nested machine,
+    /// without updating the following regions. Returns [`Target::Complete`]
+    /// once all regions completed. When a region doesn't fit in `budget`,
+    /// stops before it and returns `None`, the next update resumes there if
+    /// a region was already updated during this tick.
+    fn update<'w, 's, B, Trs, Ctx>(
+        &mut self,
+        machine: &crate::StateMachine<B, Trs>,
+        stats: &mut TransitionStats,
+        pool: &mut Pool,
+        ctx: &mut Ctx,
+        budget: &mut Budget,
+        mut tick: Tick,
+    ) -> Result<Option<Target>, Error>
+    where
+        B: Behavior<Context<'w, 's> = Ctx> + 'static,
+        Trs: Transition<Context<'w, 's> = Ctx> + 'static,
+    {
+        self.start_regions(machine);
+        let resumed = self.resume.take();
+        // Whether a region was updated during this tick
+        let mut started = resumed.is_some();
+        let (mut region, mut ret) = resumed.unwrap_or((0, Target::Continue));
+        while region < self.states.len() {
+            let state = self.region_state(machine, region, tick.checked)?;
+            if !budget.spend(1 + state.transitions.len()) {
+                if started {
+                    self.resume = Some((region, ret));
+                }
+                return Ok(None);
+            }
+            let region_state = &mut self.states[region];
+            let dt = tick.dt;
+            let inputs = tick.inputs(&self.handle, &region_state.handle);
+            let data = &mut self.data;
+            let fired = region_state.update(state, data, ctx, dt, inputs, pool);
+            started = true;
+            let result =
+                |data: &mut _, machine_data: &mut _| state.behavior.result(data, machine_data);
+            match self.settle(region, fired, result, stats, pool, &mut ret) {
+                ControlFlow::Continue(next) => region = next,
+                ControlFlow::Break(target) => {
+                    if let Some(on_complete) =
+                        machine.on_complete.as_ref().filter(|_| completes(&target))
+                    {
+                        let mut data = on_complete.default_data();
+                        on_complete.update(&mut data, &mut self.data, ctx);
+                    }
+                    return Ok(Some(target));
+                }
+            }
         }
+        Ok(Some(ret))
     }
 
-    fn update<'w, 's, 'ww, 'ss, B, Trs, Wrd, Updt>(
+    /// Same as [`Machine::update`], for [`AsyncBehavior`]s
+    async fn update_async<'w, 's, B, Trs, Ctx>(
         &mut self,
         machine: &crate::StateMachine<B, Trs>,
-        commands: &mut Updt,
-        world: &Wrd,
+        stats: &mut TransitionStats,
+        pool: &mut Pool,
+        ctx: &mut Ctx,
+        mut tick: Tick<'_>,
     ) -> Result<Target, Error>
     where
-        B: Behavior<Update<'w, 's> = Updt, World<'ww, 'ss> = Wrd> + 'static,
-        Trs: Transition<World<'ww, 'ss> = Wrd> + 'static,
+        B: AsyncBehavior<Context<'w, 's> = Ctx> + 'static,
+        Trs: Transition<Context<'w, 's> = Ctx> + 'static,
     {
-        let state = machine
-            .state(&self.state.handle)
-            .ok_or(Error::BadStateName)?;
-        let target = self.state.update(state, commands, world);
-        if let Target::Goto(ref new_state_handle) = target {
-            self.state = State::new(new_state_handle.clone());
-        };
-        Ok(target)
+        self.start_regions(machine);
+        let mut ret = Target::Continue;
+        let mut region = 0;
+        while region < self.states.len() {
+            let state = self.region_state(machine, region, tick.checked)?;
+            let region_state = &mut self.states[region];
+            let dt = tick.dt;
+            let inputs = tick.inputs(&self.handle, &region_state.handle);
+            let data = &mut self.data;
+            let fired = region_state.update_async(state, data, ctx, dt, inputs, pool);
+            let fired = fired.await;
+            let result =
+                |data: &mut _, machine_data: &mut _| state.behavior.result(data, machine_data);
+            match self.settle(region, fired, result, stats, pool, &mut ret) {
+                ControlFlow::Continue(next) => region = next,
+                ControlFlow::Break(target) => {
+                    if let Some(on_complete) =
+                        machine.on_complete.as_ref().filter(|_| completes(&target))
+                    {
+                        let mut data = on_complete.default_data();
+                        on_complete.update(&mut data, &mut self.data, ctx).await;
+                    }
+                    return Ok(target);
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
+
+/// The limits of a running [`NestedMachine`], see
+/// [`NestedMachine::with_config`]
+///
+/// The `Default` config has no limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NestedMachineConfig {
+    /// The most machines on the stack, `usize::MAX` by default
+    ///
+    /// When a transition enters a machine while the stack is this deep,
+    /// `update` returns [`Error::DepthLimit`] instead, and the machine is not
+    /// entered. This catches machines entering each other recursively, see
+    /// [`crate::StateMachines::enter_cycles`]. [`NestedMachine::enter`]
+    /// ignores this limit.
+    pub max_depth: usize,
+    /// See [`NestedMachine::with_stuck_threshold`], `None` by default
+    pub stuck_threshold: Option<u32>,
+    /// The budget of `update`, `None` by default
+    ///
+    /// With a budget, [`NestedMachine::update`], `update_timed` and
+    /// `update_unchecked` evaluate at most this many behaviors and
+    /// transitions, and return [`Complete::Throttled`] when the next region
+    /// doesn't fit, see [`NestedMachine::update_budgeted`] for details. Unlike
+    /// `update_budgeted`, they still stop after a `Goto`.
+    pub budget: Option<usize>,
+}
+impl Default for NestedMachineConfig {
+    fn default() -> Self {
+        NestedMachineConfig {
+            max_depth: usize::MAX,
+            stuck_threshold: None,
+            budget: None,
+        }
+    }
+}
+impl NestedMachineConfig {
+    fn budget(&self) -> Budget {
+        self.budget.map_or_else(Budget::unlimited, Budget::new)
     }
 }
 
@@ -94,40 +853,438 @@ impl Machine {
 ///
 /// This contains the state pointers of innactive state machines that entered a
 /// nested machine, and the state `Data` of those machines.
+///
+/// Up to [`INLINE_DEPTH`] nested machines are stored inline, so shallow
+/// hierarchies do not allocate on the heap. The storage for the data of the
+/// transitions of a state is reused by the next states, so changing states
+/// doesn't allocate either once each machine ran for a bit, only the
+/// `StateData` your behaviors and transitions create does.
 pub struct NestedMachine {
-    stack: Vec<Machine>,
+    stack: SmallVec<[Machine; INLINE_DEPTH]>,
+    config: NestedMachineConfig,
+    /// How many consecutive `update`s without a transition firing
+    ticks_unchanged: u32,
+    stats: TransitionStats,
+    /// Data of the [`crate::StateMachines::with_interrupts`] transitions
+    interrupts: State,
+    /// The [`crate::StateMachines::version`] the stack was validated against,
+    /// see [`NestedMachine::validate_against`]
+    validated: Option<u64>,
+    /// Whether an `update` returned [`Complete::Done`] since the last `enter`
+    completed: bool,
+    /// Events sent with [`NestedMachine::send_event`], not yet updated with
+    events: VecDeque<Event>,
+    /// The event of the current tick, kept while it is throttled
+    event: Option<Event>,
+    /// See [`NestedMachine::set_transition_enabled`]
+    disabled: Disabled,
+    pool: Pool,
+    /// See [`NestedMachine::set_transition_middleware`]
+    middleware: Option<Box<Middleware>>,
 }
 impl Default for NestedMachine {
     fn default() -> Self {
         Self::new()
     }
 }
+/// Prints the stack as `(machine, state)` handle pairs, from the outermost
+/// machine, omitting all `StateData`
+///
+/// The output only depends on the handles, so it is stable for snapshot
+/// tests. Use [`NestedMachine::debug_with`] to print names instead.
+impl fmt::Debug for NestedMachine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stack: Vec<_> = self.stack_handles().collect();
+        f.debug_struct("NestedMachine")
+            .field("stack", &stack)
+            .field("completed", &self.completed)
+            .field("pending_events", &self.events.len())
+            .finish()
+    }
+}
+
+/// Prints a [`NestedMachine`] with the names of its machines and states,
+/// obtained with [`NestedMachine::debug_with`]
+pub struct DebugWith<'a, B, T> {
+    nested: &'a NestedMachine,
+    machines: &'a crate::StateMachines<B, T>,
+}
+impl<B, T> fmt::Debug for DebugWith<'_, B, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let machines = self.machines;
+        let name = |(machine, state): (SmHandle, SHandle)| {
+            let state_name = machines.state_name(&machine, &state);
+            let machine_name = machines.machine_name(&machine);
+            match (machine_name, state_name) {
+                (Some(machine), Some(state)) => (machine.to_owned(), state.to_owned()),
+                _ => (format!("{machine:?}"), format!("{state:?}")),
+            }
+        };
+        let stack: Vec<_> = self.nested.stack_handles().map(name).collect();
+        f.debug_struct("NestedMachine")
+            .field("stack", &stack)
+            .field("completed", &self.nested.completed)
+            .field("pending_events", &self.nested.events.len())
+            .finish()
+    }
+}
 impl NestedMachine {
     /// Initialize a `NestedMachine` without any active state
+    ///
+    /// `update` returns [`Complete::Idle`] until a machine is entered.
     pub fn new() -> Self {
+        Self::with_stack(SmallVec::new())
+    }
+    fn with_stack(stack: SmallVec<[Machine; INLINE_DEPTH]>) -> Self {
         NestedMachine {
-            stack: Vec::with_capacity(1),
+            stack,
+            config: NestedMachineConfig::default(),
+            ticks_unchanged: 0,
+            stats: TransitionStats::default(),
+            interrupts: State::new(SHandle::INITIAL),
+            validated: None,
+            completed: false,
+            events: VecDeque::new(),
+            event: None,
+            disabled: Disabled::default(),
+            pool: Pool::default(),
+            middleware: None,
+        }
+    }
+    /// Return [`Error::Stuck`] from `update` when staying in the same state
+    /// for more than `threshold` updates in a row
+    ///
+    /// This is disabled by default. Staying in a state means no transition
+    /// fired during `update`, (re-)entering the current state with a
+    /// [`Target::Goto`] is not staying. Once `Error::Stuck` is returned, the
+    /// count starts over. Note that the `update` returning the error still
+    /// ran normally. This is useful to catch authoring mistakes when testing.
+    ///
+    /// This sets [`NestedMachineConfig::stuck_threshold`].
+    pub fn with_stuck_threshold(mut self, threshold: u32) -> Self {
+        self.config.stuck_threshold = Some(threshold);
+        self
+    }
+    /// Set all the limits of this `NestedMachine` at once
+    pub fn with_config(mut self, config: NestedMachineConfig) -> Self {
+        self.config = config;
+        self
+    }
+    /// The limits of this `NestedMachine`
+    pub fn config(&self) -> &NestedMachineConfig {
+        &self.config
+    }
+    /// See [`NestedMachineConfig::max_depth`]
+    pub fn max_depth(&self) -> usize {
+        self.config.max_depth
+    }
+    /// How many more machines can be entered before reaching
+    /// [`NestedMachineConfig::max_depth`]
+    pub fn remaining_depth(&self) -> usize {
+        self.config.max_depth.saturating_sub(self.stack.len())
+    }
+    /// Whether entering another machine would return [`Error::DepthLimit`]
+    pub fn depth_limit_reached(&self) -> bool {
+        self.remaining_depth() == 0
+    }
+    /// Enable or disable the transition at `index` of `state` in `machine`
+    ///
+    /// All transitions are enabled by default. A disabled transition is
+    /// skipped as if it returned [`Target::Continue`], the following ones are
+    /// still evaluated. The index of the state's default transition is its
+    /// number of transitions, like in
+    /// [`crate::StateMachines::transition_count`], followed by the index of
+    /// its timeout, see [`crate::builder::State::timeout`].
+    ///
+    /// The mask is part of this `NestedMachine` only, other instances
+    /// running the same [`crate::StateMachines`] are not affected. It is kept
+    /// when entering and exiting machines, and is not checked against the
+    /// `StateMachines`, an `index` past the last transition does nothing.
+    pub fn set_transition_enabled(
+        &mut self,
+        machine: &SmHandle,
+        state: &SHandle,
+        index: usize,
+        enabled: bool,
+    ) {
+        let key = (machine.clone(), state.clone());
+        let disabled = self.disabled.entry(key.clone()).or_default();
+        disabled.retain(|i| *i != index);
+        if !enabled {
+            disabled.push(index);
+        }
+        if disabled.is_empty() {
+            self.disabled.remove(&key);
         }
     }
+    /// Whether the transition at `index` of `state` in `machine` is enabled,
+    /// see [`NestedMachine::set_transition_enabled`]
+    pub fn is_transition_enabled(&self, machine: &SmHandle, state: &SHandle, index: usize) -> bool {
+        let key = (machine.clone(), state.clone());
+        self.disabled
+            .get(&key)
+            .is_none_or(|disabled| !disabled.contains(&index))
+    }
+    /// Pass what each transition decides through `middleware`, which
+    /// returns the `Target` to act on instead
+    ///
+    /// `middleware` is called in `update`, after each transition's
+    /// [`Transition::decide`] (or [`Transition::decide_event`]), with the
+    /// transition's location and its `Target`, even [`Target::Continue`]. The
+    /// returned `Target` is then handled as if the transition returned it,
+    /// so `middleware` can log decisions, force a transition that didn't
+    /// fire, or cancel one that did by returning `Target::Continue`. The
+    /// transitions after the first one that fires are not evaluated, and
+    /// not passed to `middleware`. Neither are disabled transitions, the
+    /// state's timeout, or the [`crate::StateMachines::with_interrupts`]
+    /// transitions.
+    ///
+    /// The middleware is synchronous, also with `update_async`, and is part
+    /// of this `NestedMachine` only, like
+    /// [`NestedMachine::set_transition_enabled`]. It replaces the previous
+    /// one, if any.
+    pub fn set_transition_middleware(
+        &mut self,
+        middleware: impl FnMut(TransitionContext, Target) -> Target + Send + Sync + 'static,
+    ) {
+        self.middleware = Some(Box::new(middleware));
+    }
+    /// Remove the middleware set with
+    /// [`NestedMachine::set_transition_middleware`]
+    pub fn clear_transition_middleware(&mut self) {
+        self.middleware = None;
+    }
     /// Initialize a `NestedMachine` with the first `State` of the first
     /// `Machine` activated.
     pub fn new_active() -> Self {
-        let stack = vec![Machine::new(SmHandle(0))];
-        NestedMachine { stack }
+        let mut stack = SmallVec::new();
+        stack.push(Machine::new(SmHandle(0)));
+        Self::with_stack(stack)
+    }
+    /// Initialize a `NestedMachine` already nested in the provided machines
+    ///
+    /// `path` is a list of `(machine name, state name)` pairs: the first one
+    /// is the outermost machine, the last one is the currently running
+    /// machine. Each machine is in the given state, and each of their
+    /// `StateData` is fresh. An empty `path` is equivalent to
+    /// [`NestedMachine::new`].
+    pub fn from_path<B, T>(
+        machines: &crate::StateMachines<B, T>,
+        path: &[(&str, &str)],
+    ) -> Result<Self, Error> {
+        let mut stack = SmallVec::with_capacity(path.len());
+        for (machine_name, state_name) in path {
+            let machine = machines
+                .machine_handle(machine_name)
+                .ok_or_else(|| Error::UnknownMachineName((*machine_name).to_owned()))?;
+            let id = machines.state_handle(&machine, state_name).ok_or_else(|| {
+                Error::UnknownStateName {
+                    machine: machine.clone(),
+                    name: (*state_name).to_owned(),
+                }
+            })?;
+            stack.push(Machine::with_state(id.machine, id.state));
+        }
+        Ok(Self::with_stack(stack))
     }
     /// Enter the nested state described by [`SmHandle`]
-    pub fn enter(&mut self, machine: &SmHandle) {
-        self.stack.push(Machine::new(machine.clone()));
+    ///
+    /// This pushes `machine` on the stack, returns the new stack depth. This
+    /// resets [`NestedMachine::is_complete`], and the validation of
+    /// [`NestedMachine::validate_against`], since `machine` may not exist.
+    pub fn enter(&mut self, machine: &SmHandle) -> usize {
+        self.validated = None;
+        self.push(Machine::new(machine.clone()))
+    }
+    /// Like [`NestedMachine::enter`], starting `machine` in `state` rather
+    /// than its initial state, see [`Target::EnterAt`]
+    ///
+    /// Returns [`Error::BadMachineName`] or [`Error::BadStateName`] if
+    /// `machine` or `state` is not part of `machines`, without entering.
+    pub fn enter_at<B, T>(
+        &mut self,
+        machines: &crate::StateMachines<B, T>,
+        machine: &SmHandle,
+        state: &SHandle,
+    ) -> Result<usize, Error> {
+        if machines.machine(machine).is_none() {
+            return Err(Error::BadMachineName(machine.clone()));
+        }
+        if machines.state_name(machine, state).is_none() {
+            return Err(Error::BadStateName {
+                machine: machine.clone(),
+                state: state.clone(),
+            });
+        }
+        self.validated = None;
+        Ok(self.push(Machine::with_state(machine.clone(), state.clone())))
+    }
+    /// [`NestedMachine::enter`] a machine returned by a transition
+    fn push(&mut self, machine: Machine) -> usize {
+        self.completed = false;
+        self.stack.push(machine);
+        self.stack.len()
+    }
+    /// Check that all machines and states on the stack exist in `machines`
+    ///
+    /// Returns [`Error::BadMachineName`] or [`Error::BadStateName`] for the
+    /// first that doesn't. Otherwise, this `NestedMachine` is marked as
+    /// validated against the [`crate::StateMachines::version`] of
+    /// `machines`, see [`NestedMachine::update_unchecked`]. The validation is
+    /// kept until [`NestedMachine::enter`] is called.
+    pub fn validate_against<B, T>(
+        &mut self,
+        machines: &crate::StateMachines<B, T>,
+    ) -> Result<(), Error> {
+        self.validated = None;
+        for machine in &self.stack {
+            let compiled = machines
+                .machine(&machine.handle)
+                .ok_or_else(|| Error::BadMachineName(machine.handle.clone()))?;
+            for state in &machine.states {
+                if compiled.state(&state.handle).is_none() {
+                    return Err(Error::BadStateName {
+                        machine: machine.handle.clone(),
+                        state: state.handle.clone(),
+                    });
+                }
+            }
+        }
+        self.validated = Some(machines.version());
+        Ok(())
+    }
+    /// Whether [`NestedMachine::validate_against`] succeeded with this
+    /// version of `machines`
+    pub fn is_validated_against<B, T>(&self, machines: &crate::StateMachines<B, T>) -> bool {
+        self.validated == Some(machines.version())
+    }
+    /// Enter the nested machine named `name` in `machines`
+    ///
+    /// Like [`NestedMachine::enter`], this returns the new stack depth. Returns
+    /// [`Error::UnknownMachineName`] if there is no such machine.
+    pub fn enter_by_name<B, T>(
+        &mut self,
+        machines: &crate::StateMachines<B, T>,
+        name: &str,
+    ) -> Result<usize, Error> {
+        let machine = machines
+            .machine_handle(name)
+            .ok_or_else(|| Error::UnknownMachineName(name.to_owned()))?;
+        Ok(self.enter(&machine))
+    }
+    /// Leave the current machine, dropping its state
+    ///
+    /// This pops the current machine from the stack and returns its handle,
+    /// the machine that entered it resumes on next `update`. When popping the
+    /// last machine, the `NestedMachine` becomes inactive, like
+    /// [`NestedMachine::new`]. Returns `None` if the stack is already empty.
+    ///
+    /// Unlike a [`Target::PopN`], no hook runs: the popped machine's
+    /// [`on_complete`](crate::builder::StateMachine::on_complete) is not
+    /// called, and the state that entered it gets no [`Behavior::on_result`].
+    /// Nothing else is reset either: the machine that entered the popped one
+    /// keeps the state and region it entered from, and popping the last
+    /// machine doesn't make [`NestedMachine::is_complete`] return `true`.
+    pub fn pop(&mut self) -> Option<SmHandle> {
+        let machine = self.stack.pop()?;
+        let handle = machine.handle.clone();
+        self.pool.recycle_machine(machine);
+        Some(handle)
+    }
+    /// How many times each transition fired since this `NestedMachine` was
+    /// created
+    ///
+    /// Counts are kept for the whole lifetime of the `NestedMachine`, for all
+    /// the machines it ran, not only the currently active ones.
+    #[cfg(feature = "profiling")]
+    pub fn transition_stats(&self) -> &TransitionStats {
+        &self.stats
+    }
+    /// Queue `event` for the transitions of the running states
+    ///
+    /// Each `update` takes the oldest queued event, and gives it to the
+    /// interrupts and then to the transitions of the states it updates, see
+    /// [`Transition::decide_event`]. The event is dropped at the end of the
+    /// `update`, even if no transition reacted to it. Events stay queued
+    /// while the `NestedMachine` is inactive.
+    ///
+    /// With [`NestedMachine::update_settled`] and
+    /// [`NestedMachine::update_budgeted`], each state reached through a
+    /// [`Target::Goto`] takes the next event, as if it was a separate
+    /// `update`. A throttled tick keeps its event until it is finished.
+    pub fn send_event<E: Any + Send + Sync>(&mut self, event: E) {
+        self.events.push_back(Box::new(event));
+    }
+    /// How many events sent with [`NestedMachine::send_event`] are waiting
+    /// for an `update`
+    pub fn pending_events(&self) -> usize {
+        self.events.len()
+    }
+    /// Drop all events waiting for an `update`
+    pub fn clear_events(&mut self) {
+        self.events.clear();
     }
     pub fn stack_len(&self) -> usize {
         self.stack.len()
     }
+    /// Whether there is a machine on the stack, so that `update` runs it
+    pub fn is_active(&self) -> bool {
+        !self.stack.is_empty()
+    }
+    /// Whether an `update` returned [`Complete::Done`] since this
+    /// `NestedMachine` was created or last [`NestedMachine::enter`]ed a
+    /// machine
+    ///
+    /// This distinguishes a `NestedMachine` that ran to completion from one
+    /// that was never started, while both are inactive. Emptying the stack
+    /// with [`NestedMachine::pop`] doesn't complete it.
+    pub fn is_complete(&self) -> bool {
+        self.completed
+    }
+    /// `Debug` this `NestedMachine` with the names of its machines and
+    /// states in `machines`, rather than their handles
+    ///
+    /// Handles that are not in `machines` are printed as is.
+    pub fn debug_with<'a, B, T>(
+        &'a self,
+        machines: &'a crate::StateMachines<B, T>,
+    ) -> DebugWith<'a, B, T> {
+        DebugWith {
+            nested: self,
+            machines,
+        }
+    }
+    /// The machine and active state handles of the stack, from the
+    /// outermost machine, with the state of the first region still running
+    fn stack_handles(&self) -> impl Iterator<Item = (SmHandle, SHandle)> + '_ {
+        let to_pair = |machine: &Machine| {
+            let state = machine
+                .state()
+                .map_or(SHandle::INITIAL, |s| s.handle.clone());
+            (machine.handle.clone(), state)
+        };
+        self.stack.iter().map(to_pair)
+    }
+    /// Name of the active state of the current machine
+    ///
+    /// If the machine has several parallel regions, this is the state of the
+    /// first region still running.
     pub fn current_state_name<'a, B, T>(
         &self,
         machines: &'a crate::StateMachines<B, T>,
     ) -> Option<&'a str> {
         let machine = self.stack.last()?;
-        machines.state_name(&machine.handle, &machine.state.handle)
+        machines.state_name(&machine.handle, &machine.state()?.handle)
+    }
+
+    /// The machine and active state of the current machine, see
+    /// [`NestedMachine::current_state_name`]
+    pub fn current_state_id(&self) -> Option<StateId> {
+        let machine = self.stack.last()?;
+        Some(StateId::new(
+            machine.handle.clone(),
+            machine.state()?.handle.clone(),
+        ))
     }
 
     pub fn current_machine_name<'a, B, T>(
@@ -137,35 +1294,658 @@ impl NestedMachine {
         let machine = self.stack.last()?;
         machines.machine_name(&machine.handle)
     }
+    /// Behavior of the active state of the current machine
+    ///
+    /// Like [`NestedMachine::current_state_name`], this is the state of the
+    /// first region still running. Returns `None` if the `NestedMachine` is
+    /// inactive or doesn't come from `machines`.
+    pub fn current_behavior<'a, B, T>(
+        &self,
+        machines: &'a crate::StateMachines<B, T>,
+    ) -> Option<&'a B> {
+        Some(&self.current_state(machines)?.behavior)
+    }
+    /// Transitions of the active state of the current machine, see
+    /// [`NestedMachine::current_behavior`]
+    pub fn current_transitions<'a, B, T>(
+        &self,
+        machines: &'a crate::StateMachines<B, T>,
+    ) -> Option<&'a [T]> {
+        Some(&self.current_state(machines)?.transitions)
+    }
+    fn current_state<'a, B, T>(
+        &self,
+        machines: &'a crate::StateMachines<B, T>,
+    ) -> Option<&'a crate::State<B, T>> {
+        let machine = self.stack.last()?;
+        machines
+            .machine(&machine.handle)?
+            .state(&machine.state()?.handle)
+    }
+
+    /// Run `update` until the state machine completes
+    ///
+    /// Returns [`Error::DidNotComplete`] if it didn't complete after
+    /// `max_ticks` updates, or if it is inactive ([`Complete::Idle`]). Use
+    /// this for machines that are supposed to terminate, such as cutscenes,
+    /// to turn a mis-authored infinite sequence into an error.
+    pub fn run_to_completion<'w, 's, B, Trs, Ctx>(
+        &mut self,
+        machines: &crate::StateMachines<B, Trs>,
+        ctx: &mut Ctx,
+        max_ticks: usize,
+    ) -> Result<(), Error>
+    where
+        B: Behavior<Context<'w, 's> = Ctx> + 'static,
+        Trs: Transition<Context<'w, 's> = Ctx> + 'static,
+    {
+        for ticks in 0..max_ticks {
+            match self.update(machines, ctx)? {
+                Complete::Done => return Ok(()),
+                Complete::Running | Complete::Throttled => {}
+                Complete::Idle => return Err(Error::DidNotComplete { ticks }),
+            }
+        }
+        Err(Error::DidNotComplete { ticks: max_ticks })
+    }
+    /// Run `update` until the state machine completes, an alias of
+    /// [`NestedMachine::run_to_completion`]
+    ///
+    /// Returns [`Error::DidNotComplete`] if it didn't complete after
+    /// `max_ticks` updates, or if it is inactive ([`Complete::Idle`]).
+    pub fn update_until_complete<'w, 's, B, Trs, Ctx>(
+        &mut self,
+        machines: &crate::StateMachines<B, Trs>,
+        ctx: &mut Ctx,
+        max_ticks: usize,
+    ) -> Result<(), Error>
+    where
+        B: Behavior<Context<'w, 's> = Ctx> + 'static,
+        Trs: Transition<Context<'w, 's> = Ctx> + 'static,
+    {
+        self.run_to_completion(machines, ctx, max_ticks)
+    }
+
+    /// Run the current state's behavior and transitions, and act on the
+    /// [`Target`] of the first transition that fired
+    ///
+    /// * [`Target::Continue`]: nothing to do
+    /// * [`Target::Goto`]: the state is replaced by a fresh one
+    /// * [`Target::Enter`]: the nested machine is pushed on the stack
+    /// * [`Target::EnterOnce`]: same as `Enter`, if the current state didn't
+    ///   already enter this machine with `EnterOnce`
+    /// * [`Target::Complete`]: the region of the state completes, once all
+    ///   regions completed the current machine is popped from the stack,
+    ///   when there is no more machines on the stack, returns [`Complete::Done`]
+    /// * [`Target::PopN`]: up to `n` machines are popped from the stack,
+    ///   whatever their running regions, nothing happens with `PopN(0)`
+    ///
+    /// When there is no machine on the stack, because the `NestedMachine` was
+    /// never started or already completed, this does nothing and returns
+    /// [`Complete::Idle`].
+    ///
+    /// Before all this, the interrupts of `machines` are evaluated, see
+    /// [`crate::StateMachines::with_interrupts`].
+    ///
+    /// `Target` is `#[non_exhaustive]`, the library always handles all of
+    /// its variants here. Code outside of this crate matching on `Target` (for
+    /// example in adapters wrapping a `Transition`) should treat variants it
+    /// doesn't know of like the `Target` of any fired transition: pass them
+    /// through unchanged.
+    pub fn update<'w, 's, B, Trs, Ctx>(
+        &mut self,
+        machines: &crate::StateMachines<B, Trs>,
+        ctx: &mut Ctx,
+    ) -> Result<Complete, Error>
+    where
+        B: Behavior<Context<'w, 's> = Ctx> + 'static,
+        Trs: Transition<Context<'w, 's> = Ctx> + 'static,
+    {
+        let budget = &mut self.config.budget();
+        self.update_goto(machines, ctx, budget, Duration::ZERO, true)
+            .map(|(complete, _)| complete)
+    }
+
+    /// Same as [`NestedMachine::update`], `dt` is the time since the previous
+    /// update, to fire the timeouts of states
+    ///
+    /// Each state counts the `dt` of the updates it ran in, starting with the
+    /// update that entered it, so a state with a timeout of 1 second, updated
+    /// with a `dt` of 0.5 seconds, times out on its second update. Once it
+    /// ran for its [`builder::State::timeout`], its timeout target fires if no
+    /// transition of the state fired: the timeout is checked last, after all
+    /// transitions, and [`Behavior::can_exit`] can veto it. The count
+    /// restarts when the timeout fires, so a timeout that enters a nested
+    /// machine fires again one timeout after the nested machine completes.
+    ///
+    /// Time is only counted for the states that are updated: states of
+    /// machines that entered a nested machine are not, neither are states
+    /// updated with the `update` methods without a `dt`, see also
+    /// [`NestedMachine::update_async_timed`]. With the `profiling`
+    /// feature, a timeout is counted as the transition following the last
+    /// one of its state.
+    ///
+    /// [`builder::State::timeout`]: crate::builder::State::timeout
+    pub fn update_timed<'w, 's, B, Trs, Ctx>(
+        &mut self,
+        machines: &crate::StateMachines<B, Trs>,
+        ctx: &mut Ctx,
+        dt: Duration,
+    ) -> Result<Complete, Error>
+    where
+        B: Behavior<Context<'w, 's> = Ctx> + 'static,
+        Trs: Transition<Context<'w, 's> = Ctx> + 'static,
+    {
+        let budget = &mut self.config.budget();
+        self.update_goto(machines, ctx, budget, dt, true)
+            .map(|(complete, _)| complete)
+    }
 
-    pub fn update<'w, 's, 'ww, 'ss, B, Trs, Wrd, Updt>(
+    /// Same as [`NestedMachine::update`], but looks up machines and states by
+    /// indexing, without checking they exist
+    ///
+    /// This is only faster when this `NestedMachine` was validated against
+    /// the current version of `machines` with
+    /// [`NestedMachine::validate_against`], otherwise it is `update`. Once
+    /// validated, all machines and states it reaches through the targets of
+    /// transitions are assumed to exist, which is the case for targets from
+    /// a [`crate::builder::NameMapping`]. A transition returning a handle
+    /// that doesn't exist makes this panic, rather than return
+    /// [`Error::BadMachineName`] or [`Error::BadStateName`].
+    pub fn update_unchecked<'w, 's, B, Trs, Ctx>(
         &mut self,
         machines: &crate::StateMachines<B, Trs>,
-        commands: &mut Updt,
-        world: &Wrd,
+        ctx: &mut Ctx,
     ) -> Result<Complete, Error>
     where
-        B: Behavior<Update<'w, 's> = Updt, World<'ww, 'ss> = Wrd> + 'static,
-        Trs: Transition<World<'ww, 'ss> = Wrd> + 'static,
+        B: Behavior<Context<'w, 's> = Ctx> + 'static,
+        Trs: Transition<Context<'w, 's> = Ctx> + 'static,
     {
-        use Complete::{Done, Running};
+        let budget = &mut self.config.budget();
+        let checked = !self.is_validated_against(machines);
+        self.update_goto(machines, ctx, budget, Duration::ZERO, checked)
+            .map(|(complete, _)| complete)
+    }
+
+    /// Run `update` until reaching a state that doesn't [`Target::Goto`]
+    /// another state, following at most `max_hops` `Goto`s
+    ///
+    /// This lets "transient" states, that immediately route to another
+    /// state, be resolved within a single tick: each state reached this way
+    /// runs its behavior and transitions, as with `update`. Stops at anything
+    /// else than a `Goto`, like entering or completing a machine. Other
+    /// parallel regions of the machine are updated again on each hop.
+    ///
+    /// Returns [`Error::DidNotSettle`] if the state reached after `max_hops`
+    /// `Goto`s also `Goto`ed, which is likely a cycle. The last `Goto` was
+    /// still applied.
+    pub fn update_settled<'w, 's, B, Trs, Ctx>(
+        &mut self,
+        machines: &crate::StateMachines<B, Trs>,
+        ctx: &mut Ctx,
+        max_hops: u32,
+    ) -> Result<Complete, Error>
+    where
+        B: Behavior<Context<'w, 's> = Ctx> + 'static,
+        Trs: Transition<Context<'w, 's> = Ctx> + 'static,
+    {
+        let mut hops = 0;
+        loop {
+            let budget = &mut Budget::unlimited();
+            let (complete, goto) = self.update_goto(machines, ctx, budget, Duration::ZERO, true)?;
+            if !goto {
+                return Ok(complete);
+            }
+            if hops == max_hops {
+                let current = self.stack.last().expect("a `Goto` never empties the stack");
+                return Err(Error::DidNotSettle {
+                    machine: current.handle.clone(),
+                    state: current
+                        .state()
+                        .map_or(SHandle::INITIAL, |s| s.handle.clone()),
+                    hops,
+                });
+            }
+            hops += 1;
+        }
+    }
 
-        let current = self.stack.last_mut().ok_or(Error::EmptyStack)?;
+    /// Run `update` several times in a single tick, to resolve `Goto`s,
+    /// staying within `budget` evaluations
+    ///
+    /// Each behavior update counts as one evaluation, and updating a state
+    /// counts all its transitions, even when the first fires. Interrupts are
+    /// not counted. The state machine is updated as with
+    /// [`NestedMachine::update_settled`], without a hop limit: this returns
+    /// once reaching a state that doesn't `Goto` another state, as
+    /// `update`. When the next state to update doesn't fit in what is left of
+    /// `budget`, returns [`Complete::Throttled`] instead, so that you can
+    /// resume on the next frame.
+    ///
+    /// A partially processed tick resumes consistently: throttling happens
+    /// between the updates of two regions, never between the behavior and the
+    /// transitions of a state. The next update, whether it is
+    /// `update_budgeted` or `update`, starts at the first region that wasn't
+    /// updated, without evaluating the interrupts again, so that each region
+    /// is updated once per tick. When throttled after a `Goto`, the next
+    /// update is a new tick. The first state updated by a call is always
+    /// updated, even if it doesn't fit in `budget`, so each call makes
+    /// progress.
+    ///
+    /// Stuck detection (see [`NestedMachine::with_stuck_threshold`]) only
+    /// counts finished ticks.
+    pub fn update_budgeted<'w, 's, B, Trs, Ctx>(
+        &mut self,
+        machines: &crate::StateMachines<B, Trs>,
+        ctx: &mut Ctx,
+        budget: usize,
+    ) -> Result<Complete, Error>
+    where
+        B: Behavior<Context<'w, 's> = Ctx> + 'static,
+        Trs: Transition<Context<'w, 's> = Ctx> + 'static,
+    {
+        let budget = &mut Budget::new(budget);
+        loop {
+            let (complete, goto) = self.update_goto(machines, ctx, budget, Duration::ZERO, true)?;
+            if !goto {
+                return Ok(complete);
+            }
+            if budget.left == 0 {
+                return Ok(Complete::Throttled);
+            }
+        }
+    }
+
+    /// `update`, also returns whether the current machine `Goto`ed a state
+    ///
+    /// When not `checked`, the stack is assumed to be valid for `machines`.
+    fn update_goto<'w, 's, B, Trs, Ctx>(
+        &mut self,
+        machines: &crate::StateMachines<B, Trs>,
+        ctx: &mut Ctx,
+        budget: &mut Budget,
+        dt: Duration,
+        checked: bool,
+    ) -> Result<(Complete, bool), Error>
+    where
+        B: Behavior<Context<'w, 's> = Ctx> + 'static,
+        Trs: Transition<Context<'w, 's> = Ctx> + 'static,
+    {
+        // Interrupts already ran at the start of a throttled tick
+        let resuming = self.stack.last().is_some_and(|m| m.resume.is_some());
+        if !resuming {
+            self.next_event();
+            if let Some(complete) = self.interrupt(machines, ctx) {
+                self.event = None;
+                return Ok((complete, false));
+            }
+        }
+        let current = match self.stack.last_mut() {
+            Some(current) => current,
+            None => return Ok((Complete::Idle, false)),
+        };
+        let machine = match checked {
+            true => machines
+                .machine(&current.handle)
+                .ok_or_else(|| Error::BadMachineName(current.handle.clone()))?,
+            false => machines.machine_indexed(&current.handle),
+        };
+        let regions = current.states.len();
+        let tick = Tick {
+            event: self.event.as_ref(),
+            dt,
+            checked,
+            disabled: &self.disabled,
+            middleware: self.middleware.as_deref_mut(),
+        };
+        let target =
+            match current.update(machine, &mut self.stats, &mut self.pool, ctx, budget, tick)? {
+                Some(target) => target,
+                None => return Ok((Complete::Throttled, false)),
+            };
+        self.event = None;
+        let goto = matches!(target, Target::Goto(_));
+        Ok((self.apply(target, regions)?, goto))
+    }
+
+    /// Same as [`NestedMachine::update`], for [`AsyncBehavior`]s
+    ///
+    /// The behavior of each active region is awaited in turn, then its
+    /// transitions are evaluated synchronously, like in `update`. The other
+    /// hooks of [`AsyncBehavior`] are synchronous, and called at the same
+    /// points as the ones of [`Behavior`].
+    ///
+    /// Unlike `update`, this doesn't use the `budget` of the
+    /// [`NestedMachineConfig`].
+    pub async fn update_async<'w, 's, B, Trs, Ctx>(
+        &mut self,
+        machines: &crate::StateMachines<B, Trs>,
+        ctx: &mut Ctx,
+    ) -> Result<Complete, Error>
+    where
+        B: AsyncBehavior<Context<'w, 's> = Ctx> + 'static,
+        Trs: Transition<Context<'w, 's> = Ctx> + 'static,
+    {
+        self.update_async_timed(machines, ctx, Duration::ZERO).await
+    }
+
+    /// Same as [`NestedMachine::update_async`], `dt` is the time since the
+    /// previous update, to fire the timeouts of states, see
+    /// [`NestedMachine::update_timed`]
+    pub async fn update_async_timed<'w, 's, B, Trs, Ctx>(
+        &mut self,
+        machines: &crate::StateMachines<B, Trs>,
+        ctx: &mut Ctx,
+        dt: Duration,
+    ) -> Result<Complete, Error>
+    where
+        B: AsyncBehavior<Context<'w, 's> = Ctx> + 'static,
+        Trs: Transition<Context<'w, 's> = Ctx> + 'static,
+    {
+        self.next_event();
+        if let Some(complete) = self.interrupt(machines, ctx) {
+            self.event = None;
+            return Ok(complete);
+        }
+        let current = match self.stack.last_mut() {
+            Some(current) => current,
+            None => return Ok(Complete::Idle),
+        };
         let machine = machines
             .machine(&current.handle)
-            .ok_or(Error::BadMachineName)?;
-        let target = current.update(machine, commands, world)?;
+            .ok_or_else(|| Error::BadMachineName(current.handle.clone()))?;
+        let regions = current.states.len();
+        let tick = Tick {
+            event: self.event.as_ref(),
+            dt,
+            checked: true,
+            disabled: &self.disabled,
+            middleware: self.middleware.as_deref_mut(),
+        };
+        let pool = &mut self.pool;
+        let target = current.update_async(machine, &mut self.stats, pool, ctx, tick);
+        let target = target.await?;
+        self.event = None;
+        self.apply(target, regions)
+    }
+
+    /// Take the oldest queued event for this tick, if there is a running
+    /// machine to update with it
+    fn next_event(&mut self) {
+        if self.is_active() {
+            self.event = self.events.pop_front();
+        }
+    }
+
+    /// Evaluate the interrupts of `machines`, and unwind the stack if one
+    /// fired, returns `None` if none fired
+    fn interrupt<'w, 's, B, Trs, Ctx>(
+        &mut self,
+        machines: &crate::StateMachines<B, Trs>,
+        ctx: &mut Ctx,
+    ) -> Option<Complete>
+    where
+        Trs: Transition<Context<'w, 's> = Ctx>,
+    {
+        let interrupts = machines.interrupts();
+        if interrupts.is_empty() {
+            return None;
+        }
+        let current = self.stack.last_mut()?;
+        self.interrupts
+            .start(interrupts, false, Duration::ZERO, &mut self.pool);
+        let inputs = Inputs {
+            event: self.event.as_ref(),
+            ..Inputs::default()
+        };
+        let can_exit = |_: &StateData, _: &Ctx| true;
+        let (_, target) = self.interrupts.decide(
+            interrupts.iter().enumerate(),
+            &mut current.data,
+            ctx,
+            inputs,
+            false,
+            can_exit,
+        )?;
+        self.pool.recycle(&mut self.interrupts);
+        self.interrupts = State::new(SHandle::INITIAL);
+        self.ticks_unchanged = 0;
         match target {
-            Target::Enter(nested_machine) => {
-                self.enter(&nested_machine);
-                Ok(Running)
+            Target::Enter(machine) | Target::EnterOnce(machine) => {
+                self.truncate(0);
+                self.push(Machine::new(machine));
+            }
+            Target::EnterAt(machine, state) => {
+                self.truncate(0);
+                self.push(Machine::with_state(machine, state));
+            }
+            Target::Goto(state) => {
+                self.truncate(1);
+                let root = &mut self.stack[0];
+                let root =
+                    core::mem::replace(root, Machine::with_state(root.handle.clone(), state));
+                self.pool.recycle_machine(root);
             }
             Target::Complete => {
-                self.stack.pop();
-                let empty = self.stack.is_empty();
-                Ok(if empty { Done } else { Running })
+                self.truncate(0);
+                self.completed = true;
+                return Some(Complete::Done);
             }
+            Target::PopN(n) => return Some(self.pop_n(n)),
+            Target::Continue => {}
+        }
+        Some(Complete::Running)
+    }
+
+    /// Act on the `target` returned by the current machine, which had
+    /// `regions` regions running before its update
+    fn apply(&mut self, target: Target, regions: usize) -> Result<Complete, Error> {
+        use Complete::Running;
+
+        let current = self
+            .stack
+            .last()
+            .expect("`apply` is only called after updating the current machine");
+        let stayed = matches!(target, Target::Continue) && current.states.len() == regions;
+        if stayed {
+            self.ticks_unchanged += 1;
+        } else {
+            self.ticks_unchanged = 0;
+        }
+        if let Some(threshold) = self.config.stuck_threshold {
+            if self.ticks_unchanged > threshold {
+                let ticks = core::mem::take(&mut self.ticks_unchanged);
+                return Err(Error::Stuck {
+                    machine: current.handle.clone(),
+                    state: current
+                        .state()
+                        .map_or(SHandle::INITIAL, |s| s.handle.clone()),
+                    ticks,
+                });
+            }
+        }
+        match target {
+            Target::EnterOnce(machine) if self.depth_limit_reached() => {
+                // The machine is not entered, so the `EnterOnce` may fire again
+                let current = self.stack.last_mut().expect("checked above");
+                if let Some(state) = current.states.get_mut(current.entered_from) {
+                    state.entered_once.pop();
+                }
+                let depth = self.stack.len();
+                Err(Error::DepthLimit { machine, depth })
+            }
+            Target::Enter(machine) | Target::EnterAt(machine, _) if self.depth_limit_reached() => {
+                let depth = self.stack.len();
+                Err(Error::DepthLimit { machine, depth })
+            }
+            Target::Enter(nested_machine) | Target::EnterOnce(nested_machine) => {
+                self.push(Machine::new(nested_machine));
+                Ok(Running)
+            }
+            Target::EnterAt(nested_machine, state) => {
+                self.push(Machine::with_state(nested_machine, state));
+                Ok(Running)
+            }
+            Target::Complete => Ok(self.pop_n(1)),
+            Target::PopN(n) => Ok(self.pop_n(n)),
             Target::Continue | Target::Goto(_) => Ok(Running),
         }
     }
+
+    /// Keep only the first `depth` machines of the stack, recycling the
+    /// data of the others
+    fn truncate(&mut self, depth: usize) {
+        for machine in self.stack.drain(depth.min(self.stack.len())..) {
+            self.pool.recycle_machine(machine);
+        }
+    }
+    /// Pop up to `n` machines from the stack, `Done` if it is now empty
+    ///
+    /// The result of the current machine goes to the state that entered the
+    /// outermost popped machine, see [`Behavior::on_result`].
+    fn pop_n(&mut self, n: u8) -> Complete {
+        if n == 0 {
+            return Complete::Running;
+        }
+        let result = self.stack.last_mut().and_then(|m| m.result.take());
+        let depth = self.stack.len().saturating_sub(usize::from(n));
+        self.truncate(depth);
+        let parent = match self.stack.last_mut() {
+            Some(parent) => parent,
+            None => {
+                self.completed = true;
+                return Complete::Done;
+            }
+        };
+        if let Some(state) = parent.states.get_mut(parent.entered_from) {
+            state.result = result;
+        }
+        Complete::Running
+    }
+}
+
+#[cfg(feature = "persist")]
+fn serialize_data<D: SerializableData>(data: &StateData) -> Option<SerializedData<D>> {
+    if data.is::<()>() {
+        return Some(SerializedData::Unit);
+    }
+    D::serialize(data).map(SerializedData::Data)
+}
+#[cfg(feature = "persist")]
+fn deserialize_data<D: SerializableData>(data: SerializedData<D>) -> StateData {
+    match data {
+        SerializedData::Unit => Box::new(()),
+        SerializedData::Data(data) => data.deserialize(),
+    }
+}
+#[cfg(feature = "persist")]
+impl State {
+    fn serialize<D: SerializableData>(&self) -> Option<SerializedState<D>> {
+        let transitions = match &self.transitions {
+            Some(transitions) => Some(
+                transitions
+                    .iter()
+                    .map(serialize_data)
+                    .collect::<Option<_>>()?,
+            ),
+            None => None,
+        };
+        let result = match &self.result {
+            Some(result) => Some(serialize_data(result)?),
+            None => None,
+        };
+        Some(SerializedState {
+            state: self.handle.clone(),
+            behavior: serialize_data(&self.behavior)?,
+            transitions,
+            entered_once: self.entered_once.clone(),
+            result,
+            elapsed: self.elapsed,
+        })
+    }
+    fn deserialize<D: SerializableData>(state: SerializedState<D>) -> Self {
+        State {
+            handle: state.state,
+            behavior: deserialize_data(state.behavior),
+            transitions: state
+                .transitions
+                .map(|transitions| transitions.into_iter().map(deserialize_data).collect()),
+            entered_once: state.entered_once,
+            result: state.result.map(deserialize_data),
+            elapsed: state.elapsed,
+        }
+    }
+}
+#[cfg(feature = "persist")]
+impl Machine {
+    fn serialize<D: SerializableData>(&self) -> Result<SerializedFrame<D>, UnserializableData> {
+        let mut states = Vec::with_capacity(self.states.len());
+        for state in &self.states {
+            let id = || StateId::new(self.handle.clone(), state.handle.clone());
+            let serialized = state.serialize();
+            states.push(serialized.ok_or_else(|| UnserializableData::State(id()))?);
+        }
+        let error = || UnserializableData::Machine(self.handle.clone());
+        let result = match &self.result {
+            Some(result) => Some(serialize_data(result).ok_or_else(error)?),
+            None => None,
+        };
+        Ok(SerializedFrame {
+            machine: self.handle.clone(),
+            states,
+            regions_started: self.regions_started,
+            data: serialize_data(&self.data).ok_or_else(error)?,
+            resume: self.resume.clone(),
+            entered_from: self.entered_from,
+            result,
+        })
+    }
+    fn deserialize<D: SerializableData>(frame: SerializedFrame<D>) -> Self {
+        Machine {
+            handle: frame.machine,
+            states: frame.states.into_iter().map(State::deserialize).collect(),
+            regions_started: frame.regions_started,
+            data: deserialize_data(frame.data),
+            resume: frame.resume,
+            entered_from: frame.entered_from,
+            result: frame.result.map(deserialize_data),
+        }
+    }
+}
+/// Saving and restoring, see the [`crate::persist`] module
+#[cfg(feature = "persist")]
+impl NestedMachine {
+    /// Save the stack of this `NestedMachine` with all its [`StateData`]
+    ///
+    /// Returns which data couldn't be saved if its type is not registered
+    /// in `D`. The `Box<()>` initial data is always saved.
+    pub fn serialize_full<D: SerializableData>(
+        &self,
+    ) -> Result<SerializedMachine<D>, UnserializableData> {
+        let stack: Result<_, _> = self.stack.iter().map(Machine::serialize).collect();
+        let interrupts = self.interrupts.serialize();
+        Ok(SerializedMachine {
+            stack: stack?,
+            interrupts: interrupts.ok_or(UnserializableData::Interrupts)?,
+            completed: self.completed,
+        })
+    }
+    /// Restore a `NestedMachine` saved with [`NestedMachine::serialize_full`]
+    ///
+    /// The stack is [validated against](NestedMachine::validate_against)
+    /// `machines`, this returns [`Error::BadMachineName`] or
+    /// [`Error::BadStateName`] if it refers to machines or states `machines`
+    /// doesn't have. The config is the default one, pass it with
+    /// [`NestedMachine::with_config`] afterward.
+    pub fn deserialize_full<B, T, D: SerializableData>(
+        machines: &crate::StateMachines<B, T>,
+        serialized: SerializedMachine<D>,
+    ) -> Result<Self, Error> {
+        let stack = serialized.stack.into_iter().map(Machine::deserialize);
+        let mut nested = Self::with_stack(stack.collect());
+        nested.interrupts = State::deserialize(serialized.interrupts);
+        nested.completed = serialized.completed;
+        nested.validate_against(machines)?;
+        Ok(nested)
+    }
 }
@@ -4,9 +4,9 @@
 //! definition and its changing state.
 //!
 //! I developed this library for my bevy project. This is why I give generic
-//! lifetime parameters to `Behavior::World`, `Behavior::Update` and
-//! `Transition::World` types. This requires GATs, but I found it was the only way
-//! to get it to work with the horror that is `SystemParam` in bevy.
+//! lifetime parameters to the `Behavior::Context` and `Transition::Context`
+//! types. This requires GATs, but I found it was the only way to get it to work
+//! with the horror that is `SystemParam` in bevy.
 //!
 //! The goal of the library is to have the state machine description be completely
 //! independent from the state. From this, we get a lot of cool stuff that other
@@ -16,7 +16,7 @@
 //! * Minimal mutable data, separate from the state machine. In bevy ECS, I can
 //!   store it independently from the state machine, as a `Component` (while
 //!   the state machines are loaded as `Asset`)
-//! * Shared state machines with as many instances as you want
+//! * Shared state machines with as many instances as you want, see [`shared`]
 //!
 //! There is a few downsides to know before using this library:
 //! * There is more boilerplate to write to get things to work
@@ -35,7 +35,6 @@
 //!
 //! ## Features that may or may not be added in the future
 //!
-//! - [ ] `serde` cargo feature flag to be able to compile the library without serde
 //! - [ ] Better documentation
 //! - [ ] A version without the `StateData` `Box<dyn Any>`
 //! - [ ] Tests
@@ -62,107 +61,805 @@
 //! You will be able to control the execution of a Hierarchical Finite State
 //! Machine (aka HFSM) with the [`label::NestedMachine`], passing it
 //! a [`StateMachines`] when necessary.
-#![feature(generic_associated_types)]
+//!
+//! The [`prelude`] has the types you need for this, and the ready-made
+//! [`behavior::Noop`] and [`transition::Always`], to get started without
+//! implementing `Behavior` and `Transition` for trivial states.
+//!
+//! The [`text`] module parses a [`builder::StateMachines`] from a simpler
+//! indentation-based format, for descriptions written by hand.
+//!
+//! # `no_std`
+//!
+//! The crate only needs `core` and `alloc`. Disable the default `std`
+//! feature to build it without the standard library, this removes the `bevy`
+//! integration and `builder::StateMachines::build_with_registry`, which take
+//! `std` types.
+//!
+//! On targets without atomic compare-and-swap, such as `thumbv6m-none-eabi`,
+//! enable the `critical-section` feature and provide a `critical-section`
+//! implementation, or build single-core programs with
+//! `RUSTFLAGS="--cfg portable_atomic_unsafe_assume_single_core"`. The
+//! [`shared`] module is only available on targets with pointer-sized
+//! atomics.
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+mod analysis;
+pub mod behavior;
+#[cfg(feature = "bevy")]
+pub mod bevy;
 pub mod builder;
-mod de;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod diff;
+pub mod dynamic;
 pub mod label;
+#[cfg(feature = "persist")]
+pub mod persist;
+pub mod prelude;
+#[cfg(target_has_atomic = "ptr")]
+pub mod shared;
+pub mod text;
+pub mod transition;
 
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::convert::Infallible;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::time::Duration;
+use portable_atomic::{AtomicU64, Ordering};
 use smallvec::SmallVec;
-use std::any::Any;
 
 type SHandleInner = u8;
 type SmHandleInner = u16;
+/// The hash map of the crate's internal lookup tables
+type HashMap<K, V> = hashbrown::HashMap<K, V, ahash::RandomState>;
 
+/// Mutable data associated with a running state or machine
+///
+/// Each `State` gets a fresh `StateData` for its [`Behavior`] and each of its
+/// [`Transition`]s when it is entered, it lives until the state is left. A
+/// machine also gets a fresh `StateData` when it is entered, it lives until
+/// the machine completes, so all states of a machine can use it to share data
+/// across state transitions. `StateData` is initially a `Box<()>`.
 pub type StateData = Box<dyn Any + Sync + Send>;
 
+/// The `T` in `data`, initializing it with `T::default()` if `data` is still
+/// the initial `Box<()>`
+///
+/// Use this at the start of [`Behavior::update`] or [`Transition::decide`]
+/// rather than downcasting by hand and handling the first update.
+///
+/// # Panics
+///
+/// If `data` holds something else than `T` or `()`.
+pub fn state_data<T: Default + Any + Send + Sync>(data: &mut StateData) -> &mut T {
+    if data.is::<()>() {
+        *data = Box::<T>::default();
+    }
+    match data.downcast_mut() {
+        Some(data) => data,
+        None => panic!("StateData is not a {}", core::any::type_name::<T>()),
+    }
+}
+
+/// An external event, sent to a running state machine with
+/// [`label::NestedMachine::send_event`]
+///
+/// Events are given to [`Transition::decide_event`], use
+/// `event.downcast_ref::<MyEvent>()` to read them.
+pub type Event = Box<dyn Any + Sync + Send>;
+
+/// How many machines a [`StateMachines`] stores inline
+///
+/// Collections with more machines store them on the heap instead, which is
+/// an extra indirection when looking up a machine. Use
+/// [`StateMachines::capacity_report`] to check whether yours spill.
+pub const INLINE_MACHINES: usize = 8;
+
+/// How many states each machine of a [`StateMachines`] stores inline
+///
+/// Like [`INLINE_MACHINES`], machines with more states store them on the
+/// heap. Note that this is per machine, and states are much larger than
+/// handles: raising it makes every machine bigger, even the tiny ones.
+pub const INLINE_STATES: usize = 2;
+
+/// How many nested machines a [`label::NestedMachine`] stores inline
+///
+/// Deeper hierarchies store the stack on the heap.
+pub const INLINE_DEPTH: usize = 4;
+
 /// Behavior to adopt when in a state
+///
+/// # Context
+///
+/// Everything a behavior reads and changes is bundled in its `Context`. The
+/// context has two lifetime parameters, so that it can be a bevy
+/// `SystemParam` such as:
+///
+/// ```ignore
+/// #[derive(SystemParam)]
+/// struct AiContext<'w, 's> {
+///     positions: Query<'w, 's, &'static Transform>,
+///     commands: Commands<'w, 's>,
+/// }
+/// ```
+///
+/// With `type Context<'w, 's> = AiContext<'w, 's>`. Types without lifetime
+/// parameters can ignore them: `type Context<'w, 's> = MyContext`.
+///
+/// [`Transition`]s get the same context, but read-only. [`Behavior`]s and
+/// [`Transition`]s of a [`StateMachines`] must have the same `Context`.
 pub trait Behavior {
-    /// The world we live in and influnces our behavior
-    type World<'w, 's>;
+    /// The world we live in, influences our behavior and is changed by it
+    type Context<'w, 's>;
 
-    /// Things our behavior changes in the world
-    type Update<'w, 's>;
-
-    /// The behavior, what to do to `commands` given `world`
-    fn update<'w, 's, 'ww, 'ss>(
+    /// The behavior, what to do given and to `ctx`
+    ///
+    /// `data` is the state's own data, `machine_data` is shared with all the
+    /// other states of the current machine (see [`StateData`]).
+    fn update<'w, 's>(
         &self,
         data: &mut StateData,
-        commands: &mut Self::Update<'w, 's>,
-        world: &Self::World<'ww, 'ss>,
+        machine_data: &mut StateData,
+        ctx: &mut Self::Context<'w, 's>,
     );
+
+    /// The initial `data` of this state, `Box::new(())` by default
+    ///
+    /// This is called each time the state is entered, before
+    /// [`Behavior::on_enter`], so that `data` has the right type from the
+    /// first update.
+    fn default_data(&self) -> StateData {
+        Box::new(())
+    }
+
+    /// Setup to do when entering the state, does nothing by default
+    ///
+    /// This is called right before the first [`Behavior::update`] each time
+    /// the state is entered, including when re-entering it through a
+    /// [`Target::Goto`] to itself. Use this rather than tracking the first
+    /// tick in `data`.
+    ///
+    /// There is no exit hook: nothing is called when a state is left, whether
+    /// by one of its transitions, an interrupt or
+    /// [`label::NestedMachine::pop`].
+    fn on_enter<'w, 's>(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        ctx: &mut Self::Context<'w, 's>,
+    ) {
+        let _ = (data, machine_data, ctx);
+    }
+
+    /// Whether the transitions of this state get the behavior's `data`,
+    /// `false` by default
+    ///
+    /// By default, the behavior and each transition of a state have their
+    /// own `StateData`. When this returns `true`, there is a single
+    /// `StateData` for the whole state: the `data` the behavior initialized
+    /// with [`Behavior::default_data`] and updated is passed to each
+    /// transition by [`Transition::decide`], after the behavior's update.
+    /// [`Transition::needs_data`] is then ignored.
+    ///
+    /// Use this when the behavior and transitions work on the same
+    /// information, for example a behavior counting its hits and a
+    /// transition firing after 3 hits, instead of duplicating it. The
+    /// trade-off is that transitions are no longer isolated: they must agree
+    /// on the type of `data` with the behavior, and a transition can change
+    /// what the behavior and the following transitions see. Transitions
+    /// should usually only read the shared `data`.
+    fn shares_data(&self) -> bool {
+        false
+    }
+
+    /// Whether the transitions of this state are evaluated before the
+    /// behavior's update, `false` by default
+    ///
+    /// By default, each update of the state runs [`Behavior::update`], then
+    /// evaluates the transitions, which see the data of this update. When
+    /// this returns `true`, the transitions and timeout are evaluated first,
+    /// with the data of the previous update, and `update` only runs if none
+    /// fired. So the behavior doesn't update on the tick its state is left.
+    /// [`Behavior::on_enter`] and [`Behavior::on_result`] are still called
+    /// before the transitions.
+    fn transitions_first(&self) -> bool {
+        false
+    }
+
+    /// Whether the state can be left, `true` by default
+    ///
+    /// This is called when one of the state's [`Transition`]s fires, with
+    /// the behavior's `data` after its update. When this returns `false`, the
+    /// transition is suppressed, its [`Transition::on_fire`] isn't called and
+    /// the state stays as if no transition fired. Use it for uninterruptible
+    /// phases, such as the middle of an attack animation.
+    ///
+    /// A behavior that never allows exiting keeps its machine in this state
+    /// forever, [`label::NestedMachine::with_stuck_threshold`] catches this.
+    /// Interrupts are not subject to this, see
+    /// [`StateMachines::with_interrupts`].
+    fn can_exit<'w, 's>(&self, data: &StateData, ctx: &Self::Context<'w, 's>) -> bool {
+        let _ = (data, ctx);
+        true
+    }
+
+    /// The result reported to the parent machine when this state completes
+    /// its machine, `None` by default
+    ///
+    /// This is called on the state whose transition returned
+    /// [`Target::Complete`] for the last running region of its machine, or
+    /// a [`Target::PopN`] other than `PopN(0)`, after its update. The result
+    /// is given to [`Behavior::on_result`] of the state that entered the
+    /// machine, see there.
+    fn result(&self, data: &mut StateData, machine_data: &mut StateData) -> Option<StateData> {
+        let _ = (data, machine_data);
+        None
+    }
+
+    /// Read the `result` of the nested machine this state entered, does
+    /// nothing by default
+    ///
+    /// This is called right before the first [`Behavior::update`] after the
+    /// nested machine completed, with what its last state returned from
+    /// [`Behavior::result`]. With [`Target::PopN`], the result goes to the
+    /// state that entered the outermost popped machine. This is not called
+    /// when the nested machine had no result, or when it was popped by an
+    /// interrupt.
+    fn on_result<'w, 's>(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        result: StateData,
+        ctx: &mut Self::Context<'w, 's>,
+    ) {
+        let _ = (data, machine_data, result, ctx);
+    }
+}
+
+/// The future returned by [`AsyncBehavior::update`]
+pub type BoxFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// A [`Behavior`] that needs to `.await` in its update
+///
+/// This is driven by [`label::NestedMachine::update_async`], it is not a
+/// replacement for [`Behavior`]: a `StateMachines` either has `Behavior`s and
+/// is updated with `update` or has `AsyncBehavior`s and is updated with
+/// `update_async`.
+///
+/// Only the behavior's `update` is async, its other hooks and the
+/// [`Transition`]s stay synchronous: transitions are evaluated once the
+/// behavior's future completed, with the same `ctx`.
+///
+/// `async fn` can't be used here, since the future borrows the arguments,
+/// which have generic lifetime parameters. Instead `update` returns a
+/// [`BoxFuture`], use `Box::pin(async move { .. })` in the implementation.
+pub trait AsyncBehavior {
+    /// See [`Behavior::Context`]
+    type Context<'w, 's>;
+
+    /// See [`Behavior::update`]
+    fn update<'a, 'w, 's>(
+        &'a self,
+        data: &'a mut StateData,
+        machine_data: &'a mut StateData,
+        ctx: &'a mut Self::Context<'w, 's>,
+    ) -> BoxFuture<'a>;
+
+    /// See [`Behavior::default_data`]
+    fn default_data(&self) -> StateData {
+        Box::new(())
+    }
+
+    /// See [`Behavior::on_enter`]
+    fn on_enter<'w, 's>(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        ctx: &mut Self::Context<'w, 's>,
+    ) {
+        let _ = (data, machine_data, ctx);
+    }
+
+    /// See [`Behavior::shares_data`]
+    fn shares_data(&self) -> bool {
+        false
+    }
+
+    /// See [`Behavior::transitions_first`]
+    fn transitions_first(&self) -> bool {
+        false
+    }
+
+    /// See [`Behavior::can_exit`]
+    fn can_exit<'w, 's>(&self, data: &StateData, ctx: &Self::Context<'w, 's>) -> bool {
+        let _ = (data, ctx);
+        true
+    }
+
+    /// See [`Behavior::result`]
+    fn result(&self, data: &mut StateData, machine_data: &mut StateData) -> Option<StateData> {
+        let _ = (data, machine_data);
+        None
+    }
+
+    /// See [`Behavior::on_result`]
+    fn on_result<'w, 's>(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        result: StateData,
+        ctx: &mut Self::Context<'w, 's>,
+    ) {
+        let _ = (data, machine_data, result, ctx);
+    }
 }
 
 /// Result of a transition
-#[derive(Clone, Debug)]
+///
+/// New variants may be added in the future, they will be handled by
+/// [`label::NestedMachine::update`]. When matching on a `Target`, you should
+/// treat unknown variants as a fired transition, not as [`Target::Continue`].
+///
+/// With the `serde` feature, `Target` is `Serialize` and `Deserialize`, to
+/// record and replay the decisions of a state machine. Since new variants
+/// may be added, a `Target` serialized with a newer version of this crate may
+/// fail to deserialize with an older one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum Target {
-    /// Keep the current `State`
+    /// Keep the current `State`, its `StateData` is left untouched
     Continue,
     /// Transition into a new `State`
+    ///
+    /// This always creates a fresh `State` with new `StateData`, even when
+    /// the target is the current `State`. A `Goto` to the current `State` is
+    /// therefore a "re-entry": use it to reset the state, use
+    /// [`Target::Continue`] (or its [`Target::Stay`] alias) to keep it.
     Goto(SHandle),
     /// Start a nested `StateMachine`, will come back to this `State` once
     /// the nested state machine completes
     Enter(SmHandle),
+    /// Like [`Target::Enter`], but at most once per visit of the current
+    /// `State`
+    ///
+    /// Once the current `State` entered the nested machine with `EnterOnce`,
+    /// the following `EnterOnce` of the same machine are refused until the
+    /// `State` is left: the transition is treated as if it returned
+    /// [`Target::Continue`], and the next transitions are evaluated. Use this
+    /// for one-shot sub-behaviors, which shouldn't restart when the nested
+    /// machine completes and the state resumes. A [`Target::Goto`] to the
+    /// current state is leaving it, and resets this.
+    EnterOnce(SmHandle),
+    /// Like [`Target::Enter`], but start the nested machine in the given
+    /// state rather than its initial one
+    ///
+    /// The state replaces the initial state of the first region of the
+    /// machine, the other regions start in their initial state. The state
+    /// should be part of the first region.
+    EnterAt(SmHandle, SHandle),
     /// Terminate the state machine
     Complete,
+    /// Terminate the current state machine and the machines that entered
+    /// it, up to `n` machines in total
+    ///
+    /// `PopN(1)` completes the current machine: the machine that entered it
+    /// resumes. In a machine with a single region, this is the same as
+    /// [`Target::Complete`]. With [parallel regions](builder::State::region)
+    /// it is not: `Complete` only ends its own region, and the machine runs
+    /// until all regions completed, while `PopN(1)` pops the machine at
+    /// once, with all its regions. With `PopN(3)`, the machine that
+    /// entered the machine that entered the machine that entered the current
+    /// one resumes. When `n` is larger than the stack depth, this completes
+    /// all machines, like `Complete` in the outermost machine.
+    ///
+    /// `PopN(0)` doesn't pop any machine: the transition fires, but the
+    /// current state stays active like with [`Target::Continue`], and
+    /// neither [`Behavior::result`] nor the machine's `on_complete` run.
+    PopN(u8),
+}
+impl Target {
+    /// Alias for [`Target::Continue`], stay in the current `State` without
+    /// resetting its `StateData`
+    #[allow(non_upper_case_globals)]
+    pub const Stay: Self = Target::Continue;
+
+    /// Parse the text form of a `Target`, see its `Display` impl
+    ///
+    /// Returns `None` if `text` is not the text of a `Target`, for example
+    /// if it was written by a newer version of this crate with new variants.
+    pub fn from_log_str(text: &str) -> Option<Self> {
+        let (variant, arg) = match text.split_once(':') {
+            Some((variant, arg)) => (variant, Some(arg)),
+            None => (text, None),
+        };
+        match (variant, arg) {
+            ("continue", None) => Some(Target::Continue),
+            ("complete", None) => Some(Target::Complete),
+            ("goto", Some(state)) => Some(Target::Goto(SHandle(state.parse().ok()?))),
+            ("enter", Some(machine)) => Some(Target::Enter(SmHandle(machine.parse().ok()?))),
+            ("enter_once", Some(machine)) => {
+                Some(Target::EnterOnce(SmHandle(machine.parse().ok()?)))
+            }
+            ("enter_at", Some(arg)) => {
+                let (machine, state) = arg.split_once(':')?;
+                let machine = SmHandle(machine.parse().ok()?);
+                Some(Target::EnterAt(machine, SHandle(state.parse().ok()?)))
+            }
+            ("pop_n", Some(n)) => Some(Target::PopN(n.parse().ok()?)),
+            _ => None,
+        }
+    }
+}
+/// A compact text form, such as `goto:3`, `enter:1`, `enter_at:1:2` or
+/// `complete`
+///
+/// Handles are written as their index, so the text is only meaningful for
+/// the `StateMachines` the `Target` comes from. It is stable across versions
+/// of this crate, parse it back with [`Target::from_log_str`].
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Continue => f.write_str("continue"),
+            Target::Goto(state) => write!(f, "goto:{}", state.0),
+            Target::Enter(machine) => write!(f, "enter:{}", machine.0),
+            Target::EnterOnce(machine) => write!(f, "enter_once:{}", machine.0),
+            Target::EnterAt(machine, state) => write!(f, "enter_at:{}:{}", machine.0, state.0),
+            Target::Complete => f.write_str("complete"),
+            Target::PopN(n) => write!(f, "pop_n:{n}"),
+        }
+    }
 }
 
 /// Decider for state transition
 ///
 /// Each state has many `Transition`s. `Transition`s are ran every update in
 /// order. The first to return a non-[`Target::Continue`] result will dictate
-/// the next `State` or nested `StateMachine` the machine enters.
+/// the next `State` or nested `StateMachine` the machine enters, the following
+/// transitions are not ran.
+///
+/// The order is guaranteed to be the order of [`builder::State::transitions`],
+/// which is the order in the serialized description: building the
+/// `StateMachines` never reorders transitions.
 pub trait Transition {
-    /// The world to observe to make a transition decision
-    type World<'w, 's>;
+    /// The world to observe to make a transition decision, see
+    /// [`Behavior`] for details
+    type Context<'w, 's>;
+
+    /// To what [`Target`] transition given `ctx`?
+    ///
+    /// `data` is the transition's own data, `machine_data` is shared with all
+    /// the states of the current machine (see [`StateData`]).
+    ///
+    /// `data` is reset each time the state is entered, while `machine_data`
+    /// lives until the machine completes. Use `machine_data` for transitions
+    /// that depend on what happened in previous visits of a state, for
+    /// example to enrage only the second time health drops under a
+    /// threshold: read the count of previous drops here, and increment it
+    /// in [`Transition::on_fire`], so that it only counts drops that made
+    /// the machine change state.
+    fn decide<'w, 's>(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        ctx: &Self::Context<'w, 's>,
+    ) -> Target;
+
+    /// Whether this transition uses its `data`, `true` by default
+    ///
+    /// When `false`, no `StateData` slot is kept for this transition, and the
+    /// `data` passed to [`Transition::decide`] is a throwaway `Box<()>`. When
+    /// no transition of a state needs data, entering the state doesn't
+    /// allocate.
+    ///
+    /// When the update has no event, disabled transition nor middleware, and
+    /// the behavior doesn't [`Behavior::shares_data`], the transitions of a
+    /// state before the first that needs data are evaluated in a tight loop.
+    /// List stateless transitions first to benefit from it, see the
+    /// `predicates` benchmark.
+    ///
+    /// Only return `false` for transitions that are pure predicates over the
+    /// context and `machine_data`, and don't need to remember anything.
+    fn needs_data(&self) -> bool {
+        true
+    }
+
+    /// To what [`Target`] transition in reaction to `event`? Never fires
+    /// by default
+    ///
+    /// This is the event-driven counterpart of [`Transition::decide`]: events
+    /// sent with [`label::NestedMachine::send_event`] are queued, and each
+    /// `update` takes the oldest one. Each transition of the updated states
+    /// is then first given the event with `decide_event`, and if it returns
+    /// [`Target::Continue`], `decide` is called as usual. So transitions are
+    /// still evaluated in order, a transition reacting to the event doesn't
+    /// take precedence over the polled decision of a previous transition.
+    ///
+    /// The event is dropped at the end of the `update`, whether a transition
+    /// reacted to it or not: events no transition of the current states
+    /// reacts to are discarded, they are not kept for later states.
+    fn decide_event<'w, 's>(
+        &self,
+        event: &Event,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        ctx: &Self::Context<'w, 's>,
+    ) -> Target {
+        let _ = (event, data, machine_data, ctx);
+        Target::Continue
+    }
+
+    /// Side effects of this transition firing, does nothing by default
+    ///
+    /// This is called on the transition that returned a non-[`Target::Continue`]
+    /// result, right after [`Transition::decide`], with the same `data`. At
+    /// this point, the [`Behavior::update`] of the state already ran, and the
+    /// state wasn't left yet. Other transitions are not evaluated after one
+    /// fired, so this is called at most once per state and `update`.
+    fn on_fire<'w, 's>(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        ctx: &mut Self::Context<'w, 's>,
+    ) {
+        let _ = (data, machine_data, ctx);
+    }
+}
+
+/// A [`Transition`] that is a pure predicate over the context
+///
+/// All `StatelessTransition`s are [`Transition`]s that do not need any
+/// `StateData`.
+pub trait StatelessTransition {
+    /// See [`Transition::Context`]
+    type Context<'w, 's>;
 
-    /// To what [`Target`] transition given `world`?
-    fn decide<'w, 's>(&self, data: &mut StateData, world: &Self::World<'w, 's>) -> Target;
+    /// To what [`Target`] transition given `ctx`?
+    fn decide<'w, 's>(&self, ctx: &Self::Context<'w, 's>) -> Target;
+}
+impl<T: StatelessTransition> Transition for T {
+    type Context<'w, 's> = T::Context<'w, 's>;
+
+    fn decide<'w, 's>(
+        &self,
+        _: &mut StateData,
+        _: &mut StateData,
+        ctx: &T::Context<'w, 's>,
+    ) -> Target {
+        StatelessTransition::decide(self, ctx)
+    }
+    fn needs_data(&self) -> bool {
+        false
+    }
+}
+
+/// A [`Transition`] that can tell what it may decide
+///
+/// Implement this to enable the static analysis methods of [`StateMachines`],
+/// such as [`StateMachines::enter_cycles`].
+pub trait DescribeTransition {
+    /// All the [`Target`]s this transition may return, [`Target::Continue`]
+    /// can be omitted
+    fn targets(&self) -> Vec<Target>;
 }
 
 /// `State` handle
-#[derive(Debug, Clone, PartialEq)]
+///
+/// A handle is the index of the state in its machine, it is only meaningful
+/// for the machine it was obtained from. Handles can be converted to and from
+/// integers to be stored, nothing checks that the handle of an integer is
+/// valid: methods taking an invalid handle return `None` or an error.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct SHandle(SHandleInner);
 impl SHandle {
     const INITIAL: Self = SHandle(0);
+
+    /// The handle of the `index`th state of a machine
+    pub const fn new(index: u8) -> Self {
+        SHandle(index)
+    }
+    /// The index of the state in its machine
+    pub const fn get(&self) -> u8 {
+        self.0
+    }
+}
+impl From<u8> for SHandle {
+    fn from(index: u8) -> Self {
+        SHandle(index)
+    }
+}
+impl From<SHandle> for u8 {
+    fn from(handle: SHandle) -> Self {
+        handle.0
+    }
 }
 
 /// `StateMachine` handle
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Like [`SHandle`], this is the index of the machine in its
+/// [`StateMachines`], and can be converted to and from integers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct SmHandle(SmHandleInner);
+impl SmHandle {
+    /// The handle of the `index`th machine of a `StateMachines`
+    pub const fn new(index: u16) -> Self {
+        SmHandle(index)
+    }
+    /// The index of the machine in its `StateMachines`
+    pub const fn get(&self) -> u16 {
+        self.0
+    }
+}
+impl From<u16> for SmHandle {
+    fn from(index: u16) -> Self {
+        SmHandle(index)
+    }
+}
+impl From<SmHandle> for u16 {
+    fn from(handle: SmHandle) -> Self {
+        handle.0
+    }
+}
+
+/// A state with the machine it is in
+///
+/// Unlike a lone [`SHandle`], a `StateId` can't be used with the wrong
+/// machine by mistake. It is still only meaningful for the
+/// [`StateMachines`] it was obtained from, see [`StateMachines::resolve`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateId {
+    pub machine: SmHandle,
+    pub state: SHandle,
+}
+impl StateId {
+    pub const fn new(machine: SmHandle, state: SHandle) -> Self {
+        StateId { machine, state }
+    }
+}
+impl From<(SmHandle, SHandle)> for StateId {
+    fn from((machine, state): (SmHandle, SHandle)) -> Self {
+        StateId { machine, state }
+    }
+}
+impl From<StateId> for (SmHandle, SHandle) {
+    fn from(id: StateId) -> Self {
+        (id.machine, id.state)
+    }
+}
 
 /// A classical state machine, you know the deal `:)`
-#[derive(Debug, Clone)]
+///
+/// A machine may have several parallel regions, each with its own active
+/// state, see [`builder::State::region`].
+#[derive(Debug, Clone, PartialEq)]
 struct StateMachine<B, Trs> {
-    states: SmallVec<[State<B, Trs>; 2]>,
+    states: SmallVec<[State<B, Trs>; INLINE_STATES]>,
+    /// Initial state of each region, the first one is always [`SHandle::INITIAL`]
+    regions: SmallVec<[SHandle; 1]>,
+    /// See [`builder::StateMachine::metadata`]
+    metadata: BTreeMap<String, String>,
+    /// See [`builder::StateMachine::on_complete`]
+    on_complete: Option<B>,
 }
 impl<B, T> StateMachine<B, T> {
     fn state<'s>(&'s self, state: &SHandle) -> Option<&'s State<B, T>> {
         self.states.get(state.0 as usize)
     }
+    /// Like [`StateMachine::state`], for handles known to be valid
+    ///
+    /// # Panics
+    ///
+    /// If `state` doesn't exist.
+    fn state_indexed<'s>(&'s self, state: &SHandle) -> &'s State<B, T> {
+        debug_assert!((state.0 as usize) < self.states.len(), "{state:?}");
+        &self.states[state.0 as usize]
+    }
 }
 
 /// State and the transitions in a state machine
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 struct State<B, Trs> {
     /// Criterias for exiting the current State (See [`Transition`])
     transitions: Vec<Trs>,
     /// What to do when in this state (see [`Behavior`])
     behavior: B,
+    /// The parallel region this state is part of
+    region: u8,
+    /// See [`builder::State::timeout`]
+    timeout: Option<(Duration, Target)>,
+    /// See [`builder::State::default`]
+    default: Option<Trs>,
+    /// How many of the first `transitions` don't [`Transition::needs_data`],
+    /// see [`stateless_prefix`]
+    stateless: usize,
+}
+
+/// How many of the first `transitions` don't [`Transition::needs_data`]
+///
+/// Those are evaluated in a tight loop, see [`Transition::needs_data`]. This
+/// is computed when building the machines, so that
+/// [`StateMachines::replace_machine`] and
+/// [`StateMachines::map_transitions`] update it with the transitions.
+fn stateless_prefix<Trs: Transition>(transitions: &[Trs]) -> usize {
+    transitions.iter().take_while(|t| !t.needs_data()).count()
+}
+
+impl<B, Trs> State<B, Trs> {
+    /// The transitions then the default transition, in evaluation order
+    fn all_transitions(&self) -> impl Iterator<Item = &Trs> {
+        self.transitions.iter().chain(&self.default)
+    }
+    /// Index of the timeout, right after the transitions and the default
+    /// transition, see [`builder::State::timeout`]
+    fn timeout_index(&self) -> usize {
+        self.transitions.len() + usize::from(self.default.is_some())
+    }
+    /// Like [`State::all_transitions`], with their index, see
+    /// [`builder::State::default`], skipping the `first` transitions
+    fn candidates(&self, first: usize) -> impl Iterator<Item = (usize, &Trs)> {
+        let default_index = self.transitions.len();
+        let default = self.default.as_ref().map(|t| (default_index, t));
+        let transitions = self.transitions[first..].iter();
+        (first..).zip(transitions).chain(default)
+    }
 }
 
 /// Potental errors from running a state machine
 #[derive(Debug)]
 pub enum Error {
-    EmptyStack,
-    BadMachineName,
-    BadStateName,
+    /// There is no machine with this handle in the [`StateMachines`], it was
+    /// likely created from another `StateMachines`
+    BadMachineName(SmHandle),
+    /// There is no `state` in `machine`
+    BadStateName { machine: SmHandle, state: SHandle },
+    /// There is no machine with this name in the [`StateMachines`]
+    UnknownMachineName(String),
+    /// There is no state named `name` in `machine`
+    UnknownStateName { machine: SmHandle, name: String },
+    /// No transition fired for `ticks` updates in a row, see
+    /// [`label::NestedMachine::with_stuck_threshold`]
+    Stuck {
+        machine: SmHandle,
+        state: SHandle,
+        ticks: u32,
+    },
+    /// The state machine didn't complete after `ticks` updates, see
+    /// [`label::NestedMachine::run_to_completion`]
+    DidNotComplete { ticks: usize },
+    /// `state` of `machine` was reached after `hops` [`Target::Goto`]s in a
+    /// single update, and still `Goto`ed another state, see
+    /// [`label::NestedMachine::update_settled`]
+    DidNotSettle {
+        machine: SmHandle,
+        state: SHandle,
+        hops: u32,
+    },
+    /// The first machine of the merged `StateMachines` has the handle
+    /// `found`, instead of the handle following the last machine, see
+    /// [`StateMachines::merge`]
+    BadMergeOrder { expected: SmHandle, found: SmHandle },
+    /// A transition entered `machine` while the stack was already `depth`
+    /// machines deep, see [`label::NestedMachineConfig::max_depth`]
+    DepthLimit { machine: SmHandle, depth: usize },
 }
 
-// TODO: consider adding a version field to this and S[m]Name and check against
-// this the version, then use indexing ops rather than slice::get, since we
-// know we are running a state machine compatible with the provided S[m]Name
-// there is no risk of out-of-bound access
 /// A collection of state machines
 ///
 /// Each state machines within this collection can refer to each other. You
@@ -172,13 +869,183 @@ pub enum Error {
 /// State machines in this `struct` are represented in a space-efficient and
 /// type-safe format, where it is impossible to refer to non-existing states
 /// and machines.
+///
+/// Two `StateMachines` are equal if they have the same machine and state
+/// names, and the same behaviors and transitions in each state. Comparing
+/// is O(total states + transitions), it is meant to check whether reloading
+/// changed anything, not to be used every update. The
+/// [`StateMachines::version`] is not compared.
 #[derive(Debug)]
 pub struct StateMachines<B, T> {
-    machines: SmallVec<[StateMachine<B, T>; 8]>,
+    machines: SmallVec<[StateMachine<B, T>; INLINE_MACHINES]>,
     machine_names: Vec<String>,
     state_names: Vec<Vec<String>>,
+    /// Transitions evaluated before any state's, see
+    /// [`StateMachines::with_interrupts`]
+    interrupts: Vec<T>,
+    /// See [`StateMachines::version`]
+    version: u64,
+    /// The handle of the first machine, not 0 when built with
+    /// [`builder::StateMachines::build_after`]
+    first_machine: SmHandleInner,
+}
+impl<B: PartialEq, T: PartialEq> PartialEq for StateMachines<B, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.machines == other.machines
+            && self.machine_names == other.machine_names
+            && self.state_names == other.state_names
+            && self.interrupts == other.interrupts
+            && self.first_machine == other.first_machine
+    }
+}
+/// The next [`StateMachines::version`]
+fn next_version() -> u64 {
+    static NEXT_VERSION: AtomicU64 = AtomicU64::new(0);
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
 }
 impl<B, T> StateMachines<B, T> {
+    /// Identifies the states of this `StateMachines`, unique in this process
+    ///
+    /// Each [`builder::StateMachines::build`] gets a new version, larger than
+    /// the previous ones, and so does [`StateMachines::replace_machine`],
+    /// since it changes the states of a machine. A
+    /// [`label::NestedMachine`] validated against a version can run it with
+    /// [`label::NestedMachine::update_unchecked`].
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+    /// Set transitions that are evaluated before any state's on each
+    /// [`label::NestedMachine::update`], whatever the current machine is
+    ///
+    /// Interrupts are evaluated in order before updating the current state,
+    /// the first to fire unwinds the whole stack of nested machines:
+    /// * [`Target::Enter`]: the stack is cleared and the machine entered
+    /// * [`Target::Goto`]: the stack is unwound to a fresh outermost machine,
+    ///   in the given state of that machine
+    /// * [`Target::Complete`]: the stack is cleared, `update` returns
+    ///   [`label::Complete::Done`]
+    /// * [`Target::PopN`]: up to `n` machines are popped from the stack, as
+    ///   in a state's transitions
+    ///
+    /// When an interrupt fires, no state is updated during that `update`.
+    /// Interrupts get the current machine's data as `machine_data`. Their own
+    /// `StateData` is owned by the `NestedMachine`, it lives until one of the
+    /// interrupts fire. Interrupts are not evaluated when the `NestedMachine`
+    /// is inactive.
+    pub fn with_interrupts(mut self, interrupts: Vec<T>) -> Self {
+        self.interrupts = interrupts;
+        self
+    }
+    /// The transitions set with [`StateMachines::with_interrupts`]
+    pub fn interrupts(&self) -> &[T] {
+        &self.interrupts
+    }
+    /// Add the machines of `other` after the ones of `self`
+    ///
+    /// `other` must have been built with [`builder::StateMachines::build_after`]
+    /// the [`builder::NameMapping`] returned by the build of `self`, or of the
+    /// last `StateMachines` merged into `self`, so that its handles follow
+    /// the ones of `self`. Otherwise, this returns [`Error::BadMergeOrder`]
+    /// and `self` is unchanged. The interrupts of `other` are evaluated after
+    /// the ones of `self`. This changes the [`StateMachines::version`].
+    pub fn merge(&mut self, other: Self) -> Result<(), Error> {
+        let expected = usize::from(self.first_machine) + self.machines.len();
+        if usize::from(other.first_machine) != expected {
+            return Err(Error::BadMergeOrder {
+                expected: SmHandle(expected as SmHandleInner),
+                found: SmHandle(other.first_machine),
+            });
+        }
+        // `SmallVec::extend` rounds the capacity up to a power of two
+        self.machines.reserve_exact(other.machines.len());
+        self.machines.extend(other.machines);
+        self.machine_names.extend(other.machine_names);
+        self.state_names.extend(other.state_names);
+        self.interrupts.extend(other.interrupts);
+        self.version = next_version();
+        Ok(())
+    }
+    /// Replace the behavior of each state with `f` of it
+    ///
+    /// The handles, names, transitions and [`StateMachines::version`] are
+    /// kept, `NestedMachine`s running `self` can run the result. `f` is
+    /// called on the states in handle order, machine after machine.
+    pub fn map_behaviors<B2>(self, mut f: impl FnMut(B) -> B2) -> StateMachines<B2, T> {
+        let Ok(machines) = self.try_map(|b| Ok::<_, Infallible>(f(b)), Ok);
+        machines
+    }
+    /// Replace each transition, including interrupts, with `f` of it
+    ///
+    /// Like [`StateMachines::map_behaviors`], the interrupts are mapped last.
+    pub fn map_transitions<T2>(self, mut f: impl FnMut(T) -> T2) -> StateMachines<B, T2>
+    where
+        T2: Transition,
+    {
+        let Ok(machines) = self.try_map_transitions(|t| Ok::<_, Infallible>(f(t)));
+        machines
+    }
+    /// Like [`StateMachines::map_behaviors`], stops at the first error of `f`
+    pub fn try_map_behaviors<B2, E>(
+        self,
+        f: impl FnMut(B) -> Result<B2, E>,
+    ) -> Result<StateMachines<B2, T>, E> {
+        self.try_map(f, Ok)
+    }
+    /// Like [`StateMachines::map_transitions`], stops at the first error of
+    /// `f`
+    pub fn try_map_transitions<T2, E>(
+        self,
+        f: impl FnMut(T) -> Result<T2, E>,
+    ) -> Result<StateMachines<B, T2>, E>
+    where
+        T2: Transition,
+    {
+        let mut machines = self.try_map(Ok, f)?;
+        let states = machines.machines.iter_mut().flat_map(|m| &mut m.states);
+        for state in states {
+            state.stateless = stateless_prefix(&state.transitions);
+        }
+        Ok(machines)
+    }
+    fn try_map<B2, T2, E>(
+        self,
+        mut behavior: impl FnMut(B) -> Result<B2, E>,
+        mut transition: impl FnMut(T) -> Result<T2, E>,
+    ) -> Result<StateMachines<B2, T2>, E> {
+        let mut machines = SmallVec::with_capacity(self.machines.len());
+        for machine in self.machines {
+            let mut states = SmallVec::with_capacity(machine.states.len());
+            for state in machine.states {
+                let behavior = behavior(state.behavior)?;
+                let transitions = state.transitions.into_iter().map(&mut transition);
+                let transitions = transitions.collect::<Result<_, _>>()?;
+                states.push(State {
+                    transitions,
+                    behavior,
+                    region: state.region,
+                    timeout: state.timeout,
+                    default: state.default.map(&mut transition).transpose()?,
+                    // Counted again by `map_transitions`
+                    stateless: state.stateless,
+                });
+            }
+            machines.push(StateMachine {
+                states,
+                regions: machine.regions,
+                metadata: machine.metadata,
+                on_complete: machine.on_complete.map(&mut behavior).transpose()?,
+            });
+        }
+        let interrupts = self.interrupts.into_iter().map(&mut transition);
+        Ok(StateMachines {
+            machines,
+            machine_names: self.machine_names,
+            state_names: self.state_names,
+            interrupts: interrupts.collect::<Result<_, _>>()?,
+            version: self.version,
+            first_machine: self.first_machine,
+        })
+    }
     /// Get all machine names with their handles
     pub fn machines<'s>(&'s self) -> impl Iterator<Item = (SmHandle, &'s str)> {
         let to_name = |(i, n): (_, &'s String)| (SmHandle(i as u16), n.as_ref());
@@ -197,6 +1064,15 @@ impl<B, T> StateMachines<B, T> {
     fn machine<'s>(&'s self, machine: &SmHandle) -> Option<&'s StateMachine<B, T>> {
         self.machines.get(machine.0 as usize)
     }
+    /// Like [`StateMachines::machine`], for handles known to be valid
+    ///
+    /// # Panics
+    ///
+    /// If `machine` doesn't exist.
+    fn machine_indexed<'s>(&'s self, machine: &SmHandle) -> &'s StateMachine<B, T> {
+        debug_assert!((machine.0 as usize) < self.machines.len(), "{machine:?}");
+        &self.machines[machine.0 as usize]
+    }
     fn state_name(&self, machine: &SmHandle, state: &SHandle) -> Option<&str> {
         self.state_names
             .get(machine.0 as usize)
@@ -208,10 +1084,316 @@ impl<B, T> StateMachines<B, T> {
             .get(machine.0 as usize)
             .map(String::as_ref)
     }
+    /// The [`builder::StateMachine::metadata`] of `machine`
+    ///
+    /// `None` if `machine` doesn't exist.
+    pub fn machine_metadata(&self, machine: &SmHandle) -> Option<&BTreeMap<String, String>> {
+        self.machine(machine).map(|machine| &machine.metadata)
+    }
     /// Get machine handle for provided machine name
     pub fn machine_handle(&self, name: &str) -> Option<SmHandle> {
         self.machines()
             .find(|handle| handle.1 == name)
             .map(|hn| hn.0)
     }
+    /// Get a read-only view of the machine named `name`
+    pub fn machine_ref(&self, name: &str) -> Option<MachineRef<'_, B, T>> {
+        let handle = self.machine_handle(name)?;
+        Some(MachineRef {
+            machine: self.machine(&handle)?,
+            name: self.machine_name(&handle)?,
+            state_names: self.state_names.get(handle.0 as usize)?,
+            handle,
+        })
+    }
+    /// Get the id of the state named `name` in `machine`
+    pub fn state_handle(&self, machine: &SmHandle, name: &str) -> Option<StateId> {
+        let (state, _) = self.states(machine)?.find(|handle| handle.1 == name)?;
+        Some(StateId::new(machine.clone(), state))
+    }
+    /// Get the id of the state named `state` in the machine named `machine`
+    pub fn state_id(&self, machine: &str, state: &str) -> Option<StateId> {
+        self.state_handle(&self.machine_handle(machine)?, state)
+    }
+    /// The machine and state names of `id`, `None` if it is not part of this
+    /// collection
+    pub fn resolve(&self, id: &StateId) -> Option<(&str, &str)> {
+        let state = self.state_name(&id.machine, &id.state)?;
+        Some((self.machine_name(&id.machine)?, state))
+    }
+    /// Whether there is a machine named `name`
+    pub fn contains_machine(&self, name: &str) -> bool {
+        self.machine_names.iter().any(|n| n == name)
+    }
+    /// Whether `machine` has a state named `name`
+    ///
+    /// Returns `false` if `machine` doesn't exist.
+    pub fn contains_state(&self, machine: &SmHandle, name: &str) -> bool {
+        let names = self.state_names.get(machine.0 as usize);
+        names.is_some_and(|names| names.iter().any(|n| n == name))
+    }
+    /// How many transitions `state` of `machine` has, not counting its
+    /// [default](builder::State::default)
+    ///
+    /// `None` if `machine` or `state` doesn't exist.
+    pub fn transition_count(&self, machine: &SmHandle, state: &SHandle) -> Option<usize> {
+        Some(self.machine(machine)?.state(state)?.transitions.len())
+    }
+    /// How many states all the machines have in total
+    pub fn total_states(&self) -> usize {
+        self.machines.iter().map(|m| m.states.len()).sum()
+    }
+    /// How many transitions all the states of all the machines have in
+    /// total, not counting the interrupts
+    ///
+    /// Use this to size tables with an entry per transition.
+    pub fn total_transitions(&self) -> usize {
+        let states = self.machines.iter().flat_map(|m| &m.states);
+        states.map(|s| s.transitions.len()).sum()
+    }
+    /// Change the name of `machine` to `new_name`
+    ///
+    /// Names are only used for display and lookup, compiled transitions
+    /// refer to machines by [`SmHandle`], so renaming does not affect how the
+    /// state machines run. Note however that [`builder::NameMapping`] is not
+    /// aware of the rename.
+    pub fn rename_machine(&mut self, machine: &SmHandle, new_name: String) -> Result<(), Error> {
+        let name = self
+            .machine_names
+            .get_mut(machine.0 as usize)
+            .ok_or_else(|| Error::BadMachineName(machine.clone()))?;
+        *name = new_name;
+        Ok(())
+    }
+    /// Change the name of `state` in `machine` to `new_name`
+    ///
+    /// Like [`StateMachines::rename_machine`], this only affects display and
+    /// lookup, compiled transitions refer to states by [`SHandle`].
+    pub fn rename_state(
+        &mut self,
+        machine: &SmHandle,
+        state: &SHandle,
+        new_name: String,
+    ) -> Result<(), Error> {
+        let name = self
+            .state_names
+            .get_mut(machine.0 as usize)
+            .ok_or_else(|| Error::BadMachineName(machine.clone()))?
+            .get_mut(state.0 as usize)
+            .ok_or_else(|| Error::BadStateName {
+                machine: machine.clone(),
+                state: state.clone(),
+            })?;
+        *name = new_name;
+        Ok(())
+    }
+    /// Replace the definition of `machine`, keeping its handle
+    ///
+    /// This builds `new` like [`builder::StateMachines::build`], its
+    /// transitions can enter the machines of `self` by name, and `Goto` its
+    /// own states. The name and metadata of `machine` are replaced with the
+    /// ones of `new`. The other machines are not changed, their
+    /// `Target::Enter(machine)` now enter `new`. Returns
+    /// [`Error::BadMachineName`] if `machine` is not in `self`.
+    ///
+    /// The handles of the states of `new` are their position in `new`. They
+    /// may not match the old states', nothing checks this:
+    /// * [`label::NestedMachine`]s currently in `machine` resume in whatever
+    ///   state of `new` has the handle of their state, or return
+    ///   [`Error::BadStateName`] on update if `new` has fewer states. Reset
+    ///   them, or keep the order of states and only append new ones.
+    /// * [`StateMachines::with_interrupts`] that `Goto` a state of `machine`
+    ///   now go to the state of `new` with that handle.
+    pub fn replace_machine<T2>(
+        &mut self,
+        machine: &SmHandle,
+        new: builder::StateMachine<B, T2>,
+    ) -> Result<(), Error>
+    where
+        T2: builder::IntoTransition<T>,
+        T: Transition,
+    {
+        let index = machine.0 as usize;
+        if index >= self.machines.len() {
+            return Err(Error::BadMachineName(machine.clone()));
+        }
+        let mut machine_names = self.machine_names.clone();
+        machine_names[index] = new.name.clone();
+        let mut state_names = self.state_names.clone();
+        state_names[index] = new.states.iter().map(|s| s.name.clone()).collect();
+        self.machines[index] = new.build_alone(&machine_names, &state_names);
+        self.state_names = state_names;
+        self.machine_names = machine_names;
+        self.version = next_version();
+        Ok(())
+    }
+    /// A readable summary of the machines, their states and how many
+    /// transitions each has
+    ///
+    /// This doesn't need `B` or `T` to be `Debug`. The first line is a total
+    /// count, such as `2 machines, 5 states`, followed by each machine name
+    /// and its states in handle order, one per line:
+    ///
+    /// ```text
+    /// 2 machines, 5 states
+    /// Guard
+    ///   wander: 2 transitions
+    ///   attack: 1 transition
+    ///   look_around: 1 transition (region 1)
+    /// Flee
+    ///   run: 1 transition
+    ///   hide: 0 transitions
+    /// ```
+    ///
+    /// When there are interrupts, a last line says how many. The output is
+    /// stable for identical `StateMachines`, so it can be used in snapshot
+    /// tests.
+    pub fn describe(&self) -> String {
+        use core::fmt::Write;
+
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        let machines = self.machines.len();
+        let states = self.total_states();
+        let mut ret = format!(
+            "{machines} machine{}, {states} state{}\n",
+            plural(machines),
+            plural(states)
+        );
+        let names = self.machine_names.iter().zip(&self.state_names);
+        for (machine, (machine_name, state_names)) in self.machines.iter().zip(names) {
+            let _ = writeln!(ret, "{machine_name}");
+            for (state, state_name) in machine.states.iter().zip(state_names) {
+                let transitions = state.transitions.len();
+                let _ = write!(
+                    ret,
+                    "  {state_name}: {transitions} transition{}",
+                    plural(transitions)
+                );
+                match state.region {
+                    0 => ret.push('\n'),
+                    region => {
+                        let _ = writeln!(ret, " (region {region})");
+                    }
+                }
+            }
+        }
+        let interrupts = self.interrupts.len();
+        if interrupts != 0 {
+            let _ = writeln!(ret, "{interrupts} interrupt{}", plural(interrupts));
+        }
+        ret
+    }
+    /// Release the excess capacity of all collections in the state machines
+    ///
+    /// Useful for long-lived state machines, see
+    /// [`StateMachines::capacity_report`] to check how much memory is wasted.
+    pub fn shrink_to_fit(&mut self) {
+        self.machines.shrink_to_fit();
+        self.machine_names.shrink_to_fit();
+        self.state_names.shrink_to_fit();
+        self.interrupts.shrink_to_fit();
+        for machine in &mut self.machines {
+            machine.states.shrink_to_fit();
+            machine.regions.shrink_to_fit();
+            for state in &mut machine.states {
+                state.transitions.shrink_to_fit();
+            }
+        }
+        for names in &mut self.state_names {
+            names.shrink_to_fit();
+            names.iter_mut().for_each(String::shrink_to_fit);
+        }
+        self.machine_names
+            .iter_mut()
+            .for_each(String::shrink_to_fit);
+    }
+    /// How much heap memory the state machines use and reserve
+    ///
+    /// This only accounts for the collections of the `StateMachines` itself,
+    /// not for memory owned by the `B` and `T` values.
+    pub fn capacity_report(&self) -> CapacityReport {
+        let mut report = CapacityReport::default();
+        report.add_small_vec(&self.machines);
+        report.add_vec(&self.machine_names);
+        report.add_vec(&self.state_names);
+        report.add_vec(&self.interrupts);
+        for machine in &self.machines {
+            report.add_small_vec(&machine.states);
+            report.add_small_vec(&machine.regions);
+            for state in &machine.states {
+                report.add_vec(&state.transitions);
+            }
+        }
+        for names in &self.state_names {
+            report.add_vec(names);
+        }
+        let names = self.state_names.iter().flatten();
+        for name in self.machine_names.iter().chain(names) {
+            report.used += name.len();
+            report.allocated += name.capacity();
+        }
+        report
+    }
+}
+
+/// Heap memory used by a [`StateMachines`], in bytes
+///
+/// Obtained with [`StateMachines::capacity_report`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CapacityReport {
+    /// Bytes occupied by actual elements
+    pub used: usize,
+    /// Bytes allocated, including excess capacity
+    pub allocated: usize,
+}
+impl CapacityReport {
+    fn add_vec<T>(&mut self, vec: &Vec<T>) {
+        self.used += vec.len() * core::mem::size_of::<T>();
+        self.allocated += vec.capacity() * core::mem::size_of::<T>();
+    }
+    /// Inline `SmallVec`s do not use the heap
+    fn add_small_vec<A: smallvec::Array>(&mut self, vec: &SmallVec<A>) {
+        if vec.spilled() {
+            self.used += vec.len() * core::mem::size_of::<A::Item>();
+            self.allocated += vec.capacity() * core::mem::size_of::<A::Item>();
+        }
+    }
+}
+
+/// A read-only view of a single machine of a [`StateMachines`]
+///
+/// Obtained with [`StateMachines::machine_ref`].
+pub struct MachineRef<'a, B, T> {
+    handle: SmHandle,
+    name: &'a str,
+    machine: &'a StateMachine<B, T>,
+    state_names: &'a [String],
+}
+impl<'a, B, T> MachineRef<'a, B, T> {
+    /// Handle of this machine
+    pub fn handle(&self) -> SmHandle {
+        self.handle.clone()
+    }
+    /// Name of this machine
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+    /// All state names with their state handles in this machine
+    pub fn states(&self) -> impl Iterator<Item = (SHandle, &'a str)> {
+        let to_name = |(i, n): (_, &'a String)| (SHandle(i as u8), n.as_ref());
+        self.state_names.iter().enumerate().map(to_name)
+    }
+    /// Transitions of `state`, in the order they are evaluated
+    pub fn transitions(&self, state: &SHandle) -> Option<&'a [T]> {
+        Some(&self.machine.state(state)?.transitions)
+    }
+    /// The transition evaluated after all others of `state`, see
+    /// [`builder::State::default`]
+    pub fn default_transition(&self, state: &SHandle) -> Option<&'a T> {
+        self.machine.state(state)?.default.as_ref()
+    }
+    /// Behavior of `state`
+    pub fn behavior(&self, state: &SHandle) -> Option<&'a B> {
+        Some(&self.machine.state(state)?.behavior)
+    }
 }
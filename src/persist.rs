@@ -0,0 +1,129 @@
+//! Save and restore a running [`NestedMachine`], including its [`StateData`]
+//!
+//! `StateData` is a `Box<dyn Any>`, so saving it needs a list of the data
+//! types it may hold: the data type tags. Register them as the variants of
+//! an enum, one per type used by your behaviors and transitions, derive
+//! `Serialize` and `Deserialize` for it, and implement [`SerializableData`] to
+//! go from a `StateData` to the variant and back. The variant names are the
+//! tags written in the serialized form, they tell which concrete type to
+//! deserialize:
+//!
+//! ```
+//! use pure_hfsm::persist::SerializableData;
+//! use pure_hfsm::StateData;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Patrol { waypoint: usize }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! enum Data {
+//!     Patrol(Patrol),
+//!     Counter(u32),
+//! }
+//! impl SerializableData for Data {
+//!     fn serialize(data: &StateData) -> Option<Self> {
+//!         if let Some(patrol) = data.downcast_ref::<Patrol>() {
+//!             Some(Data::Patrol(Patrol { waypoint: patrol.waypoint }))
+//!         } else {
+//!             data.downcast_ref::<u32>().map(|count| Data::Counter(*count))
+//!         }
+//!     }
+//!     fn deserialize(self) -> StateData {
+//!         match self {
+//!             Data::Patrol(patrol) => Box::new(patrol),
+//!             Data::Counter(count) => Box::new(count),
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! The initial `Box<()>` of states and machines doesn't need registering.
+//! [`NestedMachine::serialize_full`] then gives a [`SerializedMachine<Data>`],
+//! to write with any serde format, and [`NestedMachine::deserialize_full`]
+//! builds back the `NestedMachine`.
+//!
+//! Only the stack and the data of the interrupts are saved: the
+//! [`NestedMachineConfig`](crate::label::NestedMachineConfig), the
+//! [`TransitionStats`](crate::label::TransitionStats), pending events,
+//! disabled transitions and the transition middleware are not.
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(doc)]
+use crate::label::NestedMachine;
+use crate::{SHandle, SmHandle, StateData, StateId, Target};
+
+/// The tagged union of all the types of [`StateData`] to save, see the
+/// [module](self) docs
+pub trait SerializableData: Sized {
+    /// `data` as the variant of its type, `None` if its type is not
+    /// registered
+    fn serialize(data: &StateData) -> Option<Self>;
+    /// The `StateData` of this variant's type
+    fn deserialize(self) -> StateData;
+}
+
+/// A [`StateData`] saved with [`NestedMachine::serialize_full`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SerializedData<D> {
+    /// The initial `Box<()>` of a state or machine
+    Unit,
+    /// Data of a type registered in `D`
+    Data(D),
+}
+
+/// A state of a [`SerializedFrame`], with the data of its behavior and
+/// transitions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SerializedState<D> {
+    pub state: SHandle,
+    pub behavior: SerializedData<D>,
+    /// `None` before the first update of the state
+    pub transitions: Option<Vec<SerializedData<D>>>,
+    pub entered_once: Vec<SmHandle>,
+    /// The result of the nested machine this state entered, not yet given
+    /// to [`crate::Behavior::on_result`]
+    pub result: Option<SerializedData<D>>,
+    pub elapsed: Duration,
+}
+
+/// A machine on the stack of a [`SerializedMachine`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SerializedFrame<D> {
+    pub machine: SmHandle,
+    /// The active state of each region, the first region first
+    pub states: Vec<SerializedState<D>>,
+    pub regions_started: bool,
+    pub data: SerializedData<D>,
+    /// Where a throttled update resumes, see
+    /// [`NestedMachine::update_budgeted`]
+    pub resume: Option<(usize, Target)>,
+    pub entered_from: usize,
+    /// The [`crate::Behavior::result`] that completed this machine
+    pub result: Option<SerializedData<D>>,
+}
+
+/// A [`NestedMachine`] saved with [`NestedMachine::serialize_full`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SerializedMachine<D> {
+    /// The stack, from the outermost machine
+    pub stack: Vec<SerializedFrame<D>>,
+    /// The data of the [`crate::StateMachines::with_interrupts`] transitions
+    pub interrupts: SerializedState<D>,
+    pub completed: bool,
+}
+
+/// The owner of a [`StateData`] whose type is not registered in the
+/// [`SerializableData`] passed to [`NestedMachine::serialize_full`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnserializableData {
+    /// The data of an interrupt transition
+    Interrupts,
+    /// The data shared by the states of this machine
+    Machine(SmHandle),
+    /// The data of this state's behavior or of one of its transitions
+    State(StateId),
+}
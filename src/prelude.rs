@@ -0,0 +1,10 @@
+//! The types needed to describe and run state machines
+//!
+//! ```
+//! use pure_hfsm::prelude::*;
+//! ```
+pub use crate::behavior::Noop;
+pub use crate::builder::{self, IntoTransition, NameMapping};
+pub use crate::label::{Complete, NestedMachine};
+pub use crate::transition::Always;
+pub use crate::{state_data, Behavior, StateData, StateMachines, Target, Transition};
@@ -0,0 +1,98 @@
+//! Sharing one [`StateMachines`] between many instances and threads
+//!
+//! A [`StateMachines`] is never changed by running it, all the mutable data
+//! is in each [`NestedMachine`]. So a single description can be shared by
+//! any number of `NestedMachine`s, including on different threads:
+//! `StateMachines<B, T>` is `Send` and `Sync` when `B` and `T` are, and
+//! `NestedMachine` always is.
+//!
+//! [`SharedStateMachines`] is a cheap to clone handle to such a shared
+//! description, each thread gets a clone and runs its own `NestedMachine`:
+//!
+//! ```
+//! use pure_hfsm::prelude::*;
+//! use pure_hfsm::shared::SharedStateMachines;
+//!
+//! let idle = builder::State::new("Idle", Noop::new(), vec![builder::Target::End]);
+//! let machines = builder::StateMachines(vec![builder::StateMachine::new("Machine", vec![idle])]);
+//! let machines: StateMachines<Noop<()>, Always<()>> = machines.build();
+//! let shared = SharedStateMachines::new(machines);
+//!
+//! let threads: Vec<_> = (0..4)
+//!     .map(|_| {
+//!         let shared = shared.clone();
+//!         std::thread::spawn(move || {
+//!             let mut nested = NestedMachine::new_active();
+//!             shared.update(&mut nested, &mut ()).unwrap()
+//!         })
+//!     })
+//!     .collect();
+//! for thread in threads {
+//!     assert_eq!(thread.join().unwrap(), Complete::Done);
+//! }
+//! ```
+//!
+//! To change the description, such as with [`StateMachines::replace_machine`],
+//! build or edit a new `StateMachines` and share it in place of the old one,
+//! there is no lock to take.
+//!
+//! This module is only available on targets with pointer-sized atomics,
+//! which [`Arc`] needs.
+use alloc::sync::Arc;
+use core::ops::Deref;
+
+use crate::label::{Complete, NestedMachine};
+use crate::{Behavior, Error, StateMachines, Transition};
+
+/// A [`StateMachines`] shared with an [`Arc`], see the [module](self) docs
+///
+/// Cloning only clones the `Arc`. Dereferences to the `StateMachines`, so it
+/// can be passed to all the methods of [`NestedMachine`].
+pub struct SharedStateMachines<B, T>(Arc<StateMachines<B, T>>);
+impl<B, T> SharedStateMachines<B, T> {
+    pub fn new(machines: StateMachines<B, T>) -> Self {
+        SharedStateMachines(Arc::new(machines))
+    }
+    /// The shared `Arc`
+    pub fn as_arc(&self) -> &Arc<StateMachines<B, T>> {
+        &self.0
+    }
+    /// Whether `self` and `other` share the same `StateMachines`
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+    /// [`NestedMachine::update`] `nested` with the shared `StateMachines`
+    pub fn update<'w, 's, Ctx>(
+        &self,
+        nested: &mut NestedMachine,
+        ctx: &mut Ctx,
+    ) -> Result<Complete, Error>
+    where
+        B: Behavior<Context<'w, 's> = Ctx> + 'static,
+        T: Transition<Context<'w, 's> = Ctx> + 'static,
+    {
+        nested.update(&self.0, ctx)
+    }
+}
+impl<B, T> Clone for SharedStateMachines<B, T> {
+    fn clone(&self) -> Self {
+        SharedStateMachines(Arc::clone(&self.0))
+    }
+}
+impl<B, T> Deref for SharedStateMachines<B, T> {
+    type Target = StateMachines<B, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl<B, T> From<StateMachines<B, T>> for SharedStateMachines<B, T> {
+    fn from(machines: StateMachines<B, T>) -> Self {
+        Self::new(machines)
+    }
+}
+impl<B, T> From<Arc<StateMachines<B, T>>> for SharedStateMachines<B, T> {
+    fn from(machines: Arc<StateMachines<B, T>>) -> Self {
+        SharedStateMachines(machines)
+    }
+}
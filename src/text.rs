@@ -0,0 +1,246 @@
+//! A lightweight indentation-based format for [`builder::StateMachines`]
+//!
+//! This is less verbose than the serde representation, and easier to write
+//! by hand:
+//!
+//! ```text
+//! # Comments start with `#`
+//! machine Patrol:
+//!   state Walk [WalkBehavior]:
+//!     -> Idle if Arrived
+//!     -> enter Combat if SeeEnemy
+//!   state Idle [IdleBehavior]:
+//!     -> end if Tired
+//!   state LookAround [LookBehavior] region 1
+//! machine Combat:
+//!   state Fight [FightBehavior]:
+//!     -> pop 2 if Won
+//! ```
+//!
+//! * `machine <name>` lines are not indented.
+//! * `state <name> [<behavior>]` lines are indented, they add a state to the
+//!   last machine. The text between brackets is given to the behavior
+//!   factory of [`parse`]. An optional `region <n>` sets
+//!   [`builder::State::region`].
+//! * `-> <target>` lines are indented more than their state, they add a
+//!   transition to the last state. The target is `<state>` for a
+//!   [`Target::Goto`], `enter <machine>`, `enter once <machine>`,
+//!   `enter <machine> at <state>`, `end` or `pop <n>`. An optional
+//!   `if <condition>` follows, the condition is given to the transition
+//!   factory of [`parse`].
+//!
+//! The trailing `:` of `machine` and `state` lines is optional. Names are a
+//! single word, a state named `end`, `enter` or `pop` can't be targeted.
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::builder::{self, Target};
+
+/// Why [`parse`] failed, see [`ParseError`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The line doesn't follow the syntax, with what was expected
+    Syntax(&'static str),
+    /// A `state` line without a `machine` before it, or a transition
+    /// without a `state`, or that is not indented more than its state
+    Misplaced,
+    /// A transition goes to a state that is not in its machine, or enters a
+    /// machine at a state it doesn't have
+    UnknownState(String),
+    /// A transition enters a machine that is not in the source
+    UnknownMachine(String),
+    /// The behavior factory returned an error
+    Behavior(String),
+    /// The transition factory returned an error
+    Transition(String),
+}
+
+/// An error in the source of [`parse`], at the 1-based `line`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub kind: ErrorKind,
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ErrorKind::Syntax(expected) => write!(f, "expected {expected}"),
+            ErrorKind::Misplaced => write!(f, "misplaced line, check the indentation"),
+            ErrorKind::UnknownState(name) => write!(f, "no state named {name} in this machine"),
+            ErrorKind::UnknownMachine(name) => write!(f, "no machine named {name}"),
+            ErrorKind::Behavior(err) => write!(f, "invalid behavior: {err}"),
+            ErrorKind::Transition(err) => write!(f, "invalid transition: {err}"),
+        }
+    }
+}
+impl core::error::Error for ParseError {}
+
+/// A name a transition refers to, checked once all machines are parsed
+struct Reference {
+    line: usize,
+    machine: usize,
+    target: Target,
+}
+
+/// Parse `source` in the format described in the [module docs](self)
+///
+/// `behavior` creates the behavior of a state from the text between its
+/// brackets. `transition` creates a transition from its condition, if any,
+/// and its target. The target names are checked once the whole source is
+/// parsed, so `transition` is usually a type that also implements
+/// [`builder::IntoTransition`].
+///
+/// Returns the first error, with its line number.
+pub fn parse<B, T>(
+    source: &str,
+    mut behavior: impl FnMut(&str) -> Result<B, String>,
+    mut transition: impl FnMut(Option<&str>, Target) -> Result<T, String>,
+) -> Result<builder::StateMachines<B, T>, ParseError> {
+    let mut machines: Vec<builder::StateMachine<B, T>> = Vec::new();
+    let mut references = Vec::new();
+    // Indentation of the last `state` line of the current machine
+    let mut state_indent = None;
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |kind| ParseError {
+            line: line_number,
+            kind,
+        };
+        let line = line.split('#').next().unwrap_or("").trim_end();
+        let content = line.trim_start();
+        if content.is_empty() {
+            continue;
+        }
+        let indent = line.len() - content.len();
+        let content = content.strip_suffix(':').unwrap_or(content);
+        if let Some(rest) = keyword(content, "machine") {
+            if indent != 0 {
+                return Err(error(ErrorKind::Misplaced));
+            }
+            let name = single_word(rest).ok_or(error(ErrorKind::Syntax("a machine name")))?;
+            machines.push(builder::StateMachine::new(name, Vec::new()));
+            state_indent = None;
+        } else if let Some(rest) = keyword(content, "state") {
+            let machine = match machines.last_mut() {
+                Some(machine) if indent > 0 => machine,
+                _ => return Err(error(ErrorKind::Misplaced)),
+            };
+            let (name, behavior_text, region) = state_line(rest).map_err(error)?;
+            let behavior = behavior(behavior_text).map_err(|e| error(ErrorKind::Behavior(e)))?;
+            machine.states.push(builder::State {
+                region,
+                ..builder::State::new(name, behavior, Vec::new())
+            });
+            state_indent = Some(indent);
+        } else if let Some(rest) = content.strip_prefix("->") {
+            let machine_index = machines.len().wrapping_sub(1);
+            let state = match (machines.last_mut(), state_indent) {
+                (Some(machine), Some(state_indent)) if indent > state_indent => {
+                    machine.states.last_mut()
+                }
+                _ => None,
+            };
+            let state = state.ok_or(error(ErrorKind::Misplaced))?;
+            let (target, condition) = match rest.split_once(" if ") {
+                Some((target, condition)) => (target, Some(condition.trim())),
+                None => (rest, None),
+            };
+            let target =
+                target_of(target).ok_or(error(ErrorKind::Syntax("a transition target")))?;
+            references.push(Reference {
+                line: line_number,
+                machine: machine_index,
+                target: target.clone(),
+            });
+            let transition =
+                transition(condition, target).map_err(|e| error(ErrorKind::Transition(e)))?;
+            state.transitions.push(transition);
+        } else {
+            let expected = "a `machine`, `state` or `->` line";
+            return Err(error(ErrorKind::Syntax(expected)));
+        }
+    }
+    for Reference {
+        line,
+        machine,
+        target,
+    } in references
+    {
+        let machine = &machines[machine];
+        let kind = match target {
+            Target::Goto(name) if !machine.states.iter().any(|s| s.name == name) => {
+                ErrorKind::UnknownState(name)
+            }
+            Target::Enter(name) | Target::EnterOnce(name)
+                if !machines.iter().any(|m| m.name == name) =>
+            {
+                ErrorKind::UnknownMachine(name)
+            }
+            Target::EnterAt(name, state) => match machines.iter().find(|m| m.name == name) {
+                None => ErrorKind::UnknownMachine(name),
+                Some(m) if !m.states.iter().any(|s| s.name == state) => {
+                    ErrorKind::UnknownState(state)
+                }
+                Some(_) => continue,
+            },
+            _ => continue,
+        };
+        return Err(ParseError { line, kind });
+    }
+    Ok(builder::StateMachines(machines))
+}
+
+/// What follows `keyword` in `content`, if it starts with it
+fn keyword<'a>(content: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = content.strip_prefix(keyword)?;
+    match rest.chars().next() {
+        None => Some(rest),
+        Some(c) if c.is_whitespace() => Some(rest),
+        Some(_) => None,
+    }
+}
+
+fn single_word(text: &str) -> Option<&str> {
+    let text = text.trim();
+    let is_word = !text.is_empty() && !text.contains(char::is_whitespace);
+    is_word.then_some(text)
+}
+
+/// The name, behavior and region of a `state` line, after the keyword
+fn state_line(rest: &str) -> Result<(&str, &str, u8), ErrorKind> {
+    let (name, rest) = rest
+        .split_once('[')
+        .ok_or(ErrorKind::Syntax("a [behavior] after the state name"))?;
+    let name = single_word(name).ok_or(ErrorKind::Syntax("a state name"))?;
+    let (behavior, rest) = rest
+        .split_once(']')
+        .ok_or(ErrorKind::Syntax("a `]` after the behavior"))?;
+    let region = match rest.split_whitespace().collect::<Vec<_>>()[..] {
+        [] => 0,
+        ["region", region] => region
+            .parse()
+            .map_err(|_| ErrorKind::Syntax("a region number from 0 to 255"))?,
+        _ => {
+            return Err(ErrorKind::Syntax(
+                "`region <n>` or nothing after the behavior",
+            ))
+        }
+    };
+    Ok((name, behavior.trim(), region))
+}
+
+fn target_of(text: &str) -> Option<Target> {
+    let target = match text.split_whitespace().collect::<Vec<_>>()[..] {
+        ["end"] => Target::End,
+        ["pop", n] => Target::PopN(n.parse().ok()?),
+        ["enter", "once", machine] => Target::EnterOnce(machine.to_owned()),
+        ["enter", machine, "at", state] => Target::EnterAt(machine.to_owned(), state.to_owned()),
+        ["enter", machine] => Target::Enter(machine.to_owned()),
+        [state] => Target::Goto(state.to_owned()),
+        _ => return None,
+    };
+    Some(target)
+}
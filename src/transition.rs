@@ -0,0 +1,457 @@
+//! Ready-made [`Transition`]s
+//!
+//! Adapters such as [`Not`] wrap another transition and have the same context.
+//! The other transitions in this module work with any context type `C` that
+//! does not borrow, for the same reasons as the [`crate::dynamic`] traits.
+//! Each of them also exposes its logic as inherent methods, so that you can
+//! call them from the `decide` of your own transition type.
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use portable_atomic::{AtomicU64, Ordering};
+
+use crate::behavior::Completion;
+use crate::builder::{self, FromTransition, HandleMapping, IntoTransition, NameMapping};
+use crate::{DescribeTransition, Event, StateData, Target, Transition};
+
+/// Always fires `target`
+///
+/// This is the transition of states that always go to the same place, such
+/// as a `Goto` to the next step of a sequence, or the `Complete` of a final
+/// state. Its serialized counterpart is [`builder::Target`]: a
+/// `builder::Target` converts into an `Always` with [`IntoTransition`].
+pub struct Always<C> {
+    target: Target,
+    _context: PhantomData<fn(&C)>,
+}
+impl<C> Always<C> {
+    pub fn new(target: Target) -> Self {
+        Always {
+            target,
+            _context: PhantomData,
+        }
+    }
+    /// What this transition fires
+    pub fn target(&self) -> &Target {
+        &self.target
+    }
+}
+impl<C> Transition for Always<C> {
+    type Context<'w, 's> = C;
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &C) -> Target {
+        self.target.clone()
+    }
+    fn needs_data(&self) -> bool {
+        false
+    }
+}
+impl<C> DescribeTransition for Always<C> {
+    fn targets(&self) -> Vec<Target> {
+        vec![self.target.clone()]
+    }
+}
+/// # Panics
+///
+/// If the target refers to a state or machine that doesn't exist.
+impl<C> IntoTransition<Always<C>> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> Always<C> {
+        match mapping.target(&self) {
+            Some(target) => Always::new(target),
+            None => panic!("{self:?} refers to a state or machine that doesn't exist"),
+        }
+    }
+}
+impl<C> FromTransition<Always<C>> for builder::Target {
+    fn from_with(transition: &Always<C>, mapping: &HandleMapping) -> Option<Self> {
+        mapping.target(&transition.target)
+    }
+}
+
+/// Fires `target` once the behavior of its state is done
+///
+/// The state's behavior must be a [`crate::behavior::Completable`], this
+/// reads the [`Completion`] it shares. Otherwise, this never fires. Its
+/// serialized counterpart is [`builder::Target`], like [`Always`].
+pub struct BehaviorDone<C> {
+    target: Target,
+    _context: PhantomData<fn(&C)>,
+}
+impl<C> BehaviorDone<C> {
+    pub fn new(target: Target) -> Self {
+        BehaviorDone {
+            target,
+            _context: PhantomData,
+        }
+    }
+    /// What this transition fires
+    pub fn target(&self) -> &Target {
+        &self.target
+    }
+    /// Whether `data` is the [`Completion`] of a done behavior
+    pub fn is_done(data: &StateData) -> bool {
+        data.downcast_ref::<Completion>()
+            .is_some_and(|completion| completion.done)
+    }
+}
+impl<C> Transition for BehaviorDone<C> {
+    type Context<'w, 's> = C;
+
+    fn decide<'w, 's>(&self, data: &mut StateData, _: &mut StateData, _: &C) -> Target {
+        match Self::is_done(data) {
+            true => self.target.clone(),
+            false => Target::Continue,
+        }
+    }
+    fn needs_data(&self) -> bool {
+        false
+    }
+}
+impl<C> DescribeTransition for BehaviorDone<C> {
+    fn targets(&self) -> Vec<Target> {
+        vec![self.target.clone()]
+    }
+}
+/// # Panics
+///
+/// If the target refers to a state or machine that doesn't exist.
+impl<C> IntoTransition<BehaviorDone<C>> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> BehaviorDone<C> {
+        match mapping.target(&self) {
+            Some(target) => BehaviorDone::new(target),
+            None => panic!("{self:?} refers to a state or machine that doesn't exist"),
+        }
+    }
+}
+impl<C> FromTransition<BehaviorDone<C>> for builder::Target {
+    fn from_with(transition: &BehaviorDone<C>, mapping: &HandleMapping) -> Option<Self> {
+        mapping.target(&transition.target)
+    }
+}
+
+/// Fires `target` on the `ticks`th update of its state
+///
+/// The countdown is stored in the `StateData` of the transition, so it
+/// starts over each time the state is entered, including with a `Goto` to
+/// the same state. Once it reached zero, this fires on every update, until
+/// the state is left. With `ticks` of 0 or 1, this fires on the first
+/// update. Its serialized counterpart is `(u32, builder::Target)`, the
+/// number of ticks followed by the target.
+pub struct AfterTicks<C> {
+    ticks: u32,
+    target: Target,
+    _context: PhantomData<fn(&C)>,
+}
+impl<C> AfterTicks<C> {
+    pub fn new(ticks: u32, target: Target) -> Self {
+        AfterTicks {
+            ticks,
+            target,
+            _context: PhantomData,
+        }
+    }
+    /// How many updates of its state until this fires
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+    /// What this transition fires
+    pub fn target(&self) -> &Target {
+        &self.target
+    }
+    /// Count down one update in `data`, the transition's data, and return
+    /// whether the countdown reached zero
+    pub fn tick(&self, data: &mut StateData) -> bool {
+        if !data.is::<u32>() {
+            *data = Box::new(self.ticks);
+        }
+        let remaining = data.downcast_mut::<u32>().unwrap();
+        *remaining = remaining.saturating_sub(1);
+        *remaining == 0
+    }
+}
+impl<C> Transition for AfterTicks<C> {
+    type Context<'w, 's> = C;
+
+    fn decide<'w, 's>(&self, data: &mut StateData, _: &mut StateData, _: &C) -> Target {
+        match self.tick(data) {
+            true => self.target.clone(),
+            false => Target::Continue,
+        }
+    }
+}
+impl<C> DescribeTransition for AfterTicks<C> {
+    fn targets(&self) -> Vec<Target> {
+        vec![self.target.clone()]
+    }
+}
+/// # Panics
+///
+/// If the target refers to a state or machine that doesn't exist.
+impl<C> IntoTransition<AfterTicks<C>> for (u32, builder::Target) {
+    fn into_with(self, mapping: &NameMapping) -> AfterTicks<C> {
+        let (ticks, target) = self;
+        match mapping.target(&target) {
+            Some(target) => AfterTicks::new(ticks, target),
+            None => panic!("{target:?} refers to a state or machine that doesn't exist"),
+        }
+    }
+}
+impl<C> FromTransition<AfterTicks<C>> for (u32, builder::Target) {
+    fn from_with(transition: &AfterTicks<C>, mapping: &HandleMapping) -> Option<Self> {
+        Some((transition.ticks, mapping.target(&transition.target)?))
+    }
+}
+
+/// Fires a random target among several weighted choices
+///
+/// A choice of [`Target::Continue`] means "do nothing" with its weight, for
+/// example `[(7, attack), (3, Target::Continue)]` fires `attack` on 70% of
+/// `update`s.
+///
+/// # Randomness
+///
+/// By default, `Random` rolls using its own seedable pseudo-random generator
+/// (SplitMix64). The generator is part of the transition itself rather than
+/// its `StateData`: transition data is reset each time the state is entered,
+/// so it would roll the same sequence on each entry. This means all running
+/// instances of the same `StateMachines` share the generator. Rolls are
+/// deterministic for a given seed as long as the order of `update`s is.
+///
+/// To use your own random number generator instead, for example one stored
+/// in your context, implement your own transition and call [`Random::pick`]
+/// with a uniformly distributed random `u64`.
+pub struct Random<C> {
+    choices: Vec<(u32, Target)>,
+    total_weight: u64,
+    rng: AtomicU64,
+    _context: PhantomData<fn(&C)>,
+}
+impl<C> Random<C> {
+    /// Choose among `choices`, a list of `(weight, target)`, using `seed`
+    ///
+    /// Choices with a weight of 0 are never picked. If all weights are 0,
+    /// this never fires.
+    pub fn new(choices: Vec<(u32, Target)>, seed: u64) -> Self {
+        let total_weight = choices.iter().map(|(weight, _)| u64::from(*weight)).sum();
+        Random {
+            choices,
+            total_weight,
+            rng: AtomicU64::new(seed),
+            _context: PhantomData,
+        }
+    }
+    /// The choices of this transition, as `(weight, target)`
+    pub fn choices(&self) -> &[(u32, Target)] {
+        &self.choices
+    }
+    /// The target corresponding to the `roll` random number
+    ///
+    /// `roll` is a uniformly distributed random number, the chance a choice
+    /// is returned is its weight relative to the sum of all weights.
+    pub fn pick(&self, roll: u64) -> Target {
+        if self.total_weight == 0 {
+            return Target::Continue;
+        }
+        // Lemire's multiply-shift, biased by at most total_weight / 2^64
+        let mut remaining = ((u128::from(roll) * u128::from(self.total_weight)) >> 64) as u64;
+        for (weight, target) in &self.choices {
+            let weight = u64::from(*weight);
+            if remaining < weight {
+                return target.clone();
+            }
+            remaining -= weight;
+        }
+        Target::Continue
+    }
+    /// Roll the internal generator, and return the target picked
+    pub fn roll(&self) -> Target {
+        const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+        let state = self.rng.fetch_add(GOLDEN_GAMMA, Ordering::Relaxed);
+        let mut z = state.wrapping_add(GOLDEN_GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        self.pick(z ^ (z >> 31))
+    }
+}
+impl<C> Transition for Random<C> {
+    type Context<'w, 's> = C;
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &C) -> Target {
+        self.roll()
+    }
+    fn needs_data(&self) -> bool {
+        false
+    }
+}
+impl<C> DescribeTransition for Random<C> {
+    fn targets(&self) -> Vec<Target> {
+        let choices = self.choices.iter().filter(|(weight, _)| *weight != 0);
+        choices.map(|(_, target)| target.clone()).collect()
+    }
+}
+
+/// Fires `target` when the inner transition doesn't fire
+///
+/// When the inner transition fires, `Not` returns [`Target::Continue`], the
+/// inner transition's target is discarded. The `data` of `Not` is the inner
+/// transition's, so stateful transitions work as expected when wrapped.
+/// Since the inner transition never really fires, its
+/// [`Transition::on_fire`] is never called.
+///
+/// `Not` doesn't react to events: its [`Transition::decide_event`] returns
+/// `Continue` without giving the event to the inner transition. Negating it
+/// instead would fire `target` on every event the inner transition ignores,
+/// before the polled decision is even made. With an event, the inner
+/// transition's `decide` is still negated as usual.
+///
+/// Its serialized counterpart is `(S, builder::Target)`, where `S` is the
+/// serialized inner transition, followed by the target.
+pub struct Not<T> {
+    inner: T,
+    target: Target,
+}
+impl<T> Not<T> {
+    /// Fire `target` when `inner` returns `Continue`
+    pub fn new(inner: T, target: Target) -> Self {
+        Not { inner, target }
+    }
+    /// The wrapped transition
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+    /// What this transition fires when the inner transition doesn't
+    pub fn target(&self) -> &Target {
+        &self.target
+    }
+    /// The target of `Not` when the inner transition returned `inner`
+    pub fn negate(&self, inner: &Target) -> Target {
+        match inner {
+            Target::Continue => self.target.clone(),
+            _ => Target::Continue,
+        }
+    }
+}
+impl<T: Transition> Transition for Not<T> {
+    type Context<'w, 's> = T::Context<'w, 's>;
+
+    fn decide<'w, 's>(
+        &self,
+        data: &mut StateData,
+        machine_data: &mut StateData,
+        ctx: &T::Context<'w, 's>,
+    ) -> Target {
+        self.negate(&self.inner.decide(data, machine_data, ctx))
+    }
+    fn needs_data(&self) -> bool {
+        self.inner.needs_data()
+    }
+    fn decide_event<'w, 's>(
+        &self,
+        _: &Event,
+        _: &mut StateData,
+        _: &mut StateData,
+        _: &T::Context<'w, 's>,
+    ) -> Target {
+        Target::Continue
+    }
+}
+impl<T> DescribeTransition for Not<T> {
+    fn targets(&self) -> Vec<Target> {
+        vec![self.target.clone()]
+    }
+}
+/// # Panics
+///
+/// If the target refers to a state or machine that doesn't exist, or if
+/// converting the inner transition panics.
+impl<S: IntoTransition<T>, T> IntoTransition<Not<T>> for (S, builder::Target) {
+    fn into_with(self, mapping: &NameMapping) -> Not<T> {
+        let (inner, target) = self;
+        match mapping.target(&target) {
+            Some(target) => Not::new(inner.into_with(mapping), target),
+            None => panic!("{target:?} refers to a state or machine that doesn't exist"),
+        }
+    }
+}
+impl<S: FromTransition<T>, T> FromTransition<Not<T>> for (S, builder::Target) {
+    fn from_with(transition: &Not<T>, mapping: &HandleMapping) -> Option<Self> {
+        let inner = S::from_with(&transition.inner, mapping)?;
+        Some((inner, mapping.target(&transition.target)?))
+    }
+}
+
+/// A predicate of a [`DecisionTable`] row, over the context and the
+/// machine's data
+pub type Predicate<C> = Box<dyn Fn(&C, &StateData) -> bool + Send + Sync>;
+
+/// Fires the target of the first row whose predicate holds
+///
+/// Rows are evaluated top to bottom, and evaluation stops at the first
+/// predicate that returns `true`, so earlier rows take precedence. For
+/// example, with the rows `(enemy close && ammo low, flee)` then
+/// `(enemy close, attack)`, the second row only needs to check what the
+/// first one didn't. When no predicate holds, this returns
+/// [`Target::Continue`].
+///
+/// A `DecisionTable` has no `StateData` of its own: predicates are given the
+/// `machine_data` of the current machine, to read what previous states
+/// recorded there, and can't change it.
+pub struct DecisionTable<C> {
+    rows: Vec<(Predicate<C>, Target)>,
+}
+impl<C> Default for DecisionTable<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<C> DecisionTable<C> {
+    /// A table without rows, which never fires
+    pub fn new() -> Self {
+        DecisionTable { rows: Vec::new() }
+    }
+    /// Add a row after the existing ones, firing `target` when `predicate`
+    /// holds
+    pub fn with_row(
+        mut self,
+        predicate: impl Fn(&C, &StateData) -> bool + Send + Sync + 'static,
+        target: Target,
+    ) -> Self {
+        self.rows.push((Box::new(predicate), target));
+        self
+    }
+    /// The targets of the rows, in order
+    pub fn targets(&self) -> impl Iterator<Item = &Target> {
+        self.rows.iter().map(|(_, target)| target)
+    }
+    /// Index of the first row whose predicate holds for `ctx` and
+    /// `machine_data`
+    pub fn matching_row(&self, ctx: &C, machine_data: &StateData) -> Option<usize> {
+        self.rows
+            .iter()
+            .position(|(predicate, _)| predicate(ctx, machine_data))
+    }
+    /// The target of the first row whose predicate holds, `Continue` if none
+    pub fn evaluate(&self, ctx: &C, machine_data: &StateData) -> Target {
+        match self.matching_row(ctx, machine_data) {
+            Some(row) => self.rows[row].1.clone(),
+            None => Target::Continue,
+        }
+    }
+}
+impl<C> Transition for DecisionTable<C> {
+    type Context<'w, 's> = C;
+
+    fn decide<'w, 's>(&self, _: &mut StateData, machine_data: &mut StateData, ctx: &C) -> Target {
+        self.evaluate(ctx, machine_data)
+    }
+    fn needs_data(&self) -> bool {
+        false
+    }
+}
+impl<C> DescribeTransition for DecisionTable<C> {
+    fn targets(&self) -> Vec<Target> {
+        DecisionTable::targets(self).cloned().collect()
+    }
+}
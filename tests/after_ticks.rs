@@ -0,0 +1,48 @@
+//! `AfterTicks` fires on the nth update of its state, counting again on entry.
+use pure_hfsm::behavior::Noop;
+use pure_hfsm::builder;
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::transition::AfterTicks;
+use pure_hfsm::StateMachines;
+
+type Machines = StateMachines<Noop<()>, AfterTicks<()>>;
+
+fn description() -> builder::StateMachines<Noop<()>, (u32, builder::Target)> {
+    let state = |name: &str, ticks, next: &str| {
+        builder::State::new(
+            name,
+            Noop::new(),
+            vec![(ticks, builder::Target::Goto(next.to_owned()))],
+        )
+    };
+    builder::StateMachines(vec![builder::StateMachine::new(
+        "Timer",
+        vec![state("Wait", 3, "Done"), state("Done", 2, "Wait")],
+    )])
+}
+
+#[test]
+fn fires_on_nth_update_of_each_visit() {
+    let machines: Machines = description().build();
+
+    let mut nested = NestedMachine::new_active();
+    let mut visited = Vec::new();
+    for _ in 0..10 {
+        let _ = nested.update(&machines, &mut ()).unwrap();
+        visited.push(nested.current_state_name(&machines).unwrap());
+    }
+    let wait_done = ["Wait", "Wait", "Done", "Done", "Wait"];
+    assert_eq!(visited, [wait_done, wait_done].concat());
+}
+
+#[test]
+fn round_trips_to_builder() {
+    let machines: Machines = description().build();
+
+    let builder = builder::StateMachines::<Noop<()>, (u32, builder::Target)>::try_from(&machines);
+    let states = &builder.unwrap().0[0].states;
+    assert!(matches!(
+        &states[0].transitions[..],
+        [(3, builder::Target::Goto(s))] if s == "Done"
+    ));
+}
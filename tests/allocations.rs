@@ -0,0 +1,129 @@
+//! Changing states every update doesn't allocate once the machine is warm.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use pure_hfsm::prelude::*;
+
+/// Counts the allocations of each thread of this test binary
+struct Counting;
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// How many allocations `f` made on this thread
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// `Always`, but with its own `StateData`
+struct WithData(Target);
+impl Transition for WithData {
+    type Context<'w, 's> = ();
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        self.0.clone()
+    }
+}
+impl IntoTransition<WithData> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> WithData {
+        WithData(mapping.target(&self).expect("valid name"))
+    }
+}
+
+/// `Ping` and `Pong` going to each other, with `count` transitions each
+fn ping_pong<Trs>(count: usize) -> StateMachines<Noop<()>, Trs>
+where
+    builder::Target: IntoTransition<Trs>,
+    Trs: Transition,
+{
+    let state = |name: &str, next: &str| {
+        let transitions = vec![builder::Target::Goto(next.to_owned()); count];
+        builder::State::new(name, Noop::new(), transitions)
+    };
+    let states = vec![state("Ping", "Pong"), state("Pong", "Ping")];
+    builder::StateMachines(vec![builder::StateMachine::new("PingPong", states)]).build()
+}
+
+#[test]
+fn goto_every_update_does_not_allocate() {
+    let machines: StateMachines<_, WithData> = ping_pong(2);
+    let mut nested = NestedMachine::new_active();
+    for _ in 0..10 {
+        let _ = nested.update(&machines, &mut ()).unwrap();
+    }
+
+    let allocations = allocations(|| {
+        for _ in 0..10_000 {
+            let _ = nested.update(&machines, &mut ()).unwrap();
+        }
+    });
+    assert_eq!(allocations, 0);
+}
+
+/// How many allocations the first update of a new `NestedMachine` makes
+fn first_update<Trs>(machines: &StateMachines<Noop<()>, Trs>) -> usize
+where
+    Trs: for<'w, 's> Transition<Context<'w, 's> = ()> + 'static,
+{
+    let mut nested = NestedMachine::new_active();
+    allocations(|| {
+        let _ = nested.update(machines, &mut ()).unwrap();
+    })
+}
+
+#[test]
+fn stateless_transitions_have_no_data_slots() {
+    let stateful: StateMachines<_, WithData> = ping_pong(8);
+    let stateless: StateMachines<_, Always<()>> = ping_pong(8);
+
+    // The `Vec` of the 8 transitions' `StateData`, and the pool keeping it
+    // once `Ping` is left
+    assert_eq!(first_update(&stateful), first_update(&stateless) + 2);
+    // Only the transition counts of the `profiling` feature allocate
+    let stats = usize::from(cfg!(feature = "profiling"));
+    assert_eq!(first_update(&stateless), stats);
+}
+
+#[test]
+fn ten_thousand_shallow_instances_do_not_allocate() {
+    let enter = builder::Target::Enter("Inner".to_owned());
+    let outer = builder::State::new("Enter", Noop::new(), vec![enter]);
+    let inner = builder::State::new("Loop", Noop::new(), vec![]);
+    let machines: StateMachines<Noop<()>, Always<()>> = builder::StateMachines(vec![
+        builder::StateMachine::new("Outer", vec![outer]),
+        builder::StateMachine::new("Inner", vec![inner]),
+    ])
+    .build();
+    let mut instances = Vec::with_capacity(10_000);
+
+    let allocations = allocations(|| {
+        for _ in 0..10_000 {
+            let mut nested = NestedMachine::new_active();
+            for _ in 0..3 {
+                let _ = nested.update(&machines, &mut ()).unwrap();
+            }
+            instances.push(nested);
+        }
+    });
+    // Only the transition counts of the `profiling` feature allocate
+    let stats = if cfg!(feature = "profiling") {
+        10_000
+    } else {
+        0
+    };
+    assert_eq!(allocations, stats);
+    assert!(instances.iter().all(|nested| nested.stack_len() == 2));
+}
@@ -0,0 +1,156 @@
+//! `update_async` drives `AsyncBehavior`s with the same hooks as `update`.
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use pure_hfsm::builder;
+use pure_hfsm::label::{Complete, NestedMachine};
+use pure_hfsm::transition::Always;
+use pure_hfsm::{AsyncBehavior, BoxFuture, StateData, StateMachines};
+
+/// Poll `future` until it completes
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// A future that is pending once, like an actual async operation
+struct YieldOnce(bool);
+impl Future for YieldOnce {
+    type Output = ();
+    fn poll(mut self: std::pin::Pin<&mut Self>, _: &mut Context) -> Poll<()> {
+        match std::mem::replace(&mut self.0, true) {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
+
+type Log = Vec<String>;
+
+/// Logs its hooks, counts its updates in its data
+struct Act {
+    name: &'static str,
+    /// Updates before it can be left
+    busy: u32,
+    transitions_first: bool,
+}
+impl AsyncBehavior for Act {
+    type Context<'w, 's> = Log;
+
+    fn update<'a, 'w, 's>(
+        &'a self,
+        data: &'a mut StateData,
+        _: &'a mut StateData,
+        log: &'a mut Log,
+    ) -> BoxFuture<'a> {
+        Box::pin(async move {
+            YieldOnce(false).await;
+            *data.downcast_mut::<u32>().unwrap() += 1;
+            log.push(format!("{} update", self.name));
+        })
+    }
+    fn default_data(&self) -> StateData {
+        Box::new(0_u32)
+    }
+    fn on_enter(&self, _: &mut StateData, _: &mut StateData, log: &mut Log) {
+        log.push(format!("{} enter", self.name));
+    }
+    fn transitions_first(&self) -> bool {
+        self.transitions_first
+    }
+    fn can_exit(&self, data: &StateData, _: &Log) -> bool {
+        *data.downcast_ref::<u32>().unwrap() >= self.busy
+    }
+    fn result(&self, _: &mut StateData, _: &mut StateData) -> Option<StateData> {
+        Some(Box::new(self.name))
+    }
+    fn on_result(&self, _: &mut StateData, _: &mut StateData, result: StateData, log: &mut Log) {
+        let from = result.downcast_ref::<&str>().unwrap();
+        log.push(format!("{} got result of {from}", self.name));
+    }
+}
+
+fn act(name: &'static str, busy: u32) -> Act {
+    Act {
+        name,
+        busy,
+        transitions_first: false,
+    }
+}
+
+type Machines = StateMachines<Act, Always<Log>>;
+
+#[test]
+fn hooks_and_result() {
+    let enter_once = builder::Target::EnterOnce("Child".to_owned());
+    let wait = builder::State::new("Wait", act("Wait", 0), vec![enter_once]);
+    let work = builder::State::new("Work", act("Work", 2), vec![builder::Target::End]);
+    let machines: Machines = builder::StateMachines(vec![
+        builder::StateMachine::new("Parent", vec![wait]),
+        builder::StateMachine::new("Child", vec![work]),
+    ])
+    .build();
+
+    let mut nested = NestedMachine::new_active();
+    let mut log = Vec::new();
+    for _ in 0..4 {
+        let complete = block_on(nested.update_async(&machines, &mut log)).unwrap();
+        assert_eq!(complete, Complete::Running);
+    }
+    let expected = [
+        "Wait enter",
+        "Wait update",
+        "Work enter",
+        "Work update",
+        // `Work` can't exit before its second update
+        "Work update",
+        "Wait got result of Work",
+        "Wait update",
+    ];
+    assert_eq!(log, expected);
+}
+
+#[test]
+fn transitions_first_and_timeout() {
+    let first = Act {
+        transitions_first: true,
+        ..act("Leave", 0)
+    };
+    let leave = builder::State::new(
+        "Leave",
+        first,
+        vec![builder::Target::Goto("Search".to_owned())],
+    );
+    let search = builder::State {
+        timeout: Some((Duration::from_secs(1), builder::Target::End)),
+        ..builder::State::new("Search", act("Search", 0), vec![])
+    };
+    let machines: Machines = builder::StateMachines(vec![builder::StateMachine::new(
+        "Machine",
+        vec![leave, search],
+    )])
+    .build();
+
+    let mut nested = NestedMachine::new_active();
+    let mut log = Vec::new();
+    let half = Duration::from_millis(500);
+    let mut update = |log: &mut Log| block_on(nested.update_async_timed(&machines, log, half));
+    assert_eq!(update(&mut log).unwrap(), Complete::Running);
+    assert_eq!(update(&mut log).unwrap(), Complete::Running);
+    assert_eq!(update(&mut log).unwrap(), Complete::Done);
+    // `Leave` fired its transition before updating
+    let expected = [
+        "Leave enter",
+        "Search enter",
+        "Search update",
+        "Search update",
+    ];
+    assert_eq!(log, expected);
+}
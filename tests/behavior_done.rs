@@ -0,0 +1,56 @@
+//! A transition firing once its state's behavior reports it is done.
+use pure_hfsm::behavior::{Completable, CompletableBehavior};
+use pure_hfsm::prelude::*;
+use pure_hfsm::transition::BehaviorDone;
+
+/// Done after `.0` updates
+struct Wait(u32);
+impl Behavior for Wait {
+    type Context<'w, 's> = ();
+
+    fn update<'w, 's>(&self, data: &mut StateData, _: &mut StateData, _: &mut ()) {
+        *data.downcast_mut::<u32>().unwrap() += 1;
+    }
+    fn default_data(&self) -> StateData {
+        Box::new(0_u32)
+    }
+}
+impl CompletableBehavior for Wait {
+    fn is_done(&self, data: &StateData) -> bool {
+        *data.downcast_ref::<u32>().unwrap() >= self.0
+    }
+}
+
+#[test]
+fn fires_on_the_tick_the_behavior_is_done() {
+    let state = |name: &str, ticks, target| {
+        builder::State::new(name, Completable(Wait(ticks)), vec![target])
+    };
+    let machines = builder::StateMachines(vec![builder::StateMachine::new(
+        "Wait",
+        vec![
+            state("Waiting", 3, builder::Target::Goto("Done".to_owned())),
+            state("Done", 0, builder::Target::End),
+        ],
+    )]);
+    let machines: StateMachines<_, BehaviorDone<()>> = machines.build();
+
+    let mut nested = NestedMachine::new_active();
+    for _ in 0..2 {
+        let _ = nested.update(&machines, &mut ()).unwrap();
+        assert_eq!(nested.current_state_name(&machines), Some("Waiting"));
+    }
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Done"));
+}
+
+#[test]
+fn never_fires_without_completable() {
+    let done = BehaviorDone::<()>::new(Target::Complete);
+    let mut data: StateData = Box::new(3_u32);
+    let mut machine_data: StateData = Box::new(());
+    assert_eq!(
+        done.decide(&mut data, &mut machine_data, &()),
+        Target::Continue
+    );
+}
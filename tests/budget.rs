@@ -0,0 +1,90 @@
+//! `update_budgeted` spreads the regions of a tick over several updates.
+use pure_hfsm::builder;
+use pure_hfsm::label::{Complete, NestedMachine};
+use pure_hfsm::transition::Always;
+use pure_hfsm::{Behavior, StateData, StateMachines};
+
+type Log = Vec<&'static str>;
+
+/// Logs its name when updated
+struct Named(&'static str);
+impl Behavior for Named {
+    type Context<'w, 's> = Log;
+
+    fn update(&self, _: &mut StateData, _: &mut StateData, log: &mut Log) {
+        log.push(self.0);
+    }
+}
+
+type Machines = StateMachines<Named, Always<Log>>;
+
+/// A machine with the states `A` and `B` in two regions, each costing one
+/// evaluation
+fn machines() -> Machines {
+    let state = |name, region| builder::State::<_, builder::Target> {
+        region,
+        ..builder::State::new(name, Named(name), vec![])
+    };
+    let states = vec![state("A", 0), state("B", 1)];
+    builder::StateMachines(vec![builder::StateMachine::new("Machine", states)]).build()
+}
+
+/// `update_budgeted` with `budget`, or `update` when `None`, returns what
+/// was logged during the update
+fn tick(nested: &mut NestedMachine, machines: &Machines, budget: Option<usize>) -> (Complete, Log) {
+    let mut log = Vec::new();
+    let complete = match budget {
+        Some(budget) => nested.update_budgeted(machines, &mut log, budget),
+        None => nested.update(machines, &mut log),
+    };
+    (complete.unwrap(), log)
+}
+
+#[test]
+fn update_resumes_throttled_tick() {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+
+    assert_eq!(
+        tick(&mut nested, &machines, Some(1)),
+        (Complete::Throttled, vec!["A"])
+    );
+    // `A` was already updated this tick
+    assert_eq!(
+        tick(&mut nested, &machines, None),
+        (Complete::Running, vec!["B"])
+    );
+    assert_eq!(
+        tick(&mut nested, &machines, None),
+        (Complete::Running, vec!["A", "B"])
+    );
+}
+
+#[test]
+fn enough_budget_updates_all_regions() {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+
+    let all = (Complete::Running, vec!["A", "B"]);
+    assert_eq!(tick(&mut nested, &machines, Some(2)), all);
+    assert_eq!(tick(&mut nested, &machines, Some(2)), all);
+}
+
+#[test]
+fn zero_budget_still_makes_progress() {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+
+    assert_eq!(
+        tick(&mut nested, &machines, Some(0)),
+        (Complete::Throttled, vec!["A"])
+    );
+    assert_eq!(
+        tick(&mut nested, &machines, Some(0)),
+        (Complete::Running, vec!["B"])
+    );
+    assert_eq!(
+        tick(&mut nested, &machines, Some(0)),
+        (Complete::Throttled, vec!["A"])
+    );
+}
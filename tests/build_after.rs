@@ -0,0 +1,53 @@
+//! Machines split across several descriptions, built one after the other
+//! and merged.
+use pure_hfsm::prelude::*;
+use pure_hfsm::Error;
+
+fn file(machines: &[(&str, builder::Target)]) -> builder::StateMachines<Noop<()>, builder::Target> {
+    let machine = |(name, target): &(&str, builder::Target)| {
+        builder::StateMachine::new(
+            *name,
+            vec![builder::State::new(
+                "only",
+                Noop::new(),
+                vec![target.clone()],
+            )],
+        )
+    };
+    machines.iter().map(machine).collect()
+}
+
+#[test]
+fn later_files_enter_earlier_machines() {
+    let enter = |name: &str| builder::Target::Enter(name.to_owned());
+    let shared = file(&[("Wait", builder::Target::End)]);
+    let main = file(&[("Main", enter("Wait")), ("Other", enter("Main"))]);
+
+    let (mut machines, mapping) = shared.build_with_mapping::<Always<()>>();
+    let (main, mapping) = main.build_after(mapping);
+    machines.merge(main).unwrap();
+
+    let names: Vec<_> = machines.machines().map(|(_, name)| name).collect();
+    assert_eq!(names, ["Wait", "Main", "Other"]);
+    let main = machines.machine_handle("Main").unwrap();
+    assert_eq!(mapping.enter("Main"), Some(Target::Enter(main.clone())));
+
+    let mut nested = NestedMachine::new();
+    nested.enter(&main);
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.current_machine_name(&machines), Some("Wait"));
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.current_machine_name(&machines), Some("Main"));
+}
+
+#[test]
+fn merging_out_of_order_errors() {
+    let (mut machines, mapping) = file(&[("A", builder::Target::End)]).build_with_mapping();
+    let (b, mapping) = file(&[("B", builder::Target::End)]).build_after::<Always<()>>(mapping);
+    let (c, _) = file(&[("C", builder::Target::End)]).build_after::<Always<()>>(mapping);
+
+    let result = machines.merge(c);
+    assert!(matches!(result, Err(Error::BadMergeOrder { .. })));
+    machines.merge(b).unwrap();
+    assert_eq!(machines.machines().count(), 2);
+}
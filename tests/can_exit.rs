@@ -0,0 +1,89 @@
+//! Behaviors veto transitions during uninterruptible phases.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::{Behavior, StateData, Target, Transition};
+
+/// How many times a transition fired
+type Fired = u32;
+
+enum Act {
+    /// Can't be interrupted before its third update
+    Attack,
+    Idle,
+}
+impl Behavior for Act {
+    type Context<'w, 's> = Fired;
+
+    fn update<'w, 's>(&self, data: &mut StateData, _: &mut StateData, _: &mut Fired) {
+        if let Some(ticks) = data.downcast_mut::<u32>() {
+            *ticks += 1;
+        }
+    }
+    fn default_data(&self) -> StateData {
+        Box::new(0_u32)
+    }
+    fn can_exit<'w, 's>(&self, data: &StateData, _: &Fired) -> bool {
+        match self {
+            Act::Attack => data.downcast_ref::<u32>() >= Some(&3),
+            Act::Idle => true,
+        }
+    }
+}
+
+struct Always(Target);
+impl Transition for Always {
+    type Context<'w, 's> = Fired;
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &Fired) -> Target {
+        self.0.clone()
+    }
+    fn on_fire<'w, 's>(&self, _: &mut StateData, _: &mut StateData, fired: &mut Fired) {
+        *fired += 1;
+    }
+}
+impl IntoTransition<Always> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> Always {
+        Always(mapping.target(&self).expect("valid name"))
+    }
+}
+
+fn machine(first: Act) -> builder::StateMachines<Act, builder::Target> {
+    let state =
+        |name: &str, behavior, transitions| builder::State::new(name, behavior, transitions);
+    let goto_done = vec![builder::Target::Goto("Done".to_owned())];
+    builder::StateMachines(vec![builder::StateMachine::new(
+        "Fighter",
+        vec![
+            state("First", first, goto_done),
+            state("Done", Act::Idle, vec![]),
+        ],
+    )])
+}
+
+/// The state after each of 4 updates, and how many times a transition fired
+fn run(first: Act) -> (Vec<String>, Fired) {
+    let machines = machine(first).build::<Always>();
+    let mut nested = NestedMachine::new_active();
+    let mut fired = 0;
+    let states = (0..4)
+        .map(|_| {
+            let _ = nested.update(&machines, &mut fired).unwrap();
+            nested.current_state_name(&machines).unwrap().to_owned()
+        })
+        .collect();
+    (states, fired)
+}
+
+#[test]
+fn behavior_vetoes_exit() {
+    let (states, fired) = run(Act::Attack);
+    assert_eq!(states, ["First", "First", "Done", "Done"]);
+    assert_eq!(fired, 1);
+}
+
+#[test]
+fn behavior_allows_exit() {
+    let (states, fired) = run(Act::Idle);
+    assert_eq!(states, ["Done", "Done", "Done", "Done"]);
+    assert_eq!(fired, 1);
+}
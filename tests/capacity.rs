@@ -0,0 +1,80 @@
+//! Building reserves exactly the memory the state machines need.
+use std::mem::size_of;
+
+use pure_hfsm::prelude::*;
+use pure_hfsm::{CapacityReport, INLINE_MACHINES, INLINE_STATES};
+
+type Builder = builder::StateMachines<Noop<()>, builder::Target>;
+
+/// `machines` machines of `states` states, each going to the next state
+fn machines(machines: usize, states: usize, prefix: &str) -> Builder {
+    let state = |si: usize| {
+        builder::State::new(
+            format!("s{si}"),
+            Noop::new(),
+            vec![builder::Target::Goto(format!("s{}", (si + 1) % states))],
+        )
+    };
+    let machine = |mi: usize| {
+        builder::StateMachine::new(format!("{prefix}{mi}"), (0..states).map(state).collect())
+    };
+    builder::StateMachines((0..machines).map(machine).collect())
+}
+
+#[test]
+fn inline_collections_do_not_allocate() {
+    let description = machines(INLINE_MACHINES, INLINE_STATES, "m");
+    let mut names = 0;
+    for machine in &description.0 {
+        names += machine.name.len() + machine.states.iter().map(|s| s.name.len()).sum::<usize>();
+    }
+    let built: StateMachines<Noop<()>, Always<()>> = description.build();
+
+    let transitions = INLINE_MACHINES * INLINE_STATES * size_of::<Always<()>>();
+    let state_names =
+        INLINE_MACHINES * (size_of::<Vec<String>>() + INLINE_STATES * size_of::<String>());
+    let expected = INLINE_MACHINES * size_of::<String>() + state_names + transitions + names;
+    let report = built.capacity_report();
+    assert_eq!(
+        report,
+        CapacityReport {
+            used: expected,
+            allocated: expected
+        }
+    );
+}
+
+#[test]
+fn spilled_collections_are_reserved_exactly() {
+    let description = machines(3 * INLINE_MACHINES, 3 * INLINE_STATES, "m");
+    let built: StateMachines<Noop<()>, Always<()>> = description.build();
+
+    let report = built.capacity_report();
+    assert!(report.used > 0);
+    assert_eq!(report.allocated, report.used);
+}
+
+#[test]
+fn merged_collections_are_reserved_exactly() {
+    let (mut built, mapping) = machines(INLINE_MACHINES, 1, "a").build_with_mapping();
+    let (other, _) = machines(INLINE_MACHINES + 3, 1, "b").build_after(mapping);
+    let other: StateMachines<Noop<()>, Always<()>> = other;
+    built.merge(other).unwrap();
+
+    let report = built.capacity_report();
+    assert_eq!(report.allocated, report.used);
+}
+
+#[test]
+fn shrink_to_fit_releases_excess_capacity() {
+    let description = machines(3 * INLINE_MACHINES, 3 * INLINE_STATES, "m");
+    let built: StateMachines<Noop<()>, Always<()>> = description.build();
+    let mut interrupts = Vec::with_capacity(16);
+    interrupts.push(Always::new(Target::Complete));
+    let mut built = built.with_interrupts(interrupts);
+    assert!(built.capacity_report().allocated > built.capacity_report().used);
+
+    built.shrink_to_fit();
+    let report = built.capacity_report();
+    assert_eq!(report.allocated, report.used);
+}
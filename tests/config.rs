@@ -0,0 +1,49 @@
+//! The limits set with `NestedMachine::with_config`.
+use pure_hfsm::label::NestedMachineConfig;
+use pure_hfsm::prelude::*;
+use pure_hfsm::Error;
+
+/// A machine that enters itself
+fn recursive() -> StateMachines<Noop<()>, Always<()>> {
+    let machine = builder::StateMachine::new(
+        "Recurse",
+        vec![builder::State::new(
+            "deeper",
+            Noop::new(),
+            vec![builder::Target::Enter("Recurse".to_owned())],
+        )],
+    );
+    builder::StateMachines(vec![machine]).build()
+}
+
+#[test]
+fn default_config_has_no_limits() {
+    let nested = NestedMachine::new_active();
+    assert_eq!(nested.config(), &NestedMachineConfig::default());
+    assert_eq!(nested.max_depth(), usize::MAX);
+    assert!(!nested.depth_limit_reached());
+}
+
+#[test]
+fn entering_past_max_depth_errors() {
+    let machines = recursive();
+    let config = NestedMachineConfig {
+        max_depth: 3,
+        ..Default::default()
+    };
+
+    let mut nested = NestedMachine::new_active().with_config(config);
+    assert_eq!(nested.remaining_depth(), 2);
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert!(nested.depth_limit_reached());
+    let result = nested.update(&machines, &mut ());
+    assert!(matches!(result, Err(Error::DepthLimit { depth: 3, .. })));
+    assert_eq!(nested.stack_len(), 3);
+}
+
+#[test]
+fn stuck_threshold_is_part_of_config() {
+    let nested = NestedMachine::new().with_stuck_threshold(5);
+    assert_eq!(nested.config().stuck_threshold, Some(5));
+}
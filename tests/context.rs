@@ -0,0 +1,103 @@
+//! `Behavior::Context` works with contexts that borrow for two different
+//! lifetimes on each update, like bevy's `SystemParam`s.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::{Complete, NestedMachine};
+use pure_hfsm::{Behavior, StateData, Target, Transition};
+
+struct World {
+    enemy_distance: u32,
+}
+#[derive(Debug, PartialEq)]
+enum Command {
+    Wander,
+    Attack,
+}
+
+/// A bevy-style context, with a part to read and a part to write, borrowed
+/// for different lifetimes
+struct Context<'w, 's> {
+    world: &'w World,
+    commands: &'s mut Vec<Command>,
+}
+
+enum Act {
+    Wander,
+    Attack,
+}
+impl Behavior for Act {
+    type Context<'w, 's> = Context<'w, 's>;
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, ctx: &mut Context<'w, 's>) {
+        let command = match self {
+            Act::Wander => Command::Wander,
+            Act::Attack => Command::Attack,
+        };
+        ctx.commands.push(command);
+    }
+}
+
+struct EnemyCloserThan(u32, Target);
+impl Transition for EnemyCloserThan {
+    type Context<'w, 's> = Context<'w, 's>;
+
+    fn decide<'w, 's>(
+        &self,
+        _: &mut StateData,
+        _: &mut StateData,
+        ctx: &Context<'w, 's>,
+    ) -> Target {
+        if ctx.world.enemy_distance < self.0 {
+            self.1.clone()
+        } else {
+            Target::Continue
+        }
+    }
+}
+struct SerialCloser(u32, builder::Target);
+impl IntoTransition<EnemyCloserThan> for SerialCloser {
+    fn into_with(self, mapping: &NameMapping) -> EnemyCloserThan {
+        EnemyCloserThan(self.0, mapping.target(&self.1).expect("valid name"))
+    }
+}
+
+#[test]
+fn borrowing_context() {
+    let state =
+        |name: &str, behavior, transitions| builder::State::new(name, behavior, transitions);
+    let description = builder::StateMachines(vec![builder::StateMachine::new(
+        "Guard",
+        vec![
+            state(
+                "wander",
+                Act::Wander,
+                vec![SerialCloser(5, builder::Target::Goto("attack".to_owned()))],
+            ),
+            state(
+                "attack",
+                Act::Attack,
+                vec![SerialCloser(1, builder::Target::End)],
+            ),
+        ],
+    )]);
+    let machines = description.build::<EnemyCloserThan>();
+
+    let mut nested = NestedMachine::new_active();
+    let mut commands = Vec::new();
+    let mut last = Complete::Running;
+    for enemy_distance in [9, 3, 2, 0] {
+        let world = World { enemy_distance };
+        let mut ctx = Context {
+            world: &world,
+            commands: &mut commands,
+        };
+        last = nested.update(&machines, &mut ctx).unwrap();
+    }
+    assert_eq!(last, Complete::Done);
+    let expected = [
+        Command::Wander,
+        Command::Wander,
+        Command::Attack,
+        Command::Attack,
+    ];
+    assert_eq!(commands, expected);
+}
@@ -0,0 +1,172 @@
+//! Deserializing with the `de` visitors.
+use pure_hfsm::builder::{State, StateMachine, StateMachines};
+use pure_hfsm::de::{StateMachineVisitor, StateVisitor};
+use serde::de::{DeserializeSeed, SeqAccess, Visitor};
+use serde_json::{json, Value};
+
+/// A sequence that claims to be `usize::MAX` elements long
+struct Lying(std::vec::IntoIter<Value>);
+impl<'de> SeqAccess<'de> for Lying {
+    type Error = serde_json::Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, Self::Error> {
+        self.0
+            .next()
+            .map(|value| seed.deserialize(value))
+            .transpose()
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(usize::MAX)
+    }
+}
+fn lying(values: Vec<Value>) -> Lying {
+    Lying(values.into_iter())
+}
+
+#[test]
+fn huge_size_hint_does_not_preallocate() {
+    let items = vec![json!("Aim"), json!(1), json!(2), json!(3)];
+    let state: State<u32, u32> = StateVisitor::new().visit_seq(lying(items)).unwrap();
+    assert_eq!(state.transitions, [2, 3]);
+    assert!(state.transitions.capacity() <= 1024);
+
+    let items = vec![json!("Guard"), json!(["Aim", 1]), json!(["Fire", 2, 3])];
+    let machine: StateMachine<u32, u32> =
+        StateMachineVisitor::new().visit_seq(lying(items)).unwrap();
+    assert_eq!(machine.states.len(), 2);
+    assert!(machine.states.capacity() <= 1024);
+}
+
+type StateSummary<'a> = (&'a str, u32, &'a [u32], u8);
+
+/// The names, behaviors, transitions and regions of `machines`
+fn summary(machines: &StateMachines<u32, u32>) -> Vec<(&str, Vec<StateSummary<'_>>)> {
+    fn state(s: &State<u32, u32>) -> StateSummary<'_> {
+        (&s.name, s.behavior, &s.transitions, s.region)
+    }
+    fn machine(m: &StateMachine<u32, u32>) -> (&str, Vec<StateSummary<'_>>) {
+        (&m.name, m.states.iter().map(state).collect())
+    }
+    machines.iter().map(machine).collect()
+}
+
+#[derive(serde::Deserialize)]
+struct Asset {
+    #[serde(deserialize_with = "pure_hfsm::de::machines_or_map")]
+    machines: StateMachines<u32, u32>,
+}
+
+#[test]
+fn map_and_sequence_forms_are_equivalent() {
+    let sequence = r#"{ "machines": [
+        ["Patrol", ["Walk", 1, 10], [{ "name": "Look", "region": 1 }, 2]],
+        ["Combat", ["Fight", 3, 30, 31]]
+    ] }"#;
+    let map = r#"{ "machines": {
+        "Patrol": [["Walk", 1, 10], [{ "name": "Look", "region": 1 }, 2]],
+        "Combat": [["Fight", 3, 30, 31]]
+    } }"#;
+    let from_sequence: Asset = serde_json::from_str(sequence).unwrap();
+    let from_map: Asset = serde_json::from_str(map).unwrap();
+    assert_eq!(
+        summary(&from_map.machines),
+        summary(&from_sequence.machines)
+    );
+
+    // The usual `Deserialize` impl still reads the sequence form
+    let machines: StateMachines<u32, u32> =
+        serde_json::from_str(r#"[["Combat", ["Fight", 3, 30, 31]]]"#).unwrap();
+    assert_eq!(summary(&machines)[0], summary(&from_map.machines)[1]);
+}
+
+#[test]
+fn state_timeout_in_header() {
+    let json = r#"[{ "name": "Search", "timeout": [2.5, { "goto": "Patrol" }] }, 1]"#;
+    let state: State<u32, u32> = serde_json::from_str(json).unwrap();
+    let (duration, target) = state.timeout.unwrap();
+    assert_eq!(duration, std::time::Duration::from_millis(2500));
+    assert!(matches!(target, pure_hfsm::builder::Target::Goto(name) if name == "Patrol"));
+
+    let negative = r#"[{ "name": "Search", "timeout": [-1.0, "End"] }, 1]"#;
+    assert!(serde_json::from_str::<State<u32, u32>>(negative).is_err());
+}
+
+#[test]
+fn state_default_in_header() {
+    let json = r#"[{ "name": "Idle", "default": 9 }, 1, 10, 11]"#;
+    let state: State<u32, u32> = serde_json::from_str(json).unwrap();
+    assert_eq!(state.transitions, [10, 11]);
+    assert_eq!(state.default, Some(9));
+
+    let state: State<u32, u32> = serde_json::from_str(r#"["Idle", 1, 10]"#).unwrap();
+    assert_eq!(state.default, None);
+}
+
+#[test]
+fn machine_on_complete_in_header() {
+    let json = r#"[{ "name": "Guard", "on_complete": 7 }, ["Aim", 1]]"#;
+    let machine: StateMachine<u32, u32> = serde_json::from_str(json).unwrap();
+    assert_eq!(machine.on_complete, Some(7));
+
+    let machine: StateMachine<u32, u32> = serde_json::from_str(r#"["Guard"]"#).unwrap();
+    assert_eq!(machine.on_complete, None);
+}
+
+/// The `NameMapping` of a `Main` machine with `Idle` and `Run` states, and
+/// an `Other` machine
+fn mapping() -> pure_hfsm::builder::NameMapping {
+    use pure_hfsm::behavior::Noop;
+    use pure_hfsm::builder::Target;
+    use pure_hfsm::transition::Always;
+
+    let state = |name: &str| State::new(name, Noop::<()>::new(), Vec::<Target>::new());
+    let description = StateMachines(vec![
+        StateMachine::new("Main", vec![state("Idle"), state("Run")]),
+        StateMachine::new("Other", vec![state("Start")]),
+    ]);
+    description.build_with_mapping::<Always<()>>().1
+}
+
+#[test]
+fn target_json_shapes() {
+    use pure_hfsm::builder::Target;
+
+    let mapping = mapping();
+    let target = |json: Value| {
+        let target: Target = serde_json::from_value(json).unwrap();
+        mapping.target(&target).unwrap()
+    };
+    let idle = mapping.goto("Idle").unwrap();
+    assert_eq!(target(json!({ "goto": "Idle" })), idle);
+    assert_eq!(target(json!({ "Goto": "Idle" })), idle);
+    assert_eq!(
+        target(json!({ "enter": "Other" })),
+        mapping.enter("Other").unwrap()
+    );
+    let enter_at = mapping.enter_at("Other", "Start").unwrap();
+    assert_eq!(target(json!({ "enter_at": ["Other", "Start"] })), enter_at);
+    assert_eq!(target(json!({ "pop_n": 2 })), pure_hfsm::Target::PopN(2));
+    for end in ["End", "end", "Complete", "complete"] {
+        assert_eq!(target(json!(end)), pure_hfsm::Target::Complete);
+    }
+}
+
+#[test]
+fn target_ron_shapes() {
+    use pure_hfsm::builder::Target;
+
+    let mapping = mapping();
+    let target = |text: &str| {
+        let target: Target = ron::from_str(text).unwrap();
+        mapping.target(&target).unwrap()
+    };
+    assert_eq!(target(r#"Goto("Idle")"#), mapping.goto("Idle").unwrap());
+    assert_eq!(target(r#"goto("Run")"#), mapping.goto("Run").unwrap());
+    let enter_once = mapping.enter_once("Other").unwrap();
+    assert_eq!(target(r#"EnterOnce("Other")"#), enter_once);
+    assert_eq!(target("End"), pure_hfsm::Target::Complete);
+    assert_eq!(target("complete"), pure_hfsm::Target::Complete);
+}
@@ -0,0 +1,37 @@
+//! The `Debug` output of `NestedMachine` is stable for snapshot tests.
+use pure_hfsm::prelude::*;
+
+fn machines() -> StateMachines<Noop<()>, Always<()>> {
+    let state = |name: &str, target| builder::State::new(name, Noop::new(), vec![target]);
+    let machine = |name: &str, states| builder::StateMachine::new(name, states);
+    builder::StateMachines(vec![
+        machine(
+            "Guard",
+            vec![
+                state("patrol", builder::Target::Goto("alert".to_owned())),
+                state("alert", builder::Target::Enter("Look".to_owned())),
+            ],
+        ),
+        machine("Look", vec![state("around", builder::Target::End)]),
+    ])
+    .build()
+}
+
+#[test]
+fn debug_prints_handles_without_data() {
+    let machines = machines();
+
+    let mut nested = NestedMachine::new_active();
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(
+        format!("{nested:?}"),
+        "NestedMachine { stack: [(SmHandle(0), SHandle(1)), (SmHandle(1), SHandle(0))], \
+         completed: false, pending_events: 0 }"
+    );
+    assert_eq!(
+        format!("{:?}", nested.debug_with(&machines)),
+        "NestedMachine { stack: [(\"Guard\", \"alert\"), (\"Look\", \"around\")], \
+         completed: false, pending_events: 0 }"
+    );
+}
@@ -0,0 +1,70 @@
+//! The default transition of a state, evaluated after all its others.
+use pure_hfsm::prelude::*;
+
+/// Fires `target` when the context is `when`, or always if `when` is `None`
+struct When(Option<u32>, Target);
+impl Transition for When {
+    type Context<'w, 's> = u32;
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, ctx: &u32) -> Target {
+        match self.0 {
+            Some(when) if when != *ctx => Target::Continue,
+            _ => self.1.clone(),
+        }
+    }
+}
+impl IntoTransition<When> for (Option<u32>, &str) {
+    fn into_with(self, mapping: &NameMapping) -> When {
+        let target = builder::Target::Goto(self.1.to_owned());
+        When(self.0, mapping.target(&target).expect("valid name"))
+    }
+}
+
+/// `Start` goes to `One` or `Two` when the context says so, `Else` otherwise
+fn machines() -> StateMachines<Noop<u32>, When> {
+    let state = |name: &str, transitions, default| builder::State {
+        default,
+        ..builder::State::new(name, Noop::new(), transitions)
+    };
+    let start = state(
+        "Start",
+        vec![(Some(1), "One"), (Some(2), "Two")],
+        Some((None, "Else")),
+    );
+    let machines = builder::StateMachines(vec![builder::StateMachine::new(
+        "Machine",
+        vec![
+            start,
+            state("One", vec![], None),
+            state("Two", vec![], None),
+            state("Else", vec![], None),
+        ],
+    )]);
+    machines.build()
+}
+
+#[test]
+fn default_fires_only_when_others_continue() {
+    let machines = machines();
+
+    for (mut ctx, expected) in [(1, "One"), (2, "Two"), (0, "Else")] {
+        let mut nested = NestedMachine::new_active();
+        let _ = nested.update(&machines, &mut ctx).unwrap();
+        assert_eq!(nested.current_state_name(&machines), Some(expected));
+    }
+}
+
+#[test]
+fn default_is_kept_apart() {
+    let machines = machines();
+
+    let machine = machines.machine_ref("Machine").unwrap();
+    let start = machines.state_id("Machine", "Start").unwrap().state;
+    assert_eq!(machine.transitions(&start).unwrap().len(), 2);
+    assert!(machine.default_transition(&start).is_some());
+
+    let mut nested = NestedMachine::new_active();
+    nested.set_transition_enabled(&machine.handle(), &start, 2, false);
+    let _ = nested.update(&machines, &mut 0).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Start"));
+}
@@ -0,0 +1,50 @@
+//! `StateMachines::describe` summarizes the machines for snapshot tests.
+use pure_hfsm::prelude::*;
+
+type Machines = StateMachines<Noop<()>, Always<()>>;
+
+/// The machines of the `describe` documentation
+fn machines() -> Machines {
+    let goto = |state: &str| builder::Target::Goto(state.to_owned());
+    let state = |name: &str, targets: &[&str]| {
+        builder::State::new(name, Noop::new(), targets.iter().map(|t| goto(t)).collect())
+    };
+    let guard = vec![
+        state("wander", &["attack", "wander"]),
+        state("attack", &["wander"]),
+        builder::State {
+            region: 1,
+            ..state("look_around", &["look_around"])
+        },
+    ];
+    let flee = vec![state("run", &["hide"]), state("hide", &[])];
+    builder::StateMachines(vec![
+        builder::StateMachine::new("Guard", guard),
+        builder::StateMachine::new("Flee", flee),
+    ])
+    .build()
+}
+
+#[test]
+fn snapshot() {
+    let expected = "\
+2 machines, 5 states
+Guard
+  wander: 2 transitions
+  attack: 1 transition
+  look_around: 1 transition (region 1)
+Flee
+  run: 1 transition
+  hide: 0 transitions
+";
+    assert_eq!(machines().describe(), expected);
+}
+
+#[test]
+fn snapshot_with_interrupts() {
+    let flee = machines().machine_handle("Flee").unwrap();
+    let machines = machines().with_interrupts(vec![Always::new(Target::Enter(flee))]);
+
+    let description = machines.describe();
+    assert!(description.ends_with("  hide: 0 transitions\n1 interrupt\n"));
+}
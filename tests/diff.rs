@@ -0,0 +1,105 @@
+//! `StateMachines::diff` lists what changed between two builds.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::diff::Change;
+use pure_hfsm::{Behavior, StateData, StateMachines, Target, Transition};
+
+/// Moves at the given speed
+#[derive(PartialEq)]
+struct Walk(u32);
+impl Behavior for Walk {
+    type Context<'w, 's> = ();
+
+    fn update(&self, _: &mut StateData, _: &mut StateData, _: &mut ()) {}
+}
+
+/// Always goes to its target
+#[derive(PartialEq)]
+struct To(Target);
+impl Transition for To {
+    type Context<'w, 's> = ();
+
+    fn decide(&self, _: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        self.0.clone()
+    }
+}
+impl IntoTransition<To> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> To {
+        To(mapping.target(&self).expect("valid name"))
+    }
+}
+
+type Machines = StateMachines<Walk, To>;
+/// A state's `(name, speed, goto)`
+type StateDesc<'a> = (&'a str, u32, Option<&'a str>);
+
+/// One machine per `(name, states)`
+fn build(machines: &[(&str, &[StateDesc])]) -> Machines {
+    let state = |&(name, speed, goto): &StateDesc| {
+        let goto = goto.map(|state| builder::Target::Goto(state.to_owned()));
+        builder::State::new(name, Walk(speed), goto.into_iter().collect())
+    };
+    let machine = |(name, states): &(&str, &[_])| {
+        builder::StateMachine::new(*name, states.iter().map(state).collect())
+    };
+    builder::StateMachines(machines.iter().map(machine).collect()).build()
+}
+
+fn added(machine: &str, state: &str) -> Change {
+    let (machine, state) = (machine.to_owned(), state.to_owned());
+    Change::AddedState { machine, state }
+}
+fn removed(machine: &str, state: &str) -> Change {
+    let (machine, state) = (machine.to_owned(), state.to_owned());
+    Change::RemovedState { machine, state }
+}
+fn changed(machine: &str, state: &str) -> Change {
+    let (machine, state) = (machine.to_owned(), state.to_owned());
+    Change::ChangedState { machine, state }
+}
+
+#[test]
+fn same_machines_have_no_changes() {
+    let machines = build(&[("M", &[("A", 1, Some("B")), ("B", 2, None)])]);
+    assert!(machines.diff(&machines).is_empty());
+}
+
+#[test]
+fn added_and_removed_machines() {
+    let old = build(&[("Old", &[("A", 1, None)]), ("Kept", &[("A", 1, None)])]);
+    let new = build(&[("Kept", &[("A", 1, None)]), ("New", &[("A", 1, None)])]);
+
+    let expected = [
+        Change::RemovedMachine {
+            machine: "Old".to_owned(),
+        },
+        Change::AddedMachine {
+            machine: "New".to_owned(),
+        },
+    ];
+    assert_eq!(old.diff(&new).changes(), expected);
+}
+
+#[test]
+fn added_removed_and_changed_states() {
+    let old = build(&[("M", &[("A", 1, None), ("B", 1, None), ("C", 1, None)])]);
+    let new = build(&[("M", &[("A", 1, None), ("C", 2, None), ("D", 1, None)])]);
+
+    let expected = [removed("M", "B"), changed("M", "C"), added("M", "D")];
+    assert_eq!(old.diff(&new).changes(), expected);
+}
+
+#[test]
+fn renumbered_state_is_unchanged() {
+    let old = build(&[("M", &[("A", 1, None), ("B", 2, None)])]);
+    let new = build(&[("M", &[("B", 2, None), ("A", 1, None)])]);
+
+    assert!(old.diff(&new).is_empty());
+}
+
+#[test]
+fn transition_to_renumbered_state_is_changed() {
+    let old = build(&[("M", &[("A", 1, Some("B")), ("B", 2, None), ("C", 3, None)])]);
+    let new = build(&[("M", &[("A", 1, Some("B")), ("C", 3, None), ("B", 2, None)])]);
+
+    assert_eq!(old.diff(&new).changes(), [changed("M", "A")]);
+}
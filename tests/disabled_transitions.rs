@@ -0,0 +1,55 @@
+//! Transitions disabled at runtime on a single `NestedMachine`.
+use pure_hfsm::prelude::*;
+
+/// `Idle` flees if its first transition is enabled, otherwise fights
+fn machine() -> builder::StateMachines<Noop<()>, builder::Target> {
+    let state = |name: &str, transitions| builder::State::new(name, Noop::new(), transitions);
+    let goto = |name: &str| builder::Target::Goto(name.to_owned());
+    builder::StateMachines(vec![builder::StateMachine::new(
+        "Enemy",
+        vec![
+            state("Idle", vec![goto("Flee"), goto("Fight")]),
+            state("Flee", vec![]),
+            state("Fight", vec![]),
+        ],
+    )])
+}
+
+#[test]
+fn disabled_transition_never_fires() {
+    let machines: StateMachines<Noop<()>, Always<()>> = machine().build();
+    let enemy = machines.machine_handle("Enemy").unwrap();
+    let idle = machines.state_handle(&enemy, "Idle").unwrap().state;
+
+    let mut nested = NestedMachine::new_active();
+    nested.set_transition_enabled(&enemy, &idle, 0, false);
+    assert!(!nested.is_transition_enabled(&enemy, &idle, 0));
+    assert!(nested.is_transition_enabled(&enemy, &idle, 1));
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Fight"));
+
+    nested.set_transition_enabled(&enemy, &idle, 1, false);
+    nested.enter(&enemy);
+    for _ in 0..3 {
+        let _ = nested.update(&machines, &mut ()).unwrap();
+        assert_eq!(nested.current_state_name(&machines), Some("Idle"));
+    }
+}
+
+#[test]
+fn mask_is_per_instance() {
+    let machines: StateMachines<Noop<()>, Always<()>> = machine().build();
+    let enemy = machines.machine_handle("Enemy").unwrap();
+    let idle = machines.state_handle(&enemy, "Idle").unwrap().state;
+
+    let mut hard = NestedMachine::new_active();
+    let mut easy = NestedMachine::new_active();
+    hard.set_transition_enabled(&enemy, &idle, 0, false);
+    let _ = hard.update(&machines, &mut ()).unwrap();
+    let _ = easy.update(&machines, &mut ()).unwrap();
+    assert_eq!(hard.current_state_name(&machines), Some("Fight"));
+    assert_eq!(easy.current_state_name(&machines), Some("Flee"));
+
+    hard.set_transition_enabled(&enemy, &idle, 0, true);
+    assert!(hard.is_transition_enabled(&enemy, &idle, 0));
+}
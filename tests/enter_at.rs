@@ -0,0 +1,88 @@
+//! `Target::EnterAt` starts a nested machine in a state other than its initial one.
+use pure_hfsm::builder;
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::transition::Always;
+use pure_hfsm::{Behavior, Error, SHandle, StateData};
+
+/// Records the name it was given when updated
+#[derive(Clone)]
+struct Log(&'static str);
+impl Behavior for Log {
+    type Context<'w, 's> = Vec<&'static str>;
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, log: &mut Vec<&'static str>) {
+        log.push(self.0);
+    }
+}
+
+type Machines = pure_hfsm::StateMachines<Log, Always<Vec<&'static str>>>;
+
+fn state(
+    name: &'static str,
+    transitions: Vec<builder::Target>,
+) -> builder::State<Log, builder::Target> {
+    builder::State::new(name, Log(name), transitions)
+}
+
+/// `Parent` enters `Child` at `Second`, `Child` also has a `Wait` state
+/// named like one of `Parent`'s, to check names are scoped to the machine
+fn machines() -> builder::StateMachines<Log, builder::Target> {
+    let enter_at = builder::Target::EnterAt("Child".to_owned(), "Second".to_owned());
+    builder::StateMachines(vec![
+        builder::StateMachine::new(
+            "Parent",
+            vec![state("Start", vec![enter_at]), state("Second", vec![])],
+        ),
+        builder::StateMachine::new(
+            "Child",
+            vec![
+                state("First", vec![builder::Target::End]),
+                state("Second", vec![builder::Target::Goto("First".to_owned())]),
+            ],
+        ),
+    ])
+}
+
+#[test]
+fn transition_enters_at_state() {
+    let machines: Machines = machines().build();
+
+    let mut nested = NestedMachine::new_active();
+    let mut log = Vec::new();
+    let _ = nested.update(&machines, &mut log).unwrap();
+    assert_eq!(nested.stack_len(), 2);
+    assert_eq!(nested.current_state_name(&machines), Some("Second"));
+    assert_eq!(nested.current_machine_name(&machines), Some("Child"));
+    let _ = nested.update(&machines, &mut log).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("First"));
+    assert_eq!(log, ["Start", "Second"]);
+}
+
+#[test]
+fn enter_at_validates_handles() {
+    let machines: Machines = machines().build();
+    let child = machines.machine_handle("Child").unwrap();
+    let second = machines.state_handle(&child, "Second").unwrap().state;
+
+    let mut nested = NestedMachine::new();
+    let missing = SHandle::new(7);
+    let error = nested.enter_at(&machines, &child, &missing).unwrap_err();
+    assert!(matches!(error, Error::BadStateName { state, .. } if state == missing));
+    assert_eq!(nested.stack_len(), 0);
+
+    assert_eq!(nested.enter_at(&machines, &child, &second).unwrap(), 1);
+    assert_eq!(nested.current_state_name(&machines), Some("Second"));
+}
+
+#[test]
+fn round_trips_to_builder() {
+    let machines: Machines = machines().build();
+
+    let builder = builder::StateMachines::<Log, builder::Target>::try_from(&machines);
+    let builder = builder.unwrap();
+    let transitions = &builder.0[0].states[0].transitions;
+    assert!(matches!(
+        &transitions[..],
+        [builder::Target::EnterAt(m, s)] if m == "Child" && s == "Second"
+    ));
+}
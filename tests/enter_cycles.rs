@@ -0,0 +1,49 @@
+//! `StateMachines::enter_cycles` finds machines entering each other.
+use pure_hfsm::prelude::*;
+use pure_hfsm::SmHandle;
+
+type Machines = StateMachines<Noop<()>, Always<()>>;
+
+/// One machine per `(name, entered machines)`, with a single state
+fn machines(graph: &[(&str, &[&str])]) -> Machines {
+    let machine = |(name, entered): &(&str, &[&str])| {
+        let enter = |machine: &&str| builder::Target::Enter((*machine).to_owned());
+        let state = builder::State::new("only", Noop::new(), entered.iter().map(enter).collect());
+        builder::StateMachine::new(*name, vec![state])
+    };
+    builder::StateMachines(graph.iter().map(machine).collect()).build()
+}
+
+/// The cycles of `machines`, as sorted groups of machine names
+fn cycles(machines: &Machines) -> Vec<Vec<&str>> {
+    let name = |handle: SmHandle| machines.machines().find(|(h, _)| *h == handle).unwrap().1;
+    let mut cycles: Vec<Vec<&str>> = machines
+        .enter_cycles()
+        .into_iter()
+        .map(|group| {
+            let mut names: Vec<_> = group.into_iter().map(name).collect();
+            names.sort_unstable();
+            names
+        })
+        .collect();
+    cycles.sort();
+    cycles
+}
+
+#[test]
+fn mutual_and_self_enter() {
+    let machines = machines(&[
+        ("A", &["B"]),
+        ("B", &["A", "C"]),
+        ("C", &["C"]),
+        ("D", &["A"]),
+        ("E", &[]),
+    ]);
+    assert_eq!(cycles(&machines), [vec!["A", "B"], vec!["C"]]);
+}
+
+#[test]
+fn acyclic_has_no_cycles() {
+    let machines = machines(&[("A", &["B", "C"]), ("B", &["C"]), ("C", &[])]);
+    assert!(machines.enter_cycles().is_empty());
+}
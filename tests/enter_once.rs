@@ -0,0 +1,79 @@
+//! `Target::EnterOnce` pushes the nested machine once per visit of a state.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::{NestedMachine, NestedMachineConfig};
+use pure_hfsm::{Behavior, Error, StateData, Target, Transition};
+
+struct Idle;
+impl Behavior for Idle {
+    type Context<'w, 's> = ();
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &mut ()) {}
+}
+
+struct Always(Target);
+impl Transition for Always {
+    type Context<'w, 's> = ();
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        self.0.clone()
+    }
+}
+impl IntoTransition<Always> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> Always {
+        Always(mapping.target(&self).expect("valid name"))
+    }
+}
+
+fn machine(
+    name: &str,
+    transitions: Vec<builder::Target>,
+) -> builder::StateMachine<Idle, builder::Target> {
+    let state = builder::State::new(format!("{name}_state"), Idle, transitions);
+    builder::StateMachine::new(name, vec![state])
+}
+
+#[test]
+fn enter_once_pushes_once() {
+    let description = builder::StateMachines(vec![
+        machine(
+            "Parent",
+            vec![builder::Target::EnterOnce("Child".to_owned())],
+        ),
+        machine("Child", vec![builder::Target::End]),
+    ]);
+    let machines = description.build::<Always>();
+
+    let mut nested = NestedMachine::new_active();
+    let mut depths = Vec::new();
+    for _ in 0..5 {
+        let _ = nested.update(&machines, &mut ()).unwrap();
+        depths.push(nested.stack_len());
+    }
+    // Enter, the child completes, then the parent stays without re-entering
+    assert_eq!(depths, [2, 1, 1, 1, 1]);
+    assert_eq!(nested.current_machine_name(&machines), Some("Parent"));
+}
+
+#[test]
+fn depth_limit_does_not_use_up_enter_once() {
+    let description = builder::StateMachines(vec![
+        machine(
+            "Parent",
+            vec![builder::Target::EnterOnce("Child".to_owned())],
+        ),
+        machine("Child", vec![]),
+    ]);
+    let machines = description.build::<Always>();
+    let max_depth = |max_depth| NestedMachineConfig {
+        max_depth,
+        ..NestedMachineConfig::default()
+    };
+
+    let mut nested = NestedMachine::new_active().with_config(max_depth(1));
+    let error = nested.update(&machines, &mut ()).unwrap_err();
+    assert!(matches!(error, Error::DepthLimit { depth: 1, .. }));
+    // The `EnterOnce` fires again once the limit allows it
+    let mut nested = nested.with_config(max_depth(usize::MAX));
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.current_machine_name(&machines), Some("Child"));
+}
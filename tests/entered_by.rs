@@ -0,0 +1,102 @@
+//! `StateMachines::entered_by` finds all the states entering a machine, and
+//! `enter_graph` the machines each machine enters.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::{Behavior, DescribeTransition, SHandle, StateData, Target, Transition};
+
+struct Idle;
+impl Behavior for Idle {
+    type Context<'w, 's> = ();
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &mut ()) {}
+}
+
+struct Always(Target);
+impl Transition for Always {
+    type Context<'w, 's> = ();
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        self.0.clone()
+    }
+}
+impl DescribeTransition for Always {
+    fn targets(&self) -> Vec<Target> {
+        vec![self.0.clone()]
+    }
+}
+impl IntoTransition<Always> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> Always {
+        Always(mapping.target(&self).expect("valid name"))
+    }
+}
+
+fn state(name: &str, transitions: Vec<builder::Target>) -> builder::State<Idle, builder::Target> {
+    builder::State::new(name, Idle, transitions)
+}
+
+#[test]
+fn two_machines_enter_the_same_machine() {
+    let enter = |name: &str| builder::Target::Enter(name.to_owned());
+    let goto = |name: &str| builder::Target::Goto(name.to_owned());
+    let machine = |name: &str, states| builder::StateMachine::new(name, states);
+    let description = builder::StateMachines(vec![
+        machine(
+            "Guard",
+            vec![
+                state("patrol", vec![goto("alert")]),
+                state("alert", vec![enter("Shared"), enter("Shared")]),
+            ],
+        ),
+        machine(
+            "Thief",
+            vec![state("sneak", vec![goto("sneak"), enter("Shared")])],
+        ),
+        machine("Other", vec![state("idle", vec![enter("Guard")])]),
+        machine("Shared", vec![state("look_around", vec![])]),
+    ]);
+    let machines = description.build::<Always>();
+
+    let shared = machines.machine_handle("Shared").unwrap();
+    let guard = machines.machine_handle("Guard").unwrap();
+    let thief = machines.machine_handle("Thief").unwrap();
+    let alert = machines.state_handle(&guard, "alert").unwrap().state;
+    let sneak = machines.state_handle(&thief, "sneak").unwrap().state;
+    assert_eq!(
+        machines.entered_by(&shared),
+        [(guard.clone(), alert), (thief, sneak)]
+    );
+
+    let other = machines.machine_handle("Other").unwrap();
+    assert_eq!(
+        machines.entered_by(&guard),
+        [(other.clone(), SHandle::new(0))]
+    );
+    assert!(machines.entered_by(&other).is_empty());
+}
+
+#[test]
+fn enter_graph_lists_entered_machines_once() {
+    let enter = |name: &str| builder::Target::Enter(name.to_owned());
+    let machine = |name: &str, states| builder::StateMachine::new(name, states);
+    let description = builder::StateMachines(vec![
+        machine(
+            "Boss",
+            vec![
+                state("phase1", vec![enter("Summon"), enter("Rage")]),
+                state("phase2", vec![enter("Summon")]),
+            ],
+        ),
+        machine("Summon", vec![state("cast", vec![])]),
+        machine("Rage", vec![state("smash", vec![])]),
+    ]);
+    let machines = description.build::<Always>();
+
+    let handle = |name| machines.machine_handle(name).unwrap();
+    assert_eq!(
+        machines.enter_graph(),
+        [
+            (handle("Boss"), vec![handle("Summon"), handle("Rage")]),
+            (handle("Summon"), vec![]),
+            (handle("Rage"), vec![]),
+        ]
+    );
+}
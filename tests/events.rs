@@ -0,0 +1,100 @@
+//! Transitions reacting to events sent to the `NestedMachine`.
+use pure_hfsm::behavior::Noop;
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::{Event, StateData, Target, Transition};
+
+#[derive(Debug, PartialEq)]
+enum Door {
+    Open,
+    Close,
+}
+
+/// Goes to `target` on `on` events
+struct On(Door, Target);
+impl Transition for On {
+    type Context<'w, 's> = ();
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        Target::Continue
+    }
+    fn decide_event<'w, 's>(
+        &self,
+        event: &Event,
+        _: &mut StateData,
+        _: &mut StateData,
+        _: &(),
+    ) -> Target {
+        match event.downcast_ref::<Door>() {
+            Some(door) if *door == self.0 => self.1.clone(),
+            _ => Target::Continue,
+        }
+    }
+}
+impl IntoTransition<On> for (Door, builder::Target) {
+    fn into_with(self, mapping: &NameMapping) -> On {
+        On(self.0, mapping.target(&self.1).expect("valid name"))
+    }
+}
+
+fn machine() -> builder::StateMachines<Noop<()>, (Door, builder::Target)> {
+    let state = |name: &str, on, goto: &str| {
+        builder::State::new(
+            name,
+            Noop::new(),
+            vec![(on, builder::Target::Goto(goto.to_owned()))],
+        )
+    };
+    builder::StateMachines(vec![builder::StateMachine::new(
+        "Door",
+        vec![
+            state("Closed", Door::Open, "Opened"),
+            state("Opened", Door::Close, "Closed"),
+        ],
+    )])
+}
+
+#[test]
+fn one_event_per_update() {
+    let machines = machine().build::<On>();
+
+    let mut nested = NestedMachine::new_active();
+    nested.send_event(Door::Open);
+    nested.send_event(Door::Close);
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Opened"));
+    assert_eq!(nested.pending_events(), 1);
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Closed"));
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Closed"));
+}
+
+#[test]
+fn unhandled_events_are_dropped() {
+    let machines = machine().build::<On>();
+
+    let mut nested = NestedMachine::new_active();
+    nested.send_event(Door::Close);
+    nested.send_event("not a door");
+    nested.send_event(Door::Open);
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Closed"));
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Opened"));
+    assert_eq!(nested.pending_events(), 0);
+}
+
+#[test]
+fn events_wait_for_an_active_machine() {
+    let machines = machine().build::<On>();
+
+    let mut nested = NestedMachine::new();
+    nested.send_event(Door::Open);
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.pending_events(), 1);
+    nested.enter_by_name(&machines, "Door").unwrap();
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Opened"));
+}
@@ -0,0 +1,96 @@
+//! Each `Target` variant, acted on by `NestedMachine::update`.
+use pure_hfsm::behavior::Noop;
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::{Complete, NestedMachine};
+use pure_hfsm::{StateData, StateMachines, Target, Transition};
+
+/// Always returns its target, built from `None` for `Target::Continue`
+struct Fixed(Target);
+impl Transition for Fixed {
+    type Context<'w, 's> = ();
+
+    fn decide(&self, _: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        self.0.clone()
+    }
+}
+impl IntoTransition<Fixed> for Option<builder::Target> {
+    fn into_with(self, mapping: &NameMapping) -> Fixed {
+        let target = self.map(|target| mapping.target(&target).expect("valid name"));
+        Fixed(target.unwrap_or(Target::Continue))
+    }
+}
+
+/// Where the `NestedMachine` is after `Root`'s `Start` fires `target` once
+struct After {
+    complete: Complete,
+    depth: usize,
+    machine: Option<String>,
+    state: Option<String>,
+}
+
+fn after(target: Option<builder::Target>) -> After {
+    let state = |name: &str, transitions| builder::State::new(name, Noop::new(), transitions);
+    let description = builder::StateMachines(vec![
+        builder::StateMachine::new(
+            "Root",
+            vec![state("Start", vec![target]), state("Other", vec![])],
+        ),
+        builder::StateMachine::new(
+            "Child",
+            vec![state("First", vec![]), state("Second", vec![])],
+        ),
+    ]);
+    let machines: StateMachines<Noop<()>, Fixed> = description.build();
+
+    let mut nested = NestedMachine::new_active();
+    let complete = nested.update(&machines, &mut ()).unwrap();
+    After {
+        complete,
+        depth: nested.stack_len(),
+        machine: nested.current_machine_name(&machines).map(str::to_owned),
+        state: nested.current_state_name(&machines).map(str::to_owned),
+    }
+}
+
+/// Assert that `after` is running in `machine`'s `state` at `depth`
+fn assert_running(after: After, depth: usize, machine: &str, state: &str) {
+    assert_eq!(after.complete, Complete::Running);
+    assert_eq!(after.depth, depth);
+    assert_eq!(after.machine.as_deref(), Some(machine));
+    assert_eq!(after.state.as_deref(), Some(state));
+}
+
+#[test]
+fn continue_stays() {
+    assert_running(after(None), 1, "Root", "Start");
+}
+
+#[test]
+fn goto_changes_state() {
+    let goto = builder::Target::Goto("Other".to_owned());
+    assert_running(after(Some(goto)), 1, "Root", "Other");
+}
+
+#[test]
+fn enter_pushes_machine() {
+    let enter = builder::Target::Enter("Child".to_owned());
+    assert_running(after(Some(enter)), 2, "Child", "First");
+    let enter_once = builder::Target::EnterOnce("Child".to_owned());
+    assert_running(after(Some(enter_once)), 2, "Child", "First");
+}
+
+#[test]
+fn enter_at_pushes_machine_in_state() {
+    let enter_at = builder::Target::EnterAt("Child".to_owned(), "Second".to_owned());
+    assert_running(after(Some(enter_at)), 2, "Child", "Second");
+}
+
+#[test]
+fn complete_and_pop_n_end_outermost_machine() {
+    for target in [builder::Target::End, builder::Target::PopN(1)] {
+        let after = after(Some(target));
+        assert_eq!(after.complete, Complete::Done);
+        assert_eq!(after.depth, 0);
+        assert_eq!(after.state, None);
+    }
+}
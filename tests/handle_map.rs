@@ -0,0 +1,77 @@
+//! `StateMachines::build_with_handle_map` keeps handles stable between edits.
+use pure_hfsm::prelude::*;
+use pure_hfsm::SmHandle;
+
+type Machines = StateMachines<Noop<()>, Always<()>>;
+type Machine = builder::StateMachine<Noop<()>, builder::Target>;
+
+/// A machine with one state per `(name, region)`
+fn machine(name: &str, states: &[(&str, u8)]) -> Machine {
+    let state = |&(name, region): &(&str, u8)| builder::State {
+        region,
+        ..builder::State::new(name, Noop::new(), vec![])
+    };
+    builder::StateMachine::new(name, states.iter().map(state).collect())
+}
+
+/// Rebuild `machines` with the handles of `prev`
+fn rebuild(prev: &Machines, machines: Vec<Machine>) -> Machines {
+    builder::StateMachines(machines).build_with_handle_map(Some(prev))
+}
+
+/// The names of the states of `machine`, in handle order
+fn state_names<'a>(machines: &'a Machines, machine: &str) -> Vec<&'a str> {
+    let handle = machines.machine_handle(machine).unwrap();
+    machines
+        .states(&handle)
+        .unwrap()
+        .map(|(_, name)| name)
+        .collect()
+}
+
+#[test]
+fn states_keep_their_handle() {
+    let prev = builder::StateMachines(vec![machine("M", &[("A", 0), ("B", 0), ("C", 0)])]).build();
+    let machines = rebuild(
+        &prev,
+        vec![machine("M", &[("A", 0), ("X", 0), ("Y", 0), ("C", 0)])],
+    );
+
+    assert_eq!(state_names(&machines, "M"), ["A", "X", "C", "Y"]);
+}
+
+#[test]
+fn initial_state_stays_first() {
+    let prev = builder::StateMachines(vec![machine("M", &[("A", 0), ("B", 0)])]).build();
+    let machines = rebuild(&prev, vec![machine("M", &[("B", 0), ("A", 0)])]);
+
+    assert_eq!(state_names(&machines, "M"), ["B", "A"]);
+    let nested = NestedMachine::new_active();
+    assert_eq!(nested.current_state_name(&machines), Some("B"));
+}
+
+#[test]
+fn region_initial_state_stays_first_of_region() {
+    let prev = [("A", 0), ("B", 1), ("C", 1), ("D", 1)];
+    let prev = builder::StateMachines(vec![machine("M", &prev)]).build();
+    let next = [("A", 0), ("D", 1), ("B", 1), ("C", 1)];
+    let machines = rebuild(&prev, vec![machine("M", &next)]);
+
+    // D takes the place of B, the other states of region 1 keep their handle
+    assert_eq!(state_names(&machines, "M"), ["A", "D", "C", "B"]);
+}
+
+#[test]
+fn initial_machine_stays_first() {
+    let prev = vec![machine("M", &[("A", 0)]), machine("N", &[("B", 0)])];
+    let prev = builder::StateMachines(prev).build();
+    let machines = rebuild(
+        &prev,
+        vec![machine("N", &[("B", 0)]), machine("M", &[("A", 0)])],
+    );
+
+    assert_eq!(machines.machine_handle("N"), Some(SmHandle::new(0)));
+    assert_eq!(machines.machine_handle("M"), Some(SmHandle::new(1)));
+    let nested = NestedMachine::new_active();
+    assert_eq!(nested.current_machine_name(&machines), Some("N"));
+}
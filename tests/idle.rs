@@ -0,0 +1,43 @@
+//! `update` returns `Complete::Idle` when the `NestedMachine` is inactive.
+use pure_hfsm::label::Complete;
+use pure_hfsm::prelude::*;
+
+/// A single machine completing on its first update
+fn machines() -> StateMachines<Noop<()>, Always<()>> {
+    let state = builder::State::new("Finish", Noop::new(), vec![builder::Target::End]);
+    builder::StateMachines(vec![builder::StateMachine::new("Machine", vec![state])]).build()
+}
+
+fn update(nested: &mut NestedMachine, machines: &StateMachines<Noop<()>, Always<()>>) -> Complete {
+    nested.update(machines, &mut ()).unwrap()
+}
+
+#[test]
+fn never_started() {
+    let machines = machines();
+    let mut nested = NestedMachine::new();
+
+    assert_eq!(update(&mut nested, &machines), Complete::Idle);
+    assert!(!nested.is_complete());
+}
+
+#[test]
+fn popped_empty() {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+    let _ = nested.pop();
+
+    assert_eq!(update(&mut nested, &machines), Complete::Idle);
+    assert!(!nested.is_complete());
+}
+
+#[test]
+fn already_completed() {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+
+    assert_eq!(update(&mut nested, &machines), Complete::Done);
+    assert_eq!(update(&mut nested, &machines), Complete::Idle);
+    assert_eq!(update(&mut nested, &machines), Complete::Idle);
+    assert!(nested.is_complete());
+}
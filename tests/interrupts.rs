@@ -0,0 +1,67 @@
+//! Interrupts fire whatever the current state and nesting depth are.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::{Behavior, StateData, Target, Transition};
+
+struct Idle;
+impl Behavior for Idle {
+    type Context<'w, 's> = bool;
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &mut bool) {}
+}
+
+enum Trans {
+    Always(Target),
+    /// Fires when the building is on fire
+    OnFire(Target),
+}
+impl Transition for Trans {
+    type Context<'w, 's> = bool;
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, on_fire: &bool) -> Target {
+        match self {
+            Trans::Always(target) => target.clone(),
+            Trans::OnFire(target) if *on_fire => target.clone(),
+            Trans::OnFire(_) => Target::Continue,
+        }
+    }
+}
+struct Enter(&'static str);
+impl IntoTransition<Trans> for Enter {
+    fn into_with(self, mapping: &NameMapping) -> Trans {
+        Trans::Always(mapping.enter(self.0).expect("valid name"))
+    }
+}
+
+fn machine(name: &str, enters: Option<&'static str>) -> builder::StateMachine<Idle, Enter> {
+    let state = builder::State::new(
+        format!("{name}_state"),
+        Idle,
+        enters.map(Enter).into_iter().collect(),
+    );
+    builder::StateMachine::new(name, vec![state])
+}
+
+#[test]
+fn interrupt_unwinds_deep_nesting() {
+    let description = builder::StateMachines(vec![
+        machine("Root", Some("Middle")),
+        machine("Middle", Some("Deep")),
+        machine("Deep", None),
+        machine("Evacuate", None),
+    ]);
+    let machines = description.build::<Trans>();
+    let evacuate = machines.machine_handle("Evacuate").unwrap();
+    let machines = machines.with_interrupts(vec![Trans::OnFire(Target::Enter(evacuate))]);
+
+    let mut nested = NestedMachine::new_active();
+    for _ in 0..3 {
+        let _ = nested.update(&machines, &mut false).unwrap();
+    }
+    assert_eq!(nested.stack_len(), 3);
+    assert_eq!(nested.current_machine_name(&machines), Some("Deep"));
+
+    let _ = nested.update(&machines, &mut true).unwrap();
+    assert_eq!(nested.stack_len(), 1);
+    assert_eq!(nested.current_machine_name(&machines), Some("Evacuate"));
+}
@@ -0,0 +1,93 @@
+//! Transitions remember previous state visits in the machine's data.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::{Behavior, StateData, Target, Transition};
+
+/// The boss' health
+type Health = u32;
+
+struct Idle;
+impl Behavior for Idle {
+    type Context<'w, 's> = Health;
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &mut Health) {}
+}
+
+/// How many times health dropped under half, stored in the machine's data
+#[derive(Default)]
+struct Drops(u32);
+
+enum Guard {
+    /// Fires the `nth` time health drops under half
+    Dropped(u32, Target),
+    /// Fires when health is back over half
+    Recovered(Target),
+}
+impl Guard {
+    fn drops(machine_data: &mut StateData) -> &mut Drops {
+        if !machine_data.is::<Drops>() {
+            *machine_data = Box::new(Drops::default());
+        }
+        machine_data.downcast_mut().unwrap()
+    }
+}
+impl Transition for Guard {
+    type Context<'w, 's> = Health;
+
+    fn decide<'w, 's>(&self, _: &mut StateData, machine: &mut StateData, hp: &Health) -> Target {
+        match self {
+            Guard::Dropped(nth, target) if *hp < 50 && Guard::drops(machine).0 + 1 == *nth => {
+                target.clone()
+            }
+            Guard::Recovered(target) if *hp >= 50 => target.clone(),
+            _ => Target::Continue,
+        }
+    }
+    fn on_fire<'w, 's>(&self, _: &mut StateData, machine: &mut StateData, _: &mut Health) {
+        if let Guard::Dropped(..) = self {
+            Guard::drops(machine).0 += 1;
+        }
+    }
+}
+
+enum Desc {
+    Dropped(u32, &'static str),
+    Recovered(&'static str),
+}
+impl IntoTransition<Guard> for Desc {
+    fn into_with(self, mapping: &NameMapping) -> Guard {
+        let goto = |name| mapping.goto(name).expect("valid name");
+        match self {
+            Desc::Dropped(nth, name) => Guard::Dropped(nth, goto(name)),
+            Desc::Recovered(name) => Guard::Recovered(goto(name)),
+        }
+    }
+}
+
+#[test]
+fn fires_on_second_crossing() {
+    let state = |name: &str, transitions| builder::State::new(name, Idle, transitions);
+    let description = builder::StateMachines(vec![builder::StateMachine::new(
+        "Boss",
+        vec![
+            state(
+                "Fight",
+                vec![Desc::Dropped(2, "Enrage"), Desc::Dropped(1, "Flee")],
+            ),
+            state("Flee", vec![Desc::Recovered("Fight")]),
+            state("Enrage", vec![]),
+        ],
+    )]);
+    let machines = description.build::<Guard>();
+
+    let mut nested = NestedMachine::new_active();
+    let mut states = Vec::new();
+    for mut health in [100, 40, 60, 70, 40, 30] {
+        let _ = nested.update(&machines, &mut health).unwrap();
+        states.push(nested.current_state_name(&machines).unwrap());
+    }
+    assert_eq!(
+        states,
+        ["Fight", "Flee", "Fight", "Fight", "Enrage", "Enrage"]
+    );
+}
@@ -0,0 +1,77 @@
+//! Turning the behaviors and transitions of built machines into other types.
+use pure_hfsm::prelude::*;
+
+/// Pushes its word to the context on each update
+struct Say(String);
+impl Behavior for Say {
+    type Context<'w, 's> = Vec<String>;
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, said: &mut Vec<String>) {
+        said.push(self.0.clone());
+    }
+}
+
+/// A machine saying `hello` then `bye`, with placeholder behaviors
+fn machine() -> builder::StateMachines<&'static str, builder::Target> {
+    let state = |name: &str, behavior, target| builder::State::new(name, behavior, vec![target]);
+    builder::StateMachines(vec![builder::StateMachine::new(
+        "Greet",
+        vec![
+            state("Hello", "hello", builder::Target::Goto("Bye".to_owned())),
+            state("Bye", "bye", builder::Target::End),
+        ],
+    )])
+}
+
+#[test]
+fn map_placeholder_behaviors() {
+    let placeholders: StateMachines<&str, Always<Vec<String>>> = machine().build();
+    let version = placeholders.version();
+    let machines = placeholders.map_behaviors(|word| Say(word.to_owned()));
+    assert_eq!(machines.version(), version);
+
+    let mut said = Vec::new();
+    let mut nested = NestedMachine::new_active();
+    nested.run_to_completion(&machines, &mut said, 2).unwrap();
+    assert_eq!(said, ["hello", "bye"]);
+    assert_eq!(nested.current_state_name(&machines), None);
+    let greet = machines.machine_handle("Greet").unwrap();
+    assert!(machines.contains_state(&greet, "Bye"));
+}
+
+#[test]
+fn try_map_stops_at_first_error() {
+    let placeholders: StateMachines<&str, Always<Vec<String>>> = machine().build();
+
+    let mut seen = Vec::new();
+    let failed = placeholders.try_map_behaviors(|word| {
+        seen.push(word);
+        match word {
+            "hello" => Err(word),
+            _ => Ok(Say(word.to_owned())),
+        }
+    });
+    assert_eq!(failed.err(), Some("hello"));
+    assert_eq!(seen, ["hello"]);
+}
+
+#[test]
+fn map_transitions_keeps_targets() {
+    let unit_context: StateMachines<&str, Always<()>> = machine().build();
+    let unit_context = unit_context.with_interrupts(vec![Always::new(Target::Continue)]);
+
+    let mut mapped = 0;
+    let machines = unit_context
+        .map_transitions(|transition| {
+            mapped += 1;
+            Always::<Vec<String>>::new(transition.target().clone())
+        })
+        .map_behaviors(|word| Say(word.to_owned()));
+    assert_eq!(mapped, 3);
+    assert_eq!(machines.interrupts().len(), 1);
+
+    let mut said = Vec::new();
+    let mut nested = NestedMachine::new_active();
+    nested.run_to_completion(&machines, &mut said, 2).unwrap();
+    assert_eq!(said, ["hello", "bye"]);
+}
@@ -0,0 +1,104 @@
+//! Observing and replacing what transitions decide with a middleware.
+use std::sync::{Arc, Mutex};
+
+use pure_hfsm::behavior::Noop;
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::{NestedMachine, TransitionContext};
+use pure_hfsm::{StateData, StateMachines, Target, Transition};
+
+/// Goes to its target when the context is `true`
+struct When(Target);
+impl Transition for When {
+    type Context<'w, 's> = bool;
+
+    fn decide(&self, _: &mut StateData, _: &mut StateData, fire: &bool) -> Target {
+        match fire {
+            true => self.0.clone(),
+            false => Target::Continue,
+        }
+    }
+    fn needs_data(&self) -> bool {
+        false
+    }
+}
+impl IntoTransition<When> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> When {
+        When(mapping.target(&self).expect("valid name"))
+    }
+}
+
+/// `Idle` goes to `Walk` or `Run`, which go back to `Idle`
+fn machines() -> StateMachines<Noop<bool>, When> {
+    let state = |name: &str, targets: &[&str]| {
+        let goto = |target: &&str| builder::Target::Goto((*target).to_owned());
+        builder::State::new(name, Noop::new(), targets.iter().map(goto).collect())
+    };
+    let machines = builder::StateMachines(vec![builder::StateMachine::new(
+        "Mover",
+        vec![
+            state("Idle", &["Walk", "Run"]),
+            state("Walk", &["Idle"]),
+            state("Run", &["Idle"]),
+        ],
+    )]);
+    machines.build()
+}
+
+#[test]
+fn middleware_forces_a_transition() {
+    let machines = machines();
+    let mover = machines.machine_handle("Mover").unwrap();
+    let run = machines.state_handle(&mover, "Run").unwrap().state;
+
+    let mut nested = NestedMachine::new_active();
+    nested.set_transition_middleware(move |context, target| match context.index {
+        1 => Target::Goto(run.clone()),
+        _ => target,
+    });
+    let _ = nested.update(&machines, &mut false).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Run"));
+}
+
+#[test]
+fn middleware_cancels_a_transition() {
+    let machines = machines();
+
+    let mut nested = NestedMachine::new_active();
+    nested.set_transition_middleware(|_, _| Target::Continue);
+    let _ = nested.update(&machines, &mut true).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Idle"));
+
+    nested.clear_transition_middleware();
+    let _ = nested.update(&machines, &mut true).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Walk"));
+}
+
+#[test]
+fn middleware_sees_decisions_until_one_fires() {
+    let machines = machines();
+    let mover = machines.machine_handle("Mover").unwrap();
+    let idle = machines.state_handle(&mover, "Idle").unwrap().state;
+    let walk = machines.state_handle(&mover, "Walk").unwrap().state;
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut nested = NestedMachine::new_active();
+    let log = Arc::clone(&seen);
+    nested.set_transition_middleware(move |context, target| {
+        log.lock().unwrap().push((context, target.clone()));
+        target
+    });
+    let _ = nested.update(&machines, &mut false).unwrap();
+    let _ = nested.update(&machines, &mut true).unwrap();
+
+    let context = |index| TransitionContext {
+        machine: mover.clone(),
+        state: idle.clone(),
+        index,
+    };
+    let expected = vec![
+        (context(0), Target::Continue),
+        (context(1), Target::Continue),
+        (context(0), Target::Goto(walk.clone())),
+    ];
+    assert_eq!(*seen.lock().unwrap(), expected);
+}
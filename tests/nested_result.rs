@@ -0,0 +1,130 @@
+//! A nested machine reports its result to the state that entered it.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::{Behavior, StateData, Target, Transition};
+
+/// The outcomes the parent received
+type Outcomes = Vec<&'static str>;
+
+enum Act {
+    /// Waits for the `MoveTo` machine
+    Wait,
+    /// Arrives immediately
+    MoveTo,
+}
+impl Behavior for Act {
+    type Context<'w, 's> = Outcomes;
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &mut Outcomes) {}
+
+    fn result(&self, _: &mut StateData, _: &mut StateData) -> Option<StateData> {
+        match self {
+            Act::Wait => None,
+            Act::MoveTo => Some(Box::new("arrived")),
+        }
+    }
+    fn on_result<'w, 's>(
+        &self,
+        _: &mut StateData,
+        _: &mut StateData,
+        result: StateData,
+        outcomes: &mut Outcomes,
+    ) {
+        outcomes.push(*result.downcast::<&'static str>().unwrap());
+    }
+}
+
+struct Always(Target);
+impl Transition for Always {
+    type Context<'w, 's> = Outcomes;
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &Outcomes) -> Target {
+        self.0.clone()
+    }
+}
+impl IntoTransition<Always> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> Always {
+        Always(mapping.target(&self).expect("valid name"))
+    }
+}
+
+fn machine(
+    name: &str,
+    behavior: Act,
+    transition: builder::Target,
+) -> builder::StateMachine<Act, builder::Target> {
+    let state = builder::State::new(format!("{name}_state"), behavior, vec![transition]);
+    builder::StateMachine::new(name, vec![state])
+}
+
+#[test]
+fn result_passed_to_parent() {
+    let description = builder::StateMachines(vec![
+        machine(
+            "Parent",
+            Act::Wait,
+            builder::Target::EnterOnce("MoveTo".to_owned()),
+        ),
+        machine("MoveTo", Act::MoveTo, builder::Target::End),
+    ]);
+    let machines = description.build::<Always>();
+
+    let mut nested = NestedMachine::new_active();
+    let mut outcomes = Vec::new();
+    // The parent enters `MoveTo`, which completes with its result
+    for _ in 0..2 {
+        let _ = nested.update(&machines, &mut outcomes).unwrap();
+    }
+    assert_eq!(nested.stack_len(), 1);
+    assert!(outcomes.is_empty());
+
+    // The parent reads it on its next update, once
+    for _ in 0..2 {
+        let _ = nested.update(&machines, &mut outcomes).unwrap();
+    }
+    assert_eq!(outcomes, ["arrived"]);
+}
+
+/// Counts in the machine data how many results were collected
+enum Counted {
+    Region,
+    /// Reports the count, as an `on_complete`
+    Report,
+}
+impl Behavior for Counted {
+    type Context<'w, 's> = Vec<u32>;
+
+    fn update<'w, 's>(&self, _: &mut StateData, machine_data: &mut StateData, log: &mut Vec<u32>) {
+        if let Counted::Report = self {
+            log.push(*pure_hfsm::state_data::<u32>(machine_data));
+        }
+    }
+    fn result(&self, _: &mut StateData, machine_data: &mut StateData) -> Option<StateData> {
+        *pure_hfsm::state_data::<u32>(machine_data) += 1;
+        None
+    }
+}
+
+#[test]
+fn result_collected_once_with_regions() {
+    use pure_hfsm::transition::AfterTicks;
+
+    let state = |name: &str, region, ticks| builder::State {
+        region,
+        ..builder::State::new(name, Counted::Region, vec![(ticks, builder::Target::End)])
+    };
+    let machine = builder::StateMachine {
+        on_complete: Some(Counted::Report),
+        ..builder::StateMachine::new("Regions", vec![state("Fast", 0, 1), state("Slow", 1, 2)])
+    };
+    let machines = builder::StateMachines(vec![machine]).build::<AfterTicks<_>>();
+
+    let mut nested = NestedMachine::new_active();
+    let mut log = Vec::new();
+    let _ = nested.update(&machines, &mut log).unwrap();
+    assert_eq!(nested.stack_len(), 1);
+    let _ = nested.update(&machines, &mut log).unwrap();
+    assert_eq!(nested.stack_len(), 0);
+    // Only the region completing the machine gave its result
+    assert_eq!(log, [1]);
+}
@@ -0,0 +1,68 @@
+//! Runs a machine from a `no_std` crate, to check that pure-hfsm builds
+//! without `std`.
+#![no_std]
+
+extern crate alloc;
+
+use alloc::borrow::ToOwned;
+use alloc::vec;
+
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::{Complete, NestedMachine};
+use pure_hfsm::{Behavior, StateData, StateMachines, Target, Transition};
+
+/// Increments the counter
+pub struct Count;
+impl Behavior for Count {
+    type Context<'w, 's> = u32;
+
+    fn update(&self, _: &mut StateData, _: &mut StateData, count: &mut u32) {
+        *count += 1;
+    }
+}
+
+/// Goes to `Target` once the counter reaches `u32`
+pub struct Reaches(u32, Target);
+impl Transition for Reaches {
+    type Context<'w, 's> = u32;
+
+    fn decide(&self, _: &mut StateData, _: &mut StateData, count: &u32) -> Target {
+        if *count >= self.0 {
+            self.1.clone()
+        } else {
+            Target::Continue
+        }
+    }
+    fn needs_data(&self) -> bool {
+        false
+    }
+}
+impl IntoTransition<Reaches> for (u32, builder::Target) {
+    fn into_with(self, mapping: &NameMapping) -> Reaches {
+        Reaches(self.0, mapping.target(&self.1).expect("valid name"))
+    }
+}
+
+/// Counts to 3 in `Low`, then to 5 in `High`, then completes
+pub fn count_to_five() -> u32 {
+    let state = |name: &str, transition| builder::State::new(name, Count, vec![transition]);
+    let machines: StateMachines<Count, Reaches> =
+        builder::StateMachines(vec![builder::StateMachine::new(
+            "Counter",
+            vec![
+                state("Low", (3, builder::Target::Goto("High".to_owned()))),
+                state("High", (5, builder::Target::End)),
+            ],
+        )])
+        .build();
+
+    let mut nested = NestedMachine::new_active();
+    let mut count = 0;
+    while nested.update(&machines, &mut count).unwrap() == Complete::Running {}
+    count
+}
+
+#[test]
+fn counts_to_five() {
+    assert_eq!(count_to_five(), 5);
+}
@@ -0,0 +1,87 @@
+//! The `on_complete` behavior of a machine runs once when it completes.
+use pure_hfsm::prelude::*;
+
+/// Counts updates in the machine's data, and logs its name with the count
+#[derive(Clone)]
+struct Log(&'static str);
+impl Behavior for Log {
+    type Context<'w, 's> = Vec<String>;
+
+    fn update<'w, 's>(&self, _: &mut StateData, machine: &mut StateData, log: &mut Vec<String>) {
+        let count = state_data::<u32>(machine);
+        *count += 1;
+        log.push(format!("{} {count}", self.0));
+    }
+    fn result(&self, _: &mut StateData, _: &mut StateData) -> Option<StateData> {
+        Some(Box::new(self.0))
+    }
+    fn on_result<'w, 's>(
+        &self,
+        _: &mut StateData,
+        _: &mut StateData,
+        result: StateData,
+        log: &mut Vec<String>,
+    ) {
+        let from = result.downcast_ref::<&str>().unwrap();
+        log.push(format!("{} got result of {from}", self.0));
+    }
+}
+
+fn machines() -> StateMachines<Log, Always<Vec<String>>> {
+    let machine = |name: &str, state: &'static str, target, on_complete| builder::StateMachine {
+        on_complete,
+        ..builder::StateMachine::new(
+            name,
+            vec![builder::State::new(state, Log(state), vec![target])],
+        )
+    };
+    let enter_once = builder::Target::EnterOnce("Child".to_owned());
+    builder::StateMachines(vec![
+        machine("Parent", "Wait", enter_once, None),
+        machine("Child", "Work", builder::Target::End, Some(Log("Done"))),
+    ])
+    .build()
+}
+
+#[test]
+fn runs_once_between_result_and_on_result() {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+    let mut log = Vec::new();
+    for _ in 0..5 {
+        let _ = nested.update(&machines, &mut log).unwrap();
+    }
+    assert_eq!(
+        log,
+        [
+            "Wait 1",
+            "Work 1",
+            "Done 2",
+            "Wait got result of Work",
+            "Wait 2",
+            "Wait 3",
+            "Wait 4",
+        ]
+    );
+}
+
+#[test]
+fn not_run_when_popped_by_hand() {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+    let mut log = Vec::new();
+    let _ = nested.update(&machines, &mut log).unwrap();
+    let _ = nested.pop();
+    let _ = nested.update(&machines, &mut log).unwrap();
+    assert_eq!(log, ["Wait 1", "Wait 2"]);
+}
+
+#[test]
+fn round_trips_to_builder() {
+    let machines = machines();
+
+    let builder = builder::StateMachines::<Log, builder::Target>::try_from(&machines);
+    let builder = builder.unwrap();
+    assert!(builder.0[0].on_complete.is_none());
+    assert!(matches!(builder.0[1].on_complete, Some(Log("Done"))));
+}
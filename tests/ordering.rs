@@ -0,0 +1,75 @@
+//! Transitions are evaluated in the order they are described, the first one
+//! to fire wins.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::{Behavior, StateData, Target, Transition};
+
+#[derive(serde::Deserialize)]
+struct Idle;
+impl Behavior for Idle {
+    type Context<'w, 's> = ();
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &mut ()) {}
+}
+
+/// Always fires `target`, `name` is the name of the target state
+struct Fire {
+    name: String,
+    target: Target,
+}
+impl Transition for Fire {
+    type Context<'w, 's> = ();
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        self.target.clone()
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(transparent)]
+struct SerialFire(builder::Target);
+impl IntoTransition<Fire> for SerialFire {
+    fn into_with(self, mapping: &NameMapping) -> Fire {
+        let name = match &self.0 {
+            builder::Target::Goto(name) => name.clone(),
+            _ => String::new(),
+        };
+        let target = mapping.target(&self.0).expect("valid names");
+        Fire { name, target }
+    }
+}
+
+const DESCRIPTION: &str = r#"[
+    ["Machine",
+        ["start", null, {"Goto": "s0"}, {"Goto": "s1"}, {"Goto": "s2"}, {"Goto": "s3"}],
+        ["s3", null],
+        ["s2", null],
+        ["s1", null],
+        ["s0", null]
+    ]
+]"#;
+
+#[test]
+fn build_preserves_serialized_order() {
+    let description: builder::StateMachines<Idle, SerialFire> =
+        serde_json::from_str(DESCRIPTION).unwrap();
+    let machines = description.build::<Fire>();
+    let machine = machines.machine_ref("Machine").unwrap();
+    let start = machines
+        .state_handle(&machine.handle(), "start")
+        .unwrap()
+        .state;
+    let transitions = machine.transitions(&start).unwrap();
+    let names: Vec<_> = transitions.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, ["s0", "s1", "s2", "s3"]);
+}
+
+#[test]
+fn first_transition_to_fire_wins() {
+    let description: builder::StateMachines<Idle, SerialFire> =
+        serde_json::from_str(DESCRIPTION).unwrap();
+    let machines = description.build::<Fire>();
+    let mut nested = NestedMachine::new_active();
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("s0"));
+}
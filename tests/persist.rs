@@ -0,0 +1,165 @@
+//! A `NestedMachine` saved with its `StateData` resumes where it was.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::{Complete, NestedMachine};
+use pure_hfsm::persist::{SerializableData, SerializedMachine, UnserializableData};
+use pure_hfsm::{state_data, Behavior, Error, SmHandle, StateData, Target, Transition};
+use serde::{Deserialize, Serialize};
+
+/// Counts its updates in its data, and logs the count in the machine's data
+/// and in the context
+struct Count;
+impl Behavior for Count {
+    type Context<'w, 's> = Vec<u32>;
+
+    fn update<'w, 's>(&self, data: &mut StateData, machine: &mut StateData, ctx: &mut Vec<u32>) {
+        let count = state_data::<u32>(data);
+        *count += 1;
+        state_data::<Vec<u32>>(machine).push(*count);
+        ctx.push(*count);
+    }
+}
+
+/// Fires once the machine's log has at least the given number of entries
+struct Logged(usize, Target);
+impl Transition for Logged {
+    type Context<'w, 's> = Vec<u32>;
+
+    fn decide<'w, 's>(&self, _: &mut StateData, machine: &mut StateData, _: &Vec<u32>) -> Target {
+        let logged = state_data::<Vec<u32>>(machine).len();
+        if logged >= self.0 {
+            self.1.clone()
+        } else {
+            Target::Continue
+        }
+    }
+}
+impl IntoTransition<Logged> for (usize, builder::Target) {
+    fn into_with(self, mapping: &NameMapping) -> Logged {
+        Logged(self.0, mapping.target(&self.1).expect("valid name"))
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Data {
+    Count(u32),
+    Log(Vec<u32>),
+}
+impl SerializableData for Data {
+    fn serialize(data: &StateData) -> Option<Self> {
+        if let Some(count) = data.downcast_ref::<u32>() {
+            Some(Data::Count(*count))
+        } else {
+            data.downcast_ref::<Vec<u32>>().cloned().map(Data::Log)
+        }
+    }
+    fn deserialize(self) -> StateData {
+        match self {
+            Data::Count(count) => Box::new(count),
+            Data::Log(log) => Box::new(log),
+        }
+    }
+}
+
+/// Doesn't register the machines' log
+#[derive(Debug, Serialize, Deserialize)]
+struct OnlyCount(u32);
+impl SerializableData for OnlyCount {
+    fn serialize(data: &StateData) -> Option<Self> {
+        data.downcast_ref().copied().map(OnlyCount)
+    }
+    fn deserialize(self) -> StateData {
+        Box::new(self.0)
+    }
+}
+
+type Machines = pure_hfsm::StateMachines<Count, Logged>;
+
+/// `Parent` enters `Child`, which completes after three updates
+fn machines() -> Machines {
+    let machine = |name: &str, transition| {
+        builder::StateMachine::new(
+            name,
+            vec![builder::State::new(
+                format!("{name}State"),
+                Count,
+                vec![transition],
+            )],
+        )
+    };
+    builder::StateMachines(vec![
+        machine("Parent", (0, builder::Target::Enter("Child".to_owned()))),
+        machine("Child", (3, builder::Target::End)),
+    ])
+    .build()
+}
+
+fn run(machines: &Machines, nested: &mut NestedMachine, updates: usize) -> Vec<u32> {
+    let mut log = Vec::new();
+    for _ in 0..updates {
+        let _ = nested.update(machines, &mut log).unwrap();
+    }
+    log
+}
+
+#[test]
+fn restored_machine_keeps_its_data() {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+    assert_eq!(run(&machines, &mut nested, 2), [1, 1]);
+
+    let serialized = nested.serialize_full::<Data>().unwrap();
+    let json = serde_json::to_string(&serialized).unwrap();
+    let deserialized: SerializedMachine<Data> = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, serialized);
+    let mut restored = NestedMachine::deserialize_full(&machines, deserialized).unwrap();
+    assert_eq!(restored.stack_len(), 2);
+
+    let expected = run(&machines, &mut nested, 4);
+    assert_eq!(expected, [2, 3, 2, 1]);
+    assert_eq!(run(&machines, &mut restored, 4), expected);
+}
+
+#[test]
+fn unregistered_data_is_reported() {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+    let _ = run(&machines, &mut nested, 1);
+
+    let error = nested.serialize_full::<OnlyCount>().unwrap_err();
+    let parent = machines.machine_handle("Parent").unwrap();
+    assert_eq!(error, UnserializableData::Machine(parent));
+}
+
+#[test]
+fn restoring_checks_handles() {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+    let _ = run(&machines, &mut nested, 1);
+
+    let mut serialized = nested.serialize_full::<Data>().unwrap();
+    serialized.stack[1].machine = SmHandle::new(5);
+    let error = NestedMachine::deserialize_full(&machines, serialized).unwrap_err();
+    assert!(matches!(error, Error::BadMachineName(m) if m == SmHandle::new(5)));
+}
+
+#[test]
+fn throttled_tick_resumes_after_restoring() {
+    let state = |name: &str, region| builder::State {
+        region,
+        ..builder::State::new(name, Count, vec![(100, builder::Target::End)])
+    };
+    let states = vec![state("A", 0), state("B", 1)];
+    let machines: Machines =
+        builder::StateMachines(vec![builder::StateMachine::new("Regions", states)]).build();
+    let mut nested = NestedMachine::new_active();
+    let mut log = Vec::new();
+    let complete = nested.update_budgeted(&machines, &mut log, 1).unwrap();
+    assert_eq!((complete, log), (Complete::Throttled, vec![1]));
+
+    let serialized = nested.serialize_full::<Data>().unwrap();
+    assert!(serialized.stack[0].resume.is_some());
+    let mut restored = NestedMachine::deserialize_full(&machines, serialized).unwrap();
+
+    // Only `B` is left to update in the throttled tick
+    assert_eq!(run(&machines, &mut restored, 2), [1, 2, 2]);
+}
@@ -0,0 +1,77 @@
+//! `NestedMachine::pop` leaves the current machine without running hooks.
+use pure_hfsm::builder;
+use pure_hfsm::label::{Complete, NestedMachine};
+use pure_hfsm::transition::AfterTicks;
+use pure_hfsm::{Behavior, StateData, StateMachines};
+
+type Log = Vec<String>;
+
+/// Logs its updates and the results it gets, returns its name as result
+struct Named(&'static str);
+impl Behavior for Named {
+    type Context<'w, 's> = Log;
+
+    fn update(&self, _: &mut StateData, _: &mut StateData, log: &mut Log) {
+        log.push(self.0.to_owned());
+    }
+    fn result(&self, _: &mut StateData, _: &mut StateData) -> Option<StateData> {
+        Some(Box::new(self.0))
+    }
+    fn on_result(&self, _: &mut StateData, _: &mut StateData, result: StateData, log: &mut Log) {
+        let from = result.downcast_ref::<&str>().unwrap();
+        log.push(format!("{} got {from}", self.0));
+    }
+}
+
+type Machines = StateMachines<Named, AfterTicks<Log>>;
+
+/// `Parent` enters `Child` on its first update, `Child` has an `on_complete`
+fn machines() -> Machines {
+    let enter = (1, builder::Target::Enter("Child".to_owned()));
+    let parent = builder::State::new("Parent", Named("Parent"), vec![enter]);
+    let child = builder::State::new("Child", Named("Child"), vec![]);
+    builder::StateMachines(vec![
+        builder::StateMachine::new("Parent", vec![parent]),
+        builder::StateMachine {
+            on_complete: Some(Named("ChildDone")),
+            ..builder::StateMachine::new("Child", vec![child])
+        },
+    ])
+    .build()
+}
+
+fn tick(nested: &mut NestedMachine, machines: &Machines) -> (Complete, Log) {
+    let mut log = Vec::new();
+    let complete = nested.update(machines, &mut log).unwrap();
+    (complete, log)
+}
+
+#[test]
+fn parent_resumes_without_hooks() {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+    let _ = tick(&mut nested, &machines);
+    let _ = tick(&mut nested, &machines);
+    assert_eq!(nested.current_machine_name(&machines), Some("Child"));
+
+    assert_eq!(nested.pop(), machines.machine_handle("Child"));
+    assert_eq!(nested.stack_len(), 1);
+    // No `on_complete` of `Child`, no result given to `Parent`
+    let (complete, log) = tick(&mut nested, &machines);
+    assert_eq!(complete, Complete::Running);
+    assert_eq!(log, ["Parent"]);
+}
+
+#[test]
+fn popping_last_machine_does_not_complete() {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+    let _ = tick(&mut nested, &machines);
+
+    assert_eq!(nested.pop(), machines.machine_handle("Child"));
+    assert_eq!(nested.pop(), machines.machine_handle("Parent"));
+    assert!(!nested.is_active());
+    assert!(!nested.is_complete());
+    assert_eq!(tick(&mut nested, &machines).0, Complete::Idle);
+    assert_eq!(nested.pop(), None);
+}
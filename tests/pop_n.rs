@@ -0,0 +1,153 @@
+//! `Target::PopN` unwinds several nested machines at once.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::{Complete, NestedMachine};
+use pure_hfsm::{Behavior, StateData, StateMachines, Target, Transition};
+
+struct Idle;
+impl Behavior for Idle {
+    type Context<'w, 's> = ();
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &mut ()) {}
+}
+
+struct Always(Target);
+impl Transition for Always {
+    type Context<'w, 's> = ();
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        self.0.clone()
+    }
+}
+impl IntoTransition<Always> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> Always {
+        Always(mapping.target(&self).expect("valid name"))
+    }
+}
+
+fn machine(
+    name: &str,
+    transition: builder::Target,
+) -> builder::StateMachine<Idle, builder::Target> {
+    let state = builder::State::new(format!("{name}_state"), Idle, vec![transition]);
+    builder::StateMachine::new(name, vec![state])
+}
+
+/// Run a `NestedMachine` three machines deep, until the deepest one returns
+/// `PopN(n)`, returns the machines, the `NestedMachine` and that last `update`
+fn pop_from_depth_3(n: u8) -> (StateMachines<Idle, Always>, NestedMachine, Complete) {
+    let enter = |name: &str| builder::Target::Enter(name.to_owned());
+    let description = builder::StateMachines(vec![
+        machine("Root", enter("Middle")),
+        machine("Middle", enter("Deep")),
+        machine("Deep", builder::Target::PopN(n)),
+    ]);
+    let machines = description.build::<Always>();
+
+    let mut nested = NestedMachine::new_active();
+    for _ in 0..2 {
+        let _ = nested.update(&machines, &mut ()).unwrap();
+    }
+    assert_eq!(nested.stack_len(), 3);
+    let complete = nested.update(&machines, &mut ()).unwrap();
+    (machines, nested, complete)
+}
+
+#[test]
+fn pop_n_within_depth() {
+    let (machines, nested, complete) = pop_from_depth_3(2);
+    assert_eq!(complete, Complete::Running);
+    assert_eq!(nested.stack_len(), 1);
+    assert_eq!(nested.current_machine_name(&machines), Some("Root"));
+}
+
+#[test]
+fn pop_n_equal_to_depth() {
+    let (_, nested, complete) = pop_from_depth_3(3);
+    assert_eq!(complete, Complete::Done);
+    assert_eq!(nested.stack_len(), 0);
+}
+
+#[test]
+fn pop_n_exceeding_depth() {
+    let (_, nested, complete) = pop_from_depth_3(200);
+    assert_eq!(complete, Complete::Done);
+    assert_eq!(nested.stack_len(), 0);
+}
+
+/// Logs its updates and the results it gets, returns its name as result
+struct Log(&'static str);
+impl Behavior for Log {
+    type Context<'w, 's> = Vec<String>;
+
+    fn update(&self, _: &mut StateData, _: &mut StateData, log: &mut Vec<String>) {
+        log.push(self.0.to_owned());
+    }
+    fn result(&self, _: &mut StateData, _: &mut StateData) -> Option<StateData> {
+        Some(Box::new(self.0))
+    }
+    fn on_result(&self, _: &mut StateData, _: &mut StateData, _: StateData, log: &mut Vec<String>) {
+        log.push(format!("{} got a result", self.0));
+    }
+}
+
+#[test]
+fn pop_0_stays_in_state() {
+    use pure_hfsm::transition::Always;
+
+    let machine = |name: &str, state: &'static str, target| {
+        builder::StateMachine::new(
+            name,
+            vec![builder::State::new(state, Log(state), vec![target])],
+        )
+    };
+    let child = builder::StateMachine {
+        on_complete: Some(Log("on_complete")),
+        ..machine("Child", "Work", builder::Target::PopN(0))
+    };
+    let enter_once = builder::Target::EnterOnce("Child".to_owned());
+    let description = builder::StateMachines(vec![machine("Parent", "Wait", enter_once), child]);
+    let machines: StateMachines<Log, Always<Vec<String>>> = description.build();
+
+    let mut nested = NestedMachine::new_active();
+    let mut log = Vec::new();
+    for _ in 0..3 {
+        let complete = nested.update(&machines, &mut log).unwrap();
+        assert_eq!(complete, Complete::Running);
+    }
+    assert_eq!(nested.stack_len(), 2);
+    assert_eq!(nested.current_state_name(&machines), Some("Work"));
+    assert_eq!(log, ["Wait", "Work", "Work"]);
+}
+
+/// `Parent` enters `Child`, whose first region returns `first` while its
+/// second region keeps running, returns the stack length after `Child`'s
+/// first update
+fn regions_then(first: builder::Target) -> usize {
+    let enter_once = builder::Target::EnterOnce("Child".to_owned());
+    let running = builder::State {
+        region: 1,
+        ..builder::State::new("Running", Idle, vec![])
+    };
+    let child = builder::State::new("First", Idle, vec![first]);
+    let description = builder::StateMachines(vec![
+        machine("Parent", enter_once),
+        builder::StateMachine::new("Child", vec![child, running]),
+    ]);
+    let machines = description.build::<Always>();
+
+    let mut nested = NestedMachine::new_active();
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.stack_len(), 2);
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    nested.stack_len()
+}
+
+#[test]
+fn pop_1_pops_all_regions() {
+    assert_eq!(regions_then(builder::Target::PopN(1)), 1);
+}
+
+#[test]
+fn complete_waits_for_other_regions() {
+    assert_eq!(regions_then(builder::Target::End), 2);
+}
@@ -0,0 +1,37 @@
+//! A state machine built only from the ready-made types of the prelude.
+use pure_hfsm::prelude::*;
+
+/// A machine where `Idle` goes to `Done`, which completes the machine
+fn machine() -> builder::StateMachines<Noop<()>, builder::Target> {
+    let state = |name: &str, target| builder::State::new(name, Noop::new(), vec![target]);
+    builder::StateMachines(vec![builder::StateMachine::new(
+        "Machine",
+        vec![
+            state("Idle", builder::Target::Goto("Done".to_owned())),
+            state("Done", builder::Target::End),
+        ],
+    )])
+}
+
+#[test]
+fn two_states_with_prelude_only() {
+    let machines: StateMachines<Noop<()>, Always<()>> = machine().build();
+
+    let mut nested = NestedMachine::new_active();
+    assert_eq!(nested.current_state_name(&machines), Some("Idle"));
+    let complete = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(complete, Complete::Running);
+    assert_eq!(nested.current_state_name(&machines), Some("Done"));
+    let complete = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(complete, Complete::Done);
+}
+
+#[test]
+fn always_round_trips_to_builder() {
+    let machines: StateMachines<Noop<()>, Always<()>> = machine().build();
+
+    let builder = builder::StateMachines::<Noop<()>, builder::Target>::try_from(&machines);
+    let states = &builder.unwrap().0[0].states;
+    assert!(matches!(&states[0].transitions[..], [builder::Target::Goto(s)] if s == "Done"));
+    assert!(matches!(&states[1].transitions[..], [builder::Target::End]));
+}
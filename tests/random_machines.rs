@@ -0,0 +1,141 @@
+//! Deterministic property test: generate random valid state machines and run
+//! them for many ticks, checking that `NestedMachine` never panics, never
+//! errors and that its stack stays bounded.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::{Complete, NestedMachine};
+use pure_hfsm::{Behavior, StateData, Target, Transition};
+
+const SEEDS: u64 = 200;
+const TICKS: u64 = 500;
+
+/// A small xorshift PRNG, so that failures are reproducible from the seed
+struct Rng(u64);
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    fn below(&mut self, max: u64) -> u64 {
+        self.next() % max
+    }
+}
+
+/// The current tick and how many behaviors ran so far
+struct Ticks {
+    tick: u64,
+    updates: u64,
+}
+
+/// Counts how many times it ran
+struct Count;
+impl Behavior for Count {
+    type Context<'w, 's> = Ticks;
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, ctx: &mut Ticks) {
+        ctx.updates += 1;
+    }
+}
+
+/// Fires `target` every `period` ticks
+struct Every {
+    period: u64,
+    target: Target,
+}
+impl Transition for Every {
+    type Context<'w, 's> = Ticks;
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, ctx: &Ticks) -> Target {
+        if ctx.tick.is_multiple_of(self.period) {
+            self.target.clone()
+        } else {
+            Target::Continue
+        }
+    }
+}
+
+struct SerialEvery {
+    period: u64,
+    target: builder::Target,
+}
+impl IntoTransition<Every> for SerialEvery {
+    fn into_with(self, mapping: &NameMapping) -> Every {
+        let target = mapping
+            .target(&self.target)
+            .expect("generated names are valid");
+        Every {
+            period: self.period,
+            target,
+        }
+    }
+}
+
+/// Generate a machine collection where every name reference is valid.
+///
+/// Machines only `Enter` machines with a greater index, so the nesting depth
+/// is bounded by the machine count.
+fn random_machines(rng: &mut Rng) -> builder::StateMachines<Count, SerialEvery> {
+    let machine_count = 1 + rng.below(5);
+    let mut machines = Vec::new();
+    for mi in 0..machine_count {
+        let state_count = 1 + rng.below(6);
+        let mut states = Vec::new();
+        for si in 0..state_count {
+            let mut transitions = Vec::new();
+            for _ in 0..rng.below(4) {
+                let target = match rng.below(3) {
+                    0 => builder::Target::Goto(format!("m{mi}s{}", rng.below(state_count))),
+                    1 if mi + 1 < machine_count => {
+                        let entered = mi + 1 + rng.below(machine_count - mi - 1);
+                        builder::Target::Enter(format!("m{entered}"))
+                    }
+                    _ => builder::Target::End,
+                };
+                let period = 1 + rng.below(7);
+                transitions.push(SerialEvery { period, target });
+            }
+            states.push(builder::State::new(
+                format!("m{mi}s{si}"),
+                Count,
+                transitions,
+            ));
+        }
+        machines.push(builder::StateMachine::new(format!("m{mi}"), states));
+    }
+    builder::StateMachines(machines)
+}
+
+#[test]
+fn random_machines_run_without_errors() {
+    for seed in 0..SEEDS {
+        let mut rng = Rng::new(seed);
+        let description = random_machines(&mut rng);
+        let machine_count = description.0.len();
+        let machines = description.build::<Every>();
+
+        let mut nested = NestedMachine::new_active();
+        let mut ctx = Ticks {
+            tick: 0,
+            updates: 0,
+        };
+        for tick in 0..TICKS {
+            ctx.tick = tick;
+            let result = nested.update(&machines, &mut ctx);
+            let complete = result.unwrap_or_else(|err| panic!("seed {seed}, tick {tick}: {err:?}"));
+            assert!(
+                nested.stack_len() <= machine_count,
+                "seed {seed}, tick {tick}: stack grew to {}",
+                nested.stack_len(),
+            );
+            if let Complete::Done = complete {
+                assert_eq!(nested.stack_len(), 0, "seed {seed}: Done with frames left");
+                nested = NestedMachine::new_active();
+            }
+        }
+        assert_eq!(ctx.updates, TICKS, "seed {seed}: a behavior was skipped");
+    }
+}
@@ -0,0 +1,124 @@
+//! Machines with parallel regions, each with its own active state.
+use pure_hfsm::builder;
+use pure_hfsm::label::{Complete, NestedMachine};
+use pure_hfsm::transition::AfterTicks;
+use pure_hfsm::{Behavior, StateData, StateMachines};
+
+type Log = Vec<String>;
+
+/// Logs its updates and the results it gets, returns its name as result
+struct Named(&'static str);
+impl Behavior for Named {
+    type Context<'w, 's> = Log;
+
+    fn update(&self, _: &mut StateData, _: &mut StateData, log: &mut Log) {
+        log.push(self.0.to_owned());
+    }
+    fn result(&self, _: &mut StateData, _: &mut StateData) -> Option<StateData> {
+        Some(Box::new(self.0))
+    }
+    fn on_result(&self, _: &mut StateData, _: &mut StateData, result: StateData, log: &mut Log) {
+        let from = result.downcast_ref::<&str>().unwrap();
+        log.push(format!("{} got {from}", self.0));
+    }
+}
+
+type Description = builder::StateMachine<Named, (u32, builder::Target)>;
+type Machines = StateMachines<Named, AfterTicks<Log>>;
+
+/// A state of `region`, firing the target of `fire` on its nth update
+fn state(
+    name: &'static str,
+    region: u8,
+    fire: Option<(u32, builder::Target)>,
+) -> builder::State<Named, (u32, builder::Target)> {
+    builder::State {
+        region,
+        ..builder::State::new(name, Named(name), fire.into_iter().collect())
+    }
+}
+
+/// Update `nested` once, returns what was logged during the update
+fn tick(nested: &mut NestedMachine, machines: &Machines) -> (Complete, Log) {
+    let mut log = Vec::new();
+    let complete = nested.update(machines, &mut log).unwrap();
+    (complete, log)
+}
+
+fn build(machines: Vec<Description>) -> Machines {
+    builder::StateMachines(machines).build()
+}
+
+#[test]
+fn goto_only_changes_its_region() {
+    let goto = builder::Target::Goto("A2".to_owned());
+    let machines = build(vec![builder::StateMachine::new(
+        "Machine",
+        vec![
+            state("A1", 0, Some((1, goto))),
+            state("A2", 0, None),
+            state("B", 1, None),
+        ],
+    )]);
+
+    let mut nested = NestedMachine::new_active();
+    let (complete, log) = tick(&mut nested, &machines);
+    assert_eq!(complete, Complete::Running);
+    assert_eq!(log, ["A1", "B"]);
+    let (complete, log) = tick(&mut nested, &machines);
+    assert_eq!(complete, Complete::Running);
+    assert_eq!(log, ["A2", "B"]);
+}
+
+#[test]
+fn machine_completes_with_its_last_region() {
+    let end = |ticks| Some((ticks, builder::Target::End));
+    let machines = build(vec![builder::StateMachine::new(
+        "Machine",
+        vec![state("A", 0, end(1)), state("B", 1, end(3))],
+    )]);
+
+    let mut nested = NestedMachine::new_active();
+    let (complete, log) = tick(&mut nested, &machines);
+    assert_eq!(complete, Complete::Running);
+    assert_eq!(log, ["A", "B"]);
+    let (complete, log) = tick(&mut nested, &machines);
+    assert_eq!(complete, Complete::Running);
+    assert_eq!(log, ["B"]);
+    let (complete, log) = tick(&mut nested, &machines);
+    assert_eq!(complete, Complete::Done);
+    assert_eq!(log, ["B"]);
+}
+
+#[test]
+fn enter_suspends_later_regions() {
+    let enter_once = builder::Target::EnterOnce("Child".to_owned());
+    let machines = build(vec![
+        builder::StateMachine::new(
+            "Machine",
+            vec![
+                state("A", 0, None),
+                state("B", 1, Some((1, enter_once))),
+                state("C", 2, None),
+            ],
+        ),
+        builder::StateMachine::new(
+            "Child",
+            vec![state("X", 0, Some((2, builder::Target::End)))],
+        ),
+    ]);
+
+    let mut nested = NestedMachine::new_active();
+    // `C` is not updated on the tick `B` enters `Child`
+    let (_, log) = tick(&mut nested, &machines);
+    assert_eq!(log, ["A", "B"]);
+    assert_eq!(nested.stack_len(), 2);
+    for _ in 0..2 {
+        let (_, log) = tick(&mut nested, &machines);
+        assert_eq!(log, ["X"]);
+    }
+    assert_eq!(nested.stack_len(), 1);
+    // All regions run again, the result goes to `B`, which entered `Child`
+    let (_, log) = tick(&mut nested, &machines);
+    assert_eq!(log, ["A", "B got X", "B", "C"]);
+}
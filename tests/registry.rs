@@ -0,0 +1,85 @@
+//! `build_with_registry` looks up behaviors and transitions by id.
+use std::collections::HashMap;
+use std::time::Duration;
+
+use pure_hfsm::builder::{self, UnknownId};
+use pure_hfsm::prelude::*;
+
+type Description = builder::StateMachines<String, String>;
+type Machines = StateMachines<Noop<()>, Always<()>>;
+
+/// A machine with a single state, using the `idle` behavior and the `stay`
+/// transition
+fn description() -> Description {
+    let state = builder::State::new("Idle", "idle".to_owned(), vec!["stay".to_owned()]);
+    builder::StateMachines(vec![builder::StateMachine::new("Machine", vec![state])])
+}
+
+fn build(description: Description) -> Result<Machines, UnknownId> {
+    let behaviors = HashMap::from([("idle".to_owned(), Noop::new())]);
+    let stay = builder::Target::Goto("Idle".to_owned());
+    let transitions = HashMap::from([("stay".to_owned(), stay)]);
+    description.build_with_registry(&behaviors, &transitions)
+}
+
+#[test]
+fn known_ids_build() {
+    let mut description = description();
+    let machine = &mut description.0[0];
+    machine.states[0].default = Some("stay".to_owned());
+    machine.on_complete = Some("idle".to_owned());
+
+    let Ok(machines) = build(description) else {
+        panic!("all ids are registered")
+    };
+    let machine = machines.machine_handle("Machine").unwrap();
+    assert!(machines.state_handle(&machine, "Idle").is_some());
+}
+
+#[test]
+fn unknown_state_behavior() {
+    let mut description = description();
+    description.0[0].states[0].behavior = "walk".to_owned();
+
+    let error = build(description).err().unwrap();
+    assert!(matches!(error, UnknownId::Behavior(id) if id == "walk"));
+}
+
+#[test]
+fn unknown_on_complete() {
+    let mut description = description();
+    description.0[0].on_complete = Some("cheer".to_owned());
+
+    let error = build(description).err().unwrap();
+    assert!(matches!(error, UnknownId::Behavior(id) if id == "cheer"));
+}
+
+#[test]
+fn unknown_transition() {
+    let mut description = description();
+    description.0[0].states[0]
+        .transitions
+        .push("leave".to_owned());
+
+    let error = build(description).err().unwrap();
+    assert!(matches!(error, UnknownId::Transition(id) if id == "leave"));
+}
+
+#[test]
+fn unknown_default() {
+    let mut description = description();
+    description.0[0].states[0].default = Some("fallback".to_owned());
+
+    let error = build(description).err().unwrap();
+    assert!(matches!(error, UnknownId::Transition(id) if id == "fallback"));
+}
+
+#[test]
+fn unknown_timeout_target() {
+    let mut description = description();
+    let flee = builder::Target::Goto("Flee".to_owned());
+    description.0[0].states[0].timeout = Some((Duration::from_secs(1), flee));
+
+    let error = build(description).err().unwrap();
+    assert!(matches!(error, UnknownId::Timeout(target) if target.state == "Idle"));
+}
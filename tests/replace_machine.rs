@@ -0,0 +1,72 @@
+//! `replace_machine` swaps a machine's definition, keeping its handle.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::{Behavior, Error, SmHandle, StateData, Target, Transition};
+
+struct Idle;
+impl Behavior for Idle {
+    type Context<'w, 's> = ();
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &mut ()) {}
+}
+
+struct Always(Target);
+impl Transition for Always {
+    type Context<'w, 's> = ();
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        self.0.clone()
+    }
+}
+impl IntoTransition<Always> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> Always {
+        Always(mapping.target(&self).expect("valid name"))
+    }
+}
+
+fn machine(
+    name: &str,
+    states: &[(&str, builder::Target)],
+) -> builder::StateMachine<Idle, builder::Target> {
+    let state = |(name, transition): &(&str, builder::Target)| {
+        builder::State::new(*name, Idle, vec![transition.clone()])
+    };
+    builder::StateMachine::new(name, states.iter().map(state).collect())
+}
+
+#[test]
+fn replaced_machine_is_entered_by_handle() {
+    let enter = |name: &str| builder::Target::Enter(name.to_owned());
+    let description = builder::StateMachines(vec![
+        machine("Root", &[("root", enter("Child"))]),
+        machine("Child", &[("child", builder::Target::End)]),
+    ]);
+    let mut machines = description.build::<Always>();
+    let child = machines.machine_handle("Child").unwrap();
+
+    let goto = |name: &str| builder::Target::Goto(name.to_owned());
+    let new_child = machine("NewChild", &[("x", goto("y")), ("y", enter("Root"))]);
+    machines.replace_machine(&child, new_child).unwrap();
+    assert_eq!(machines.machine_handle("NewChild"), Some(child));
+    assert!(!machines.contains_machine("Child"));
+
+    let mut nested = NestedMachine::new_active();
+    let mut visited = Vec::new();
+    for _ in 0..3 {
+        let _ = nested.update(&machines, &mut ()).unwrap();
+        let machine = nested.current_machine_name(&machines).unwrap();
+        let state = nested.current_state_name(&machines).unwrap();
+        visited.push(format!("{machine}.{state}"));
+    }
+    assert_eq!(visited, ["NewChild.x", "NewChild.y", "Root.root"]);
+    assert_eq!(nested.stack_len(), 3);
+}
+
+#[test]
+fn replace_missing_machine() {
+    let description = builder::StateMachines(vec![machine("Root", &[])]);
+    let mut machines = description.build::<Always>();
+
+    let result = machines.replace_machine(&SmHandle::new(3), machine("Other", &[]));
+    assert!(matches!(result, Err(Error::BadMachineName(handle)) if handle.get() == 3));
+}
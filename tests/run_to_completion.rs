@@ -0,0 +1,82 @@
+//! Running machines to completion, `run_to_completion` errors on machines
+//! that never complete.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::{Behavior, Error, StateData, Target, Transition};
+
+struct Idle;
+impl Behavior for Idle {
+    type Context<'w, 's> = ();
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &mut ()) {}
+}
+
+struct Always(Target);
+impl Transition for Always {
+    type Context<'w, 's> = ();
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        self.0.clone()
+    }
+}
+impl IntoTransition<Always> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> Always {
+        Always(mapping.target(&self).expect("valid name"))
+    }
+}
+
+/// A machine with states `a` and `b`, `a` goes to `b` and `b` does `b_does`
+fn machine(b_does: builder::Target) -> builder::StateMachines<Idle, builder::Target> {
+    let state = |name: &str, transition| builder::State::new(name, Idle, vec![transition]);
+    builder::StateMachines(vec![builder::StateMachine::new(
+        "Cutscene",
+        vec![
+            state("a", builder::Target::Goto("b".to_owned())),
+            state("b", b_does),
+        ],
+    )])
+}
+
+#[test]
+fn never_completing_machine_errors() {
+    let machines = machine(builder::Target::Goto("a".to_owned())).build::<Always>();
+
+    let mut nested = NestedMachine::new_active();
+    let result = nested.run_to_completion(&machines, &mut (), 10);
+    assert!(matches!(result, Err(Error::DidNotComplete { ticks: 10 })));
+}
+
+#[test]
+fn completing_machine_runs_to_completion() {
+    let machines = machine(builder::Target::End).build::<Always>();
+
+    let mut nested = NestedMachine::new_active();
+    nested.run_to_completion(&machines, &mut (), 2).unwrap();
+    assert_eq!(nested.stack_len(), 0);
+}
+
+#[test]
+fn lifecycle_predicates() {
+    let machines = machine(builder::Target::End).build::<Always>();
+
+    let mut nested = NestedMachine::new();
+    assert!(!nested.is_active() && !nested.is_complete());
+
+    nested.enter(&machines.machine_handle("Cutscene").unwrap());
+    assert!(nested.is_active() && !nested.is_complete());
+
+    nested.run_to_completion(&machines, &mut (), 2).unwrap();
+    assert!(!nested.is_active() && nested.is_complete());
+
+    nested.enter(&machines.machine_handle("Cutscene").unwrap());
+    assert!(nested.is_active() && !nested.is_complete());
+}
+
+#[test]
+fn inactive_machine_does_not_complete() {
+    let machines = machine(builder::Target::End).build::<Always>();
+
+    let mut nested = NestedMachine::new();
+    let result = nested.update_until_complete(&machines, &mut (), 10);
+    assert!(matches!(result, Err(Error::DidNotComplete { ticks: 0 })));
+}
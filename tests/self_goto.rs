@@ -0,0 +1,47 @@
+//! A `Goto` to the current state re-enters it, not transitioning keeps it.
+use pure_hfsm::builder;
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::transition::Always;
+use pure_hfsm::{Behavior, StateData, StateMachines};
+
+/// Logs whether its data is fresh, then replaces it
+struct Check;
+impl Behavior for Check {
+    type Context<'w, 's> = Vec<&'static str>;
+
+    fn update(&self, data: &mut StateData, _: &mut StateData, log: &mut Vec<&'static str>) {
+        log.push(match data.is::<()>() {
+            true => "fresh",
+            false => "kept",
+        });
+        *data = Box::new(1_u32);
+    }
+    fn on_enter(&self, _: &mut StateData, _: &mut StateData, log: &mut Vec<&'static str>) {
+        log.push("enter");
+    }
+}
+
+fn log_of(transitions: Vec<builder::Target>) -> Vec<&'static str> {
+    let state = builder::State::new("Loop", Check, transitions);
+    let machines: StateMachines<Check, Always<_>> =
+        builder::StateMachines(vec![builder::StateMachine::new("Machine", vec![state])]).build();
+
+    let mut nested = NestedMachine::new_active();
+    let mut log = Vec::new();
+    for _ in 0..3 {
+        let _ = nested.update(&machines, &mut log).unwrap();
+    }
+    log
+}
+
+#[test]
+fn goto_self_resets_data_and_enters_again() {
+    let log = log_of(vec![builder::Target::Goto("Loop".to_owned())]);
+    assert_eq!(log, ["enter", "fresh", "enter", "fresh", "enter", "fresh"]);
+}
+
+#[test]
+fn continue_keeps_data() {
+    let log = log_of(vec![]);
+    assert_eq!(log, ["enter", "fresh", "kept", "kept"]);
+}
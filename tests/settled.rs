@@ -0,0 +1,71 @@
+//! `update_settled` follows `Goto`s through transient states in one tick.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::{Complete, NestedMachine};
+use pure_hfsm::{Behavior, Error, StateData, Target, Transition};
+
+/// Records the name of the state each time it runs
+struct Log(&'static str);
+impl Behavior for Log {
+    type Context<'w, 's> = Vec<&'static str>;
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, ran: &mut Vec<&'static str>) {
+        ran.push(self.0);
+    }
+}
+
+struct Always(Target);
+impl Transition for Always {
+    type Context<'w, 's> = Vec<&'static str>;
+
+    fn decide<'w, 's>(
+        &self,
+        _: &mut StateData,
+        _: &mut StateData,
+        _: &Vec<&'static str>,
+    ) -> Target {
+        self.0.clone()
+    }
+}
+impl IntoTransition<Always> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> Always {
+        Always(mapping.target(&self).expect("valid name"))
+    }
+}
+
+fn machine(
+    states: &[(&'static str, Option<&str>)],
+) -> builder::StateMachines<Log, builder::Target> {
+    let state = |&(name, goto): &(&'static str, Option<&str>)| {
+        let goto = goto.map(|goto| builder::Target::Goto(goto.to_owned()));
+        builder::State::new(name, Log(name), goto.into_iter().collect())
+    };
+    builder::StateMachines(vec![builder::StateMachine::new(
+        "Machine",
+        states.iter().map(state).collect(),
+    )])
+}
+
+#[test]
+fn transient_states_settle_in_one_tick() {
+    let machines = machine(&[("A", Some("B")), ("B", Some("C")), ("C", None)]).build::<Always>();
+
+    let mut nested = NestedMachine::new_active();
+    let mut ran = Vec::new();
+    let complete = nested.update_settled(&machines, &mut ran, 4).unwrap();
+    assert_eq!(complete, Complete::Running);
+    assert_eq!(ran, ["A", "B", "C"]);
+    assert_eq!(nested.current_state_name(&machines), Some("C"));
+
+    ran.clear();
+    let _ = nested.update_settled(&machines, &mut ran, 4).unwrap();
+    assert_eq!(ran, ["C"]);
+}
+
+#[test]
+fn goto_cycle_does_not_settle() {
+    let machines = machine(&[("A", Some("B")), ("B", Some("A"))]).build::<Always>();
+
+    let mut nested = NestedMachine::new_active();
+    let result = nested.update_settled(&machines, &mut Vec::new(), 3);
+    assert!(matches!(result, Err(Error::DidNotSettle { hops: 3, .. })));
+}
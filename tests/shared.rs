@@ -0,0 +1,55 @@
+//! One `StateMachines` run by `NestedMachine`s on several threads.
+use pure_hfsm::prelude::*;
+use pure_hfsm::shared::SharedStateMachines;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+/// A machine counting to `3` in its context, then completing
+fn machine() -> builder::StateMachines<Noop<u32>, builder::Target> {
+    let state = |name: &str, target| builder::State::new(name, Noop::new(), vec![target]);
+    let goto = |name: &str| builder::Target::Goto(name.to_owned());
+    builder::StateMachines(vec![builder::StateMachine::new(
+        "Count",
+        vec![
+            state("One", goto("Two")),
+            state("Two", goto("Three")),
+            state("Three", builder::Target::End),
+        ],
+    )])
+}
+
+#[test]
+fn descriptions_and_instances_are_send_sync() {
+    assert_send_sync::<StateMachines<Noop<u32>, Always<u32>>>();
+    assert_send_sync::<SharedStateMachines<Noop<u32>, Always<u32>>>();
+    assert_send_sync::<NestedMachine>();
+}
+
+#[test]
+fn many_threads_share_one_description() {
+    let machines: StateMachines<Noop<u32>, Always<u32>> = machine().build();
+    let shared = SharedStateMachines::new(machines);
+
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                let mut nested = NestedMachine::new_active();
+                let mut updates = 0;
+                while shared.update(&mut nested, &mut updates).unwrap() == Complete::Running {
+                    updates += 1;
+                }
+                (updates, nested.is_complete())
+            })
+        })
+        .collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), (2, true));
+    }
+    let clone = shared.clone();
+    assert!(clone.ptr_eq(&shared));
+    assert_eq!(
+        clone.machine_handle("Count"),
+        shared.machine_handle("Count")
+    );
+}
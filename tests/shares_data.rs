@@ -0,0 +1,82 @@
+//! A behavior with `shares_data` gives its data to its state's transitions.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::{Behavior, StateData, StateMachines, Target, Transition};
+
+/// Counts its updates in its data, shared with the transitions
+///
+/// When `even_exit`, the state can only be left after an even count.
+struct Hits {
+    even_exit: bool,
+}
+impl Behavior for Hits {
+    type Context<'w, 's> = ();
+
+    fn default_data(&self) -> StateData {
+        Box::new(0_u32)
+    }
+    fn update(&self, data: &mut StateData, _: &mut StateData, _: &mut ()) {
+        *data.downcast_mut::<u32>().unwrap() += 1;
+    }
+    fn shares_data(&self) -> bool {
+        true
+    }
+    fn can_exit(&self, data: &StateData, _: &()) -> bool {
+        !self.even_exit || data.downcast_ref::<u32>().unwrap() % 2 == 0
+    }
+}
+
+/// Fires once the count of the behavior is at least the given number
+struct AtLeast(u32, Target);
+impl Transition for AtLeast {
+    type Context<'w, 's> = ();
+
+    fn decide(&self, data: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        // Panics if the transition got its own `Box<()>` data instead
+        let hits = *data.downcast_ref::<u32>().unwrap();
+        if hits >= self.0 {
+            self.1.clone()
+        } else {
+            Target::Continue
+        }
+    }
+}
+impl IntoTransition<AtLeast> for (u32, builder::Target) {
+    fn into_with(self, mapping: &NameMapping) -> AtLeast {
+        AtLeast(self.0, mapping.target(&self.1).expect("valid name"))
+    }
+}
+
+/// `Hitting` going to `Done` after 3 hits
+fn machines(even_exit: bool) -> StateMachines<Hits, AtLeast> {
+    let done = (3, builder::Target::Goto("Done".to_owned()));
+    let states = vec![
+        builder::State::new("Hitting", Hits { even_exit }, vec![done]),
+        builder::State::new("Done", Hits { even_exit }, vec![]),
+    ];
+    builder::StateMachines(vec![builder::StateMachine::new("Machine", states)]).build()
+}
+
+/// The name of the state after each of `updates` updates
+fn states(even_exit: bool, updates: usize) -> Vec<String> {
+    let machines = machines(even_exit);
+    let mut nested = NestedMachine::new_active();
+    let mut update = || {
+        let _ = nested.update(&machines, &mut ()).unwrap();
+        nested.current_state_name(&machines).unwrap().to_owned()
+    };
+    (0..updates).map(|_| update()).collect()
+}
+
+#[test]
+fn transition_reads_behavior_data() {
+    let expected = ["Hitting", "Hitting", "Done", "Done"];
+    assert_eq!(states(false, 4), expected);
+}
+
+#[test]
+fn can_exit_sees_shared_data() {
+    // The transition fires at 3 hits, but can only exit at 4
+    let expected = ["Hitting", "Hitting", "Hitting", "Done"];
+    assert_eq!(states(true, 4), expected);
+}
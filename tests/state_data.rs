@@ -0,0 +1,24 @@
+//! `state_data` initializes and downcasts a `StateData`.
+use pure_hfsm::{state_data, StateData};
+
+#[test]
+fn fresh_data_is_initialized() {
+    let mut data: StateData = Box::new(());
+    assert_eq!(*state_data::<u32>(&mut data), 0);
+    assert!(data.is::<u32>());
+}
+
+#[test]
+fn initialized_data_is_kept() {
+    let mut data: StateData = Box::new(());
+    *state_data::<Vec<u8>>(&mut data) = vec![1, 2];
+    state_data::<Vec<u8>>(&mut data).push(3);
+    assert_eq!(data.downcast_ref::<Vec<u8>>(), Some(&vec![1, 2, 3]));
+}
+
+#[test]
+#[should_panic(expected = "StateData is not a u32")]
+fn other_data_panics() {
+    let mut data: StateData = Box::new("text");
+    state_data::<u32>(&mut data);
+}
@@ -0,0 +1,51 @@
+//! Looking up states by `StateId`, which keeps their machine with them.
+use pure_hfsm::prelude::*;
+use pure_hfsm::{SHandle, StateId};
+
+/// Two machines with a state named `Idle` at different handles
+fn machines() -> StateMachines<Noop<()>, Always<()>> {
+    let state = |name: &str| builder::State::<_, builder::Target>::new(name, Noop::new(), vec![]);
+    let machine = |name: &str, states: &[&str]| {
+        builder::StateMachine::new(name, states.iter().map(|s| state(s)).collect())
+    };
+    let machines = builder::StateMachines(vec![
+        machine("Guard", &["Idle", "Alert"]),
+        machine("Thief", &["Sneak", "Steal", "Idle"]),
+    ]);
+    machines.build()
+}
+
+#[test]
+fn ids_resolve_to_their_own_machine() {
+    let machines = machines();
+
+    let guard_idle = machines.state_id("Guard", "Idle").unwrap();
+    let thief_idle = machines.state_id("Thief", "Idle").unwrap();
+    assert_ne!(guard_idle, thief_idle);
+    assert_eq!(machines.resolve(&guard_idle), Some(("Guard", "Idle")));
+    assert_eq!(machines.resolve(&thief_idle), Some(("Thief", "Idle")));
+
+    let thief = machines.machine_handle("Thief").unwrap();
+    assert_eq!(machines.state_handle(&thief, "Idle"), Some(thief_idle));
+    assert_eq!(machines.state_id("Guard", "Steal"), None);
+}
+
+#[test]
+fn out_of_bounds_ids_do_not_resolve() {
+    let machines = machines();
+
+    let guard = machines.machine_handle("Guard").unwrap();
+    let missing = StateId::new(guard, SHandle::new(2));
+    assert_eq!(machines.resolve(&missing), None);
+}
+
+#[test]
+fn current_state_id() {
+    let machines = machines();
+
+    let mut nested = NestedMachine::new();
+    assert_eq!(nested.current_state_id(), None);
+    nested.enter(&machines.machine_handle("Thief").unwrap());
+    let id = nested.current_state_id().unwrap();
+    assert_eq!(machines.resolve(&id), Some(("Thief", "Sneak")));
+}
@@ -0,0 +1,131 @@
+//! Stateless transitions listed before stateful ones, evaluated in a tight loop.
+use pure_hfsm::prelude::*;
+
+/// Whether the enemy should flee, and whether it should fight
+type Orders = (bool, bool);
+
+enum Check {
+    /// Stateless, fires when the order is given
+    Order(fn(&Orders) -> bool, Target),
+    /// Stateful, fires on its nth evaluation
+    Nth(u32, Target),
+}
+impl Transition for Check {
+    type Context<'w, 's> = Orders;
+
+    fn decide(&self, data: &mut StateData, _: &mut StateData, orders: &Orders) -> Target {
+        match self {
+            Check::Order(given, target) if given(orders) => target.clone(),
+            Check::Order(..) => Target::Continue,
+            Check::Nth(n, target) => {
+                if !data.is::<u32>() {
+                    *data = Box::new(0_u32);
+                }
+                let evaluations = data.downcast_mut::<u32>().unwrap();
+                *evaluations += 1;
+                match *evaluations == *n {
+                    true => target.clone(),
+                    false => Target::Continue,
+                }
+            }
+        }
+    }
+    fn needs_data(&self) -> bool {
+        matches!(self, Check::Nth(..))
+    }
+}
+impl IntoTransition<Check> for (Option<fn(&Orders) -> bool>, builder::Target) {
+    fn into_with(self, mapping: &NameMapping) -> Check {
+        let target = mapping.target(&self.1).expect("valid name");
+        match self.0 {
+            Some(given) => Check::Order(given, target),
+            None => Check::Nth(3, target),
+        }
+    }
+}
+
+/// `Idle` flees or fights when ordered to, or gets tired after 3 updates
+fn machines() -> StateMachines<Noop<Orders>, Check> {
+    let goto = |name: &str| builder::Target::Goto(name.to_owned());
+    let flee: fn(&Orders) -> bool = |orders| orders.0;
+    let fight: fn(&Orders) -> bool = |orders| orders.1;
+    let transitions = vec![
+        (Some(flee), goto("Flee")),
+        (None, goto("Tired")),
+        (Some(fight), goto("Fight")),
+    ];
+    let state = |name: &str, transitions| builder::State::new(name, Noop::new(), transitions);
+    let states = vec![
+        state("Idle", transitions),
+        state("Flee", vec![]),
+        state("Tired", vec![]),
+        state("Fight", vec![]),
+    ];
+    builder::StateMachines(vec![builder::StateMachine::new("Enemy", states)]).build()
+}
+
+/// The state after each update with `orders`
+fn states(orders: Orders, updates: usize) -> Vec<String> {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+    let mut update = |mut orders| {
+        let _ = nested.update(&machines, &mut orders).unwrap();
+        nested.current_state_name(&machines).unwrap().to_owned()
+    };
+    (0..updates).map(|_| update(orders)).collect()
+}
+
+#[test]
+fn leading_stateless_fires_first() {
+    assert_eq!(states((true, true), 1), ["Flee"]);
+}
+
+#[test]
+fn stateful_after_stateless_keeps_its_data() {
+    assert_eq!(states((false, false), 3), ["Idle", "Idle", "Tired"]);
+}
+
+#[test]
+fn stateless_after_stateful_fires() {
+    assert_eq!(states((false, true), 1), ["Fight"]);
+}
+
+#[test]
+fn disabled_stateless_is_skipped() {
+    let machines = machines();
+    let enemy = machines.machine_handle("Enemy").unwrap();
+    let idle = machines.state_handle(&enemy, "Idle").unwrap().state;
+    let mut nested = NestedMachine::new_active();
+    nested.set_transition_enabled(&enemy, &idle, 0, false);
+
+    let _ = nested.update(&machines, &mut (true, true)).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Fight"));
+}
+
+#[test]
+fn replaced_machine_counts_its_own_stateless_transitions() {
+    let mut machines = machines();
+    let mut nested = NestedMachine::new_active();
+    let _ = nested.update(&machines, &mut (false, false)).unwrap();
+
+    // The stateful transition is now first, and keeps its count
+    let goto = |name: &str| builder::Target::Goto(name.to_owned());
+    let fight: fn(&Orders) -> bool = |orders| orders.1;
+    let transitions = vec![(None, goto("Tired")), (Some(fight), goto("Fight"))];
+    let state = |name: &str, transitions| builder::State::new(name, Noop::new(), transitions);
+    let states = vec![
+        state("Idle", transitions),
+        state("Flee", vec![]),
+        state("Tired", vec![]),
+        state("Fight", vec![]),
+    ];
+    let enemy = machines.machine_handle("Enemy").unwrap();
+    let new = builder::StateMachine::new("Enemy", states);
+    machines.replace_machine(&enemy, new).unwrap();
+
+    let mut update = || {
+        let _ = nested.update(&machines, &mut (false, false)).unwrap();
+        nested.current_state_name(&machines).unwrap().to_owned()
+    };
+    assert_eq!([update(), update()], ["Idle", "Tired"]);
+}
@@ -0,0 +1,77 @@
+//! `NestedMachine::with_stuck_threshold` catches machines that stopped moving.
+use pure_hfsm::builder;
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::prelude::Noop;
+use pure_hfsm::transition::AfterTicks;
+use pure_hfsm::{Error, StateMachines};
+
+type Machines = StateMachines<Noop<()>, AfterTicks<()>>;
+
+/// A state of `region`, firing the target of `fire` on its nth update
+fn state(
+    name: &str,
+    region: u8,
+    fire: Option<(u32, builder::Target)>,
+) -> builder::State<Noop<()>, (u32, builder::Target)> {
+    builder::State {
+        region,
+        ..builder::State::new(name, Noop::new(), fire.into_iter().collect())
+    }
+}
+
+fn build(states: Vec<builder::State<Noop<()>, (u32, builder::Target)>>) -> Machines {
+    builder::StateMachines(vec![builder::StateMachine::new("Machine", states)]).build()
+}
+
+/// Whether each of `updates` updates of `nested` returned `Error::Stuck`
+fn stuck(nested: &mut NestedMachine, machines: &Machines, updates: usize) -> Vec<bool> {
+    let mut update = || match nested.update(machines, &mut ()) {
+        Ok(_) => false,
+        Err(Error::Stuck { .. }) => true,
+        Err(error) => panic!("unexpected error: {error:?}"),
+    };
+    (0..updates).map(|_| update()).collect()
+}
+
+#[test]
+fn staying_past_threshold_is_stuck() {
+    let machines = build(vec![state("Idle", 0, None)]);
+    let mut nested = NestedMachine::new_active().with_stuck_threshold(2);
+
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    let error = nested.update(&machines, &mut ()).unwrap_err();
+    let machine = machines.machine_handle("Machine").unwrap();
+    let idle = machines.state_handle(&machine, "Idle").unwrap().state;
+    assert!(matches!(
+        error,
+        Error::Stuck { machine: m, state, ticks: 3 } if m == machine && state == idle
+    ));
+    // The count starts over
+    assert_eq!(stuck(&mut nested, &machines, 3), [false, false, true]);
+}
+
+#[test]
+fn goto_resets_the_count() {
+    let goto = |state: &str| Some((2, builder::Target::Goto(state.to_owned())));
+    let machines = build(vec![state("A", 0, goto("B")), state("B", 0, goto("A"))]);
+    let mut nested = NestedMachine::new_active().with_stuck_threshold(1);
+
+    assert_eq!(stuck(&mut nested, &machines, 10), [false; 10]);
+}
+
+#[test]
+fn region_completing_resets_the_count() {
+    let end = |ticks| Some((ticks, builder::Target::End));
+    let states = vec![
+        state("A", 0, None),
+        state("B", 1, end(2)),
+        state("C", 2, end(4)),
+    ];
+    let machines = build(states);
+    let mut nested = NestedMachine::new_active().with_stuck_threshold(1);
+
+    // B completes on the 2nd update, C on the 4th, then only A stays
+    let expected = [false, false, false, false, false, true];
+    assert_eq!(stuck(&mut nested, &machines, 6), expected);
+}
@@ -0,0 +1,48 @@
+//! The text form of `Target`s round-trips through `Target::from_log_str`.
+use pure_hfsm::{builder, SHandle, SmHandle, Target};
+
+#[test]
+fn target_text_round_trips() {
+    let targets = [
+        Target::Continue,
+        Target::Goto(SHandle::new(3)),
+        Target::Enter(SmHandle::new(1)),
+        Target::EnterOnce(SmHandle::new(260)),
+        Target::EnterAt(SmHandle::new(2), SHandle::new(4)),
+        Target::Complete,
+        Target::PopN(2),
+    ];
+    let texts: Vec<_> = targets.iter().map(Target::to_string).collect();
+    assert_eq!(
+        texts,
+        [
+            "continue",
+            "goto:3",
+            "enter:1",
+            "enter_once:260",
+            "enter_at:2:4",
+            "complete",
+            "pop_n:2"
+        ]
+    );
+    for (target, text) in targets.iter().zip(&texts) {
+        let parsed = Target::from_log_str(text).unwrap();
+        assert_eq!(parsed.to_string(), target.to_string());
+    }
+}
+
+#[test]
+fn unknown_text_is_not_a_target() {
+    for text in ["", "goto", "goto:", "goto:256", "complete:1", "jump:1"] {
+        assert!(Target::from_log_str(text).is_none(), "{text} parsed");
+    }
+}
+
+#[test]
+fn builder_target_text_has_names() {
+    let goto = builder::Target::Goto("Idle".to_owned());
+    assert_eq!(goto.to_string(), "goto:Idle");
+    let enter_at = builder::Target::EnterAt("Combat".to_owned(), "Flee".to_owned());
+    assert_eq!(enter_at.to_string(), "enter_at:Combat:Flee");
+    assert_eq!(builder::Target::End.to_string(), "complete");
+}
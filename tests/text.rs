@@ -0,0 +1,147 @@
+//! Parsing the indentation-based text format, and inspecting what it built.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::text::{parse, ErrorKind, ParseError};
+use pure_hfsm::{Behavior, StateData, Target, Transition};
+
+struct Idle;
+impl Behavior for Idle {
+    type Context<'w, 's> = ();
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &mut ()) {}
+}
+
+/// A transition that always fires, or never if its condition is `Never`
+struct Always(Option<Target>);
+impl Transition for Always {
+    type Context<'w, 's> = ();
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        self.0.clone().unwrap_or(Target::Continue)
+    }
+}
+struct Desc(bool, builder::Target);
+impl IntoTransition<Always> for Desc {
+    fn into_with(self, mapping: &NameMapping) -> Always {
+        Always(self.0.then(|| mapping.target(&self.1).expect("valid name")))
+    }
+}
+
+fn parse_source(source: &str) -> Result<builder::StateMachines<Idle, Desc>, ParseError> {
+    let behavior = |name: &str| match name {
+        "Idle" => Ok(Idle),
+        _ => Err(format!("unknown behavior {name}")),
+    };
+    let transition = |condition: Option<&str>, target| match condition {
+        None => Ok(Desc(true, target)),
+        Some("Never") => Ok(Desc(false, target)),
+        Some(other) => Err(format!("unknown condition {other}")),
+    };
+    parse(source, behavior, transition)
+}
+
+const NESTED: &str = "
+# The root machine
+machine Patrol:
+  state Walk [Idle]:
+    -> enter Combat
+    -> Rest if Never
+  state Rest [Idle] region 1
+
+machine Combat:
+  state Fight [Idle]:
+    -> Flee
+  state Flee [Idle]:
+    -> end
+";
+
+#[test]
+fn nested_machines() {
+    let description = parse_source(NESTED).unwrap();
+    let names: Vec<_> = description.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, ["Patrol", "Combat"]);
+    let patrol = &description.0[0];
+    assert_eq!(patrol.states[0].transitions.len(), 2);
+    assert_eq!(patrol.states[1].region, 1);
+
+    let machines = description.build::<Always>();
+    let mut nested = NestedMachine::new_active();
+    let mut visited = Vec::new();
+    for _ in 0..3 {
+        let _ = nested.update(&machines, &mut ()).unwrap();
+        let machine = nested.current_machine_name(&machines).unwrap();
+        let state = nested.current_state_name(&machines).unwrap();
+        visited.push(format!("{machine}.{state}"));
+    }
+    assert_eq!(visited, ["Combat.Fight", "Combat.Flee", "Patrol.Walk"]);
+}
+
+fn error_of(source: &str) -> ParseError {
+    parse_source(source).err().expect("source is invalid")
+}
+
+#[test]
+fn errors_have_line_numbers() {
+    let misplaced = "machine A\n  state X [Idle]\n  -> X\n";
+    assert_eq!(error_of(misplaced).line, 3);
+    assert_eq!(error_of(misplaced).kind, ErrorKind::Misplaced);
+
+    let orphan_state = "\n  state X [Idle]\n";
+    assert_eq!(error_of(orphan_state).line, 2);
+    assert_eq!(error_of(orphan_state).kind, ErrorKind::Misplaced);
+
+    let no_behavior = "machine A\n  state X\n";
+    assert_eq!(error_of(no_behavior).line, 2);
+    assert!(matches!(error_of(no_behavior).kind, ErrorKind::Syntax(_)));
+
+    let bad_behavior = "machine A\n  state X [Walk]\n";
+    let error = error_of(bad_behavior);
+    assert_eq!(
+        error.to_string(),
+        "line 2: invalid behavior: unknown behavior Walk"
+    );
+
+    let bad_condition = "machine A\n  state X [Idle]\n    -> X if Maybe\n";
+    assert_eq!(error_of(bad_condition).line, 3);
+    assert!(matches!(
+        error_of(bad_condition).kind,
+        ErrorKind::Transition(_)
+    ));
+}
+
+#[test]
+fn unknown_names_are_reported_where_used() {
+    let unknown_state = "machine A\n  state X [Idle]\n    -> X\n    -> Y\n";
+    let error = error_of(unknown_state);
+    assert_eq!(error.line, 4);
+    assert_eq!(error.kind, ErrorKind::UnknownState("Y".to_owned()));
+
+    // Machines can be entered before they are defined
+    let forward = "machine A\n  state X [Idle]\n    -> enter B\nmachine B\n  state Y [Idle]\n";
+    assert!(parse_source(forward).is_ok());
+
+    let unknown_machine = "machine A\n  state X [Idle]\n    -> enter once C\n";
+    let error = error_of(unknown_machine);
+    assert_eq!(error.line, 3);
+    assert_eq!(error.kind, ErrorKind::UnknownMachine("C".to_owned()));
+
+    let enter_at =
+        "machine A\n  state X [Idle]\n    -> enter B at Z\nmachine B\n  state Y [Idle]\n";
+    let error = error_of(enter_at);
+    assert_eq!(error.line, 3);
+    assert_eq!(error.kind, ErrorKind::UnknownState("Z".to_owned()));
+    assert!(parse_source(&enter_at.replace("at Z", "at Y")).is_ok());
+}
+
+#[test]
+fn inspect_current_state() {
+    let machines = parse_source(NESTED).unwrap().build::<Always>();
+    let mut nested = NestedMachine::new();
+    assert!(nested.current_behavior(&machines).is_none());
+
+    nested.enter(&machines.machine_handle("Patrol").unwrap());
+    let transitions = nested.current_transitions(&machines).unwrap();
+    assert_eq!(transitions.len(), 2);
+    assert!(transitions[1].0.is_none());
+    assert!(nested.current_behavior(&machines).is_some());
+}
@@ -0,0 +1,105 @@
+//! States fire their timeout target after running for long enough.
+use std::time::Duration;
+
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::{Behavior, StateData, Target, Transition};
+
+/// Whether the player is visible
+type Sees = bool;
+
+struct Idle;
+impl Behavior for Idle {
+    type Context<'w, 's> = Sees;
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &mut Sees) {}
+}
+
+/// Fires when the player is visible
+struct Spotted(Target);
+impl Transition for Spotted {
+    type Context<'w, 's> = Sees;
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, sees: &Sees) -> Target {
+        match sees {
+            true => self.0.clone(),
+            false => Target::Continue,
+        }
+    }
+}
+impl IntoTransition<Spotted> for builder::Target {
+    fn into_with(self, mapping: &NameMapping) -> Spotted {
+        Spotted(mapping.target(&self).expect("valid name"))
+    }
+}
+
+/// `Search` gives up after a second, unless it spots the player
+fn machine() -> builder::StateMachines<Idle, builder::Target> {
+    let state = |name: &str, transitions, timeout| builder::State {
+        timeout,
+        ..builder::State::new(name, Idle, transitions)
+    };
+    let goto = |name: &str| builder::Target::Goto(name.to_owned());
+    builder::StateMachines(vec![builder::StateMachine::new(
+        "Guard",
+        vec![
+            state(
+                "Search",
+                vec![goto("Chase")],
+                Some((Duration::from_secs(1), goto("Patrol"))),
+            ),
+            state("Patrol", vec![], None),
+            state("Chase", vec![], None),
+        ],
+    )])
+}
+
+#[test]
+fn timeout_fires_after_enough_time() {
+    let machines = machine().build::<Spotted>();
+    let half_second = Duration::from_millis(500);
+
+    let mut nested = NestedMachine::new_active();
+    let _ = nested
+        .update_timed(&machines, &mut false, half_second)
+        .unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Search"));
+    let _ = nested
+        .update_timed(&machines, &mut false, half_second)
+        .unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Patrol"));
+}
+
+#[test]
+fn transitions_are_checked_before_timeout() {
+    let machines = machine().build::<Spotted>();
+
+    let mut nested = NestedMachine::new_active();
+    let _ = nested.update_timed(&machines, &mut true, Duration::from_secs(5));
+    assert_eq!(nested.current_state_name(&machines), Some("Chase"));
+}
+
+#[test]
+fn update_does_not_count_time() {
+    let machines = machine().build::<Spotted>();
+
+    let mut nested = NestedMachine::new_active();
+    for _ in 0..10 {
+        let _ = nested.update(&machines, &mut false).unwrap();
+    }
+    assert_eq!(nested.current_state_name(&machines), Some("Search"));
+}
+
+#[test]
+fn try_build_reports_unknown_timeout_target() {
+    let mut machines = machine();
+    let search = &mut machines.0[0].states[0];
+    search.timeout = Some((
+        Duration::from_secs(1),
+        builder::Target::Goto("Flee".to_owned()),
+    ));
+
+    let error = machines.try_build::<Spotted>().err().unwrap();
+    assert_eq!((&error.machine[..], &error.state[..]), ("Guard", "Search"));
+    assert_eq!(error.target.to_string(), "goto:Flee");
+}
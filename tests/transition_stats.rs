@@ -0,0 +1,70 @@
+//! With the `profiling` feature, `NestedMachine` counts fired transitions.
+use std::time::Duration;
+
+use pure_hfsm::builder;
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::prelude::Noop;
+use pure_hfsm::transition::AfterTicks;
+use pure_hfsm::StateMachines;
+
+type Machines = StateMachines<Noop<()>, AfterTicks<()>>;
+
+/// `A` goes to `B` with its transition, `B` to `C` with its timeout and
+/// `C` to `A` with its default transition
+fn machines() -> Machines {
+    let target = |state: &str| builder::Target::Goto(state.to_owned());
+    let never = || vec![(100, builder::Target::End)];
+    let states = vec![
+        builder::State::new("A", Noop::new(), vec![(2, target("B"))]),
+        builder::State {
+            timeout: Some((Duration::from_secs(1), target("C"))),
+            ..builder::State::new("B", Noop::new(), never())
+        },
+        builder::State {
+            default: Some((1, target("A"))),
+            ..builder::State::new("C", Noop::new(), never())
+        },
+    ];
+    builder::StateMachines(vec![builder::StateMachine::new("Machine", states)]).build()
+}
+
+#[test]
+fn counts_transitions_timeouts_and_defaults() {
+    let machines = machines();
+    let mut nested = NestedMachine::new_active();
+    for _ in 0..4 {
+        let second = Duration::from_secs(1);
+        let _ = nested.update_timed(&machines, &mut (), second).unwrap();
+    }
+    assert_eq!(nested.current_state_name(&machines), Some("A"));
+
+    let machine = machines.machine_handle("Machine").unwrap();
+    let state = |name| machines.state_handle(&machine, name).unwrap().state;
+    let stats = nested.transition_stats();
+    // The default is after the single transition, and so is the timeout
+    // of a state without default
+    assert_eq!(stats.count(&machine, &state("A"), 0), 1);
+    assert_eq!(stats.count(&machine, &state("B"), 1), 1);
+    assert_eq!(stats.count(&machine, &state("C"), 1), 1);
+    assert_eq!(stats.count(&machine, &state("C"), 0), 0);
+    assert_eq!(stats.iter().count(), 3);
+}
+
+#[test]
+fn timeout_index_follows_default() {
+    let never = || (100, builder::Target::End);
+    let state = builder::State {
+        timeout: Some((Duration::from_secs(1), builder::Target::End)),
+        default: Some(never()),
+        ..builder::State::new("A", Noop::new(), vec![never()])
+    };
+    let machines: Machines =
+        builder::StateMachines(vec![builder::StateMachine::new("Machine", vec![state])]).build();
+    let mut nested = NestedMachine::new_active();
+    let second = Duration::from_secs(1);
+    let _ = nested.update_timed(&machines, &mut (), second).unwrap();
+
+    let machine = machines.machine_handle("Machine").unwrap();
+    let a = machines.state_handle(&machine, "A").unwrap().state;
+    assert_eq!(nested.transition_stats().count(&machine, &a, 2), 1);
+}
@@ -0,0 +1,188 @@
+//! Tests for the ready-made transitions of `pure_hfsm::transition`
+use pure_hfsm::behavior::Noop;
+use pure_hfsm::builder;
+use pure_hfsm::label::NestedMachine;
+use pure_hfsm::transition::{AfterTicks, DecisionTable, Not, Random};
+use pure_hfsm::{Event, StateData, StateMachines, Target, Transition};
+
+fn rolls(random: &Random<()>, count: usize) -> Vec<Target> {
+    let mut no_data: StateData = Box::new(());
+    let mut machine_data: StateData = Box::new(());
+    let decide = |_| random.decide(&mut no_data, &mut machine_data, &());
+    (0..count).map(decide).collect()
+}
+
+#[test]
+fn random_is_deterministic_and_weighted() {
+    let choices = vec![
+        (7, Target::Complete),
+        (0, Target::Complete),
+        (3, Target::Continue),
+    ];
+    let first = rolls(&Random::new(choices.clone(), 42), 1000);
+    let second = rolls(&Random::new(choices, 42), 1000);
+
+    let same = first.iter().zip(&second).all(|pair| {
+        matches!(
+            pair,
+            (Target::Complete, Target::Complete) | (Target::Continue, Target::Continue)
+        )
+    });
+    assert!(same, "same seed rolled different targets");
+    let completes = first
+        .iter()
+        .filter(|t| matches!(t, Target::Complete))
+        .count();
+    assert!((620..780).contains(&completes), "{completes} out of 1000");
+
+    let never = Random::new(vec![(0, Target::Complete)], 42);
+    let fired = rolls(&never, 100)
+        .iter()
+        .any(|t| !matches!(t, Target::Continue));
+    assert!(!fired, "a choice with weight 0 was picked");
+}
+
+/// Fires `Complete` on its `n`th call
+struct OnCall(u32);
+impl Transition for OnCall {
+    type Context<'w, 's> = ();
+
+    fn decide<'w, 's>(&self, data: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        if data.is::<()>() {
+            *data = Box::new(0_u32);
+        }
+        let calls = data.downcast_mut::<u32>().unwrap();
+        *calls += 1;
+        if *calls == self.0 {
+            Target::Complete
+        } else {
+            Target::Continue
+        }
+    }
+}
+
+#[test]
+fn not_fires_when_inner_continues() {
+    let not = Not::new(OnCall(2), Target::Complete);
+    let mut data: StateData = Box::new(());
+    let mut machine_data: StateData = Box::new(());
+    let target = not.decide(&mut data, &mut machine_data, &());
+    assert!(matches!(target, Target::Complete));
+}
+
+#[test]
+fn not_continues_when_inner_fires() {
+    let not = Not::new(OnCall(2), Target::Complete);
+    let mut data: StateData = Box::new(());
+    let mut machine_data: StateData = Box::new(());
+    let _ = not.decide(&mut data, &mut machine_data, &());
+    // The inner transition kept its call count in the data of `Not`
+    let target = not.decide(&mut data, &mut machine_data, &());
+    assert!(matches!(target, Target::Continue));
+    assert_eq!(data.downcast_ref::<u32>(), Some(&2));
+}
+
+/// Fires `Complete` on any event, never when polled
+struct OnEvent;
+impl Transition for OnEvent {
+    type Context<'w, 's> = ();
+
+    fn decide<'w, 's>(&self, _: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        Target::Continue
+    }
+    fn decide_event<'w, 's>(
+        &self,
+        _: &Event,
+        _: &mut StateData,
+        _: &mut StateData,
+        _: &(),
+    ) -> Target {
+        Target::Complete
+    }
+}
+
+#[test]
+fn not_ignores_events() {
+    let event: Event = Box::new(());
+    let mut data: StateData = Box::new(());
+    let mut machine_data: StateData = Box::new(());
+    // Neither the inner transition reacting nor ignoring the event fires `Not`
+    let reacts = Not::new(OnEvent, Target::PopN(1));
+    let target = reacts.decide_event(&event, &mut data, &mut machine_data, &());
+    assert!(matches!(target, Target::Continue));
+    let ignores = Not::new(OnCall(1), Target::PopN(1));
+    let target = ignores.decide_event(&event, &mut data, &mut machine_data, &());
+    assert!(matches!(target, Target::Continue));
+    // The polled decision is still negated
+    let target = reacts.decide(&mut data, &mut machine_data, &());
+    assert!(matches!(target, Target::PopN(1)));
+}
+
+type NotAfter = ((u32, builder::Target), builder::Target);
+
+#[test]
+fn not_builds_and_round_trips() {
+    let goto = |name: &str| builder::Target::Goto(name.to_owned());
+    let start: NotAfter = ((2, goto("Start")), goto("Next"));
+    let states = vec![
+        builder::State::new("Start", Noop::new(), vec![start]),
+        builder::State::new("Next", Noop::new(), vec![]),
+    ];
+    let description = builder::StateMachines(vec![builder::StateMachine::new("Not", states)]);
+    let machines: StateMachines<Noop<()>, Not<AfterTicks<()>>> = description.build();
+
+    // The inner `AfterTicks` didn't fire on the first update, so `Not` did
+    let mut nested = NestedMachine::new_active();
+    let _ = nested.update(&machines, &mut ()).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("Next"));
+
+    let builder = builder::StateMachines::<Noop<()>, NotAfter>::try_from(&machines);
+    let states = &builder.unwrap().0[0].states;
+    assert!(matches!(
+        &states[0].transitions[..],
+        [((2, builder::Target::Goto(inner)), builder::Target::Goto(next))]
+            if inner == "Start" && next == "Next"
+    ));
+}
+
+/// Enemy distance and ammo
+struct World {
+    enemy_close: bool,
+    ammo: u32,
+}
+
+fn fight_or_flight() -> DecisionTable<World> {
+    DecisionTable::new()
+        .with_row(|w: &World, _| w.enemy_close && w.ammo < 3, Target::PopN(2))
+        .with_row(|w: &World, _| w.enemy_close, Target::Complete)
+}
+
+#[test]
+fn decision_table_first_matching_row_fires() {
+    let table = fight_or_flight();
+    let mut no_data: StateData = Box::new(());
+    let mut machine_data: StateData = Box::new(());
+    let mut decide = |enemy_close, ammo| {
+        let world = World { enemy_close, ammo };
+        table.decide(&mut no_data, &mut machine_data, &world)
+    };
+    // Both rows hold, the first one takes precedence
+    assert!(matches!(decide(true, 1), Target::PopN(2)));
+    assert!(matches!(decide(true, 5), Target::Complete));
+    assert!(matches!(decide(false, 1), Target::Continue));
+}
+
+#[test]
+fn decision_table_reads_machine_data() {
+    let table = DecisionTable::new().with_row(
+        |_: &World, data| data.downcast_ref::<u32>() == Some(&2),
+        Target::Complete,
+    );
+    let world = World {
+        enemy_close: false,
+        ammo: 0,
+    };
+    let (no_count, count): (StateData, StateData) = (Box::new(()), Box::new(2_u32));
+    assert_eq!(table.matching_row(&world, &no_count), None);
+    assert_eq!(table.matching_row(&world, &count), Some(0));
+}
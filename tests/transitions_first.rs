@@ -0,0 +1,58 @@
+//! States evaluating their transitions before or after their behavior.
+use pure_hfsm::prelude::*;
+
+/// Counts its updates in the context
+struct Count {
+    transitions_first: bool,
+}
+impl Behavior for Count {
+    type Context<'w, 's> = u32;
+
+    fn update<'w, 's>(&self, _: &mut StateData, _: &mut StateData, updates: &mut u32) {
+        *updates += 1;
+    }
+    fn transitions_first(&self) -> bool {
+        self.transitions_first
+    }
+}
+
+/// `Start` always goes to `End`, which stays there
+fn machines(transitions_first: bool) -> StateMachines<Count, Always<u32>> {
+    let state = |name: &str, transitions| {
+        builder::State::new(name, Count { transitions_first }, transitions)
+    };
+    let machines = builder::StateMachines(vec![builder::StateMachine::new(
+        "Machine",
+        vec![
+            state("Start", vec![builder::Target::Goto("End".to_owned())]),
+            state("End", vec![]),
+        ],
+    )]);
+    machines.build()
+}
+
+#[test]
+fn behavior_first_updates_the_state_being_left() {
+    let machines = machines(false);
+
+    let mut updates = 0;
+    let mut nested = NestedMachine::new_active();
+    let _ = nested.update(&machines, &mut updates).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("End"));
+    assert_eq!(updates, 1);
+    let _ = nested.update(&machines, &mut updates).unwrap();
+    assert_eq!(updates, 2);
+}
+
+#[test]
+fn transitions_first_skips_the_state_being_left() {
+    let machines = machines(true);
+
+    let mut updates = 0;
+    let mut nested = NestedMachine::new_active();
+    let _ = nested.update(&machines, &mut updates).unwrap();
+    assert_eq!(nested.current_state_name(&machines), Some("End"));
+    assert_eq!(updates, 0);
+    let _ = nested.update(&machines, &mut updates).unwrap();
+    assert_eq!(updates, 1);
+}
@@ -0,0 +1,67 @@
+//! `update_unchecked` once a `NestedMachine` is validated against a
+//! `StateMachines`.
+use pure_hfsm::prelude::*;
+use pure_hfsm::{Error, SmHandle};
+
+/// A machine cycling between `A` and `B`
+fn machine() -> builder::StateMachines<Noop<()>, builder::Target> {
+    let state = |name: &str, goto: &str| {
+        builder::State::new(
+            name,
+            Noop::new(),
+            vec![builder::Target::Goto(goto.to_owned())],
+        )
+    };
+    builder::StateMachines(vec![builder::StateMachine::new(
+        "Cycle",
+        vec![state("A", "B"), state("B", "A")],
+    )])
+}
+
+#[test]
+fn validated_machine_updates_unchecked() {
+    let machines: StateMachines<Noop<()>, Always<()>> = machine().build();
+
+    let mut nested = NestedMachine::new_active();
+    assert!(!nested.is_validated_against(&machines));
+    nested.validate_against(&machines).unwrap();
+    assert!(nested.is_validated_against(&machines));
+    for expected in ["B", "A", "B"] {
+        let complete = nested.update_unchecked(&machines, &mut ()).unwrap();
+        assert_eq!(complete, Complete::Running);
+        assert_eq!(nested.current_state_name(&machines), Some(expected));
+    }
+    assert!(nested.is_validated_against(&machines));
+}
+
+#[test]
+fn validation_is_per_version() {
+    let machines: StateMachines<Noop<()>, Always<()>> = machine().build();
+    let mut rebuilt: StateMachines<Noop<()>, Always<()>> = machine().build();
+    assert!(rebuilt.version() > machines.version());
+
+    let mut nested = NestedMachine::new_active();
+    nested.validate_against(&machines).unwrap();
+    assert!(!nested.is_validated_against(&rebuilt));
+
+    nested.validate_against(&rebuilt).unwrap();
+    let cycle = rebuilt.machine_handle("Cycle").unwrap();
+    let replacement = machine().0.remove(0);
+    rebuilt.replace_machine(&cycle, replacement).unwrap();
+    assert!(!nested.is_validated_against(&rebuilt));
+}
+
+#[test]
+fn entering_invalidates() {
+    let machines: StateMachines<Noop<()>, Always<()>> = machine().build();
+
+    let mut nested = NestedMachine::new_active();
+    nested.validate_against(&machines).unwrap();
+    nested.enter(&SmHandle::new(7));
+    assert!(!nested.is_validated_against(&machines));
+    // Falls back to the checked `update`
+    let result = nested.update_unchecked(&machines, &mut ());
+    assert!(matches!(result, Err(Error::BadMachineName(_))));
+    let result = nested.validate_against(&machines);
+    assert!(matches!(result, Err(Error::BadMachineName(_))));
+}
@@ -0,0 +1,50 @@
+//! Checking that no reachable state refers to a missing state or machine.
+use pure_hfsm::prelude::*;
+use pure_hfsm::Error;
+
+/// `Start` goes to `End`, which completes the machine
+fn machine() -> StateMachines<Noop<()>, Always<()>> {
+    let state = |name: &str, target| builder::State::new(name, Noop::new(), vec![target]);
+    let machines = builder::StateMachines(vec![builder::StateMachine::new(
+        "Machine",
+        vec![
+            state("Start", builder::Target::Goto("End".to_owned())),
+            state("End", builder::Target::End),
+        ],
+    )]);
+    machines.build()
+}
+
+#[test]
+fn built_machines_are_runnable() {
+    assert!(machine().verify_runnable().is_ok());
+}
+
+#[test]
+fn dangling_goto_is_reported() {
+    let dangling = machine().map_transitions(|transition| match transition.target() {
+        Target::Goto(_) => Always::new(Target::Goto(9.into())),
+        target => Always::new(target.clone()),
+    });
+
+    let errors = dangling.verify_runnable().unwrap_err();
+    assert!(matches!(
+        &errors[..],
+        [Error::BadStateName { state, .. }] if u8::from(state.clone()) == 9
+    ));
+    let mut nested = NestedMachine::new_active();
+    let _ = nested.update(&dangling, &mut ()).unwrap();
+    assert!(matches!(
+        nested.update(&dangling, &mut ()),
+        Err(Error::BadStateName { .. })
+    ));
+}
+
+#[test]
+fn dangling_enter_in_interrupts_is_reported() {
+    let interrupt = Always::new(Target::Enter(7.into()));
+    let machines = machine().with_interrupts(vec![interrupt]);
+
+    let errors = machines.verify_runnable().unwrap_err();
+    assert!(matches!(&errors[..], [Error::BadMachineName(_)]));
+}
@@ -0,0 +1,142 @@
+//! `StateMachines::walk_reachable` visits the states a machine can reach.
+use pure_hfsm::builder::{self, IntoTransition, NameMapping};
+use pure_hfsm::{
+    DescribeTransition, SHandle, SmHandle, StateData, StateMachines, Target, Transition,
+};
+
+/// Never fires, but describes `Target` as its target
+struct To(Target);
+impl Transition for To {
+    type Context<'w, 's> = ();
+
+    fn decide(&self, _: &mut StateData, _: &mut StateData, _: &()) -> Target {
+        Target::Continue
+    }
+}
+impl DescribeTransition for To {
+    fn targets(&self) -> Vec<Target> {
+        vec![self.0.clone()]
+    }
+}
+
+/// A target by name, or by handle to point out of the collection
+enum Desc {
+    Named(builder::Target),
+    Raw(Target),
+}
+impl IntoTransition<To> for Desc {
+    fn into_with(self, mapping: &NameMapping) -> To {
+        match self {
+            Desc::Named(target) => To(mapping.target(&target).expect("valid name")),
+            Desc::Raw(target) => To(target),
+        }
+    }
+}
+
+/// The behavior is the state's `machine/state` path
+type Machines = StateMachines<&'static str, To>;
+
+fn state(
+    path: &'static str,
+    region: u8,
+    transitions: Vec<Desc>,
+) -> builder::State<&'static str, Desc> {
+    let name = path.split_once('/').unwrap().1;
+    builder::State {
+        region,
+        ..builder::State::new(name, path, transitions)
+    }
+}
+
+fn machines() -> Machines {
+    let named = Desc::Named;
+    let goto = |state: &str| named(builder::Target::Goto(state.to_owned()));
+    let enter = |machine: &str| named(builder::Target::Enter(machine.to_owned()));
+    let enter_at = named(builder::Target::EnterAt(
+        "Other".to_owned(),
+        "Late".to_owned(),
+    ));
+    let outside = vec![
+        goto("Next"),
+        Desc::Raw(Target::Enter(SmHandle::new(50))),
+        Desc::Raw(Target::Goto(SHandle::new(50))),
+    ];
+    let machine = builder::StateMachine::new;
+    builder::StateMachines(vec![
+        machine(
+            "Root",
+            vec![
+                state("Root/Start", 0, outside),
+                state("Root/Next", 0, vec![enter("Child"), enter_at]),
+                state("Root/Unreached", 0, vec![]),
+                state("Root/Side", 1, vec![]),
+            ],
+        ),
+        machine(
+            "Child",
+            vec![
+                state("Child/First", 0, vec![]),
+                state("Child/Region", 1, vec![]),
+                state("Child/Unreached", 1, vec![]),
+            ],
+        ),
+        machine(
+            "Other",
+            vec![
+                state("Other/Early", 0, vec![]),
+                state("Other/Late", 0, vec![]),
+            ],
+        ),
+        machine("Far", vec![state("Far/Unreached", 0, vec![])]),
+    ])
+    .build()
+}
+
+/// The paths of the states reachable from `machine`, in visiting order
+fn reachable(machines: &Machines, machine: &str) -> Vec<&'static str> {
+    let mut visited = Vec::new();
+    let machine = machines.machine_handle(machine).unwrap();
+    machines.walk_reachable(&machine, |_, _, path, _| visited.push(*path));
+    visited
+}
+
+#[test]
+fn breadth_first_through_regions_and_enters() {
+    let machines = machines();
+    let expected = [
+        // Both regions of the root, then what the first region reaches,
+        // ignoring the targets of `Start` out of the collection
+        "Root/Start",
+        "Root/Side",
+        "Root/Next",
+        // All regions of an entered machine, only the state of an `EnterAt`
+        "Child/First",
+        "Child/Region",
+        "Other/Late",
+    ];
+    assert_eq!(reachable(&machines, "Root"), expected);
+}
+
+#[test]
+fn handles_match_paths() {
+    let machines = machines();
+    let root = machines.machine_handle("Root").unwrap();
+    let mut visited = Vec::new();
+    machines.walk_reachable(&root, |machine, state, path, transitions| {
+        visited.push((machine, state, *path, transitions.len()));
+    });
+
+    let child = machines.machine_handle("Child").unwrap();
+    let region = machines.state_handle(&child, "Region").unwrap().state;
+    assert!(visited.contains(&(child, region, "Child/Region", 0)));
+    let start = machines.state_handle(&root, "Start").unwrap().state;
+    assert_eq!(visited[0], (root, start, "Root/Start", 3));
+}
+
+#[test]
+fn unknown_machine_visits_nothing() {
+    let machines = machines();
+    let mut visited = 0;
+    machines.walk_reachable(&SmHandle::new(50), |_, _, _, _| visited += 1);
+    assert_eq!(visited, 0);
+}